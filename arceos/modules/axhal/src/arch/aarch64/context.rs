@@ -15,6 +15,16 @@ pub struct TrapFrame {
     pub spsr: u64,
 }
 
+impl TrapFrame {
+    /// Sets the instruction pointer that the trap returns to, with `arg` as
+    /// the first argument.
+    #[cfg(feature = "uspace")]
+    pub(crate) fn set_fault_return(&mut self, pc: usize, arg: usize) {
+        self.elr = pc as u64;
+        self.r[0] = arg as u64;
+    }
+}
+
 /// FP & SIMD registers.
 #[repr(C, align(16))]
 #[derive(Debug, Default)]
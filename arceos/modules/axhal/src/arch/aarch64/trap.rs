@@ -41,16 +41,17 @@ fn handle_irq_exception(_tf: &TrapFrame) {
     handle_trap!(IRQ, 0);
 }
 
-fn handle_instruction_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
+fn handle_instruction_abort(tf: &mut TrapFrame, iss: u64, is_user: bool) {
     let mut access_flags = MappingFlags::EXECUTE;
     if is_user {
         access_flags |= MappingFlags::USER;
     }
     let vaddr = va!(FAR_EL1.get() as usize);
+    let pc = tf.elr as usize;
 
     // Only handle Translation fault and Permission fault
     if !matches!(iss & 0b111100, 0b0100 | 0b1100) // IFSC or DFSC bits
-        || !handle_trap!(PAGE_FAULT, vaddr, access_flags, is_user)
+        || !crate::trap::handle_page_fault(tf, pc, vaddr, access_flags, is_user)
     {
         panic!(
             "Unhandled {} Instruction Abort @ {:#x}, fault_vaddr={:#x}, ISS={:#x} ({:?}):\n{:#x?}",
@@ -64,7 +65,7 @@ fn handle_instruction_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
     }
 }
 
-fn handle_data_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
+fn handle_data_abort(tf: &mut TrapFrame, iss: u64, is_user: bool) {
     let wnr = (iss & (1 << 6)) != 0; // WnR: Write not Read
     let cm = (iss & (1 << 8)) != 0; // CM: Cache maintenance
     let mut access_flags = if wnr & !cm {
@@ -76,10 +77,11 @@ fn handle_data_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
         access_flags |= MappingFlags::USER;
     }
     let vaddr = va!(FAR_EL1.get() as usize);
+    let pc = tf.elr as usize;
 
     // Only handle Translation fault and Permission fault
     if !matches!(iss & 0b111100, 0b0100 | 0b1100) // IFSC or DFSC bits
-        || !handle_trap!(PAGE_FAULT, vaddr, access_flags, is_user)
+        || !crate::trap::handle_page_fault(tf, pc, vaddr, access_flags, is_user)
     {
         panic!(
             "Unhandled {} Data Abort @ {:#x}, fault_vaddr={:#x}, ISS=0b{:08b} ({:?}):\n{:#x?}",
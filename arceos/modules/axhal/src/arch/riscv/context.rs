@@ -56,6 +56,14 @@ pub struct TrapFrame {
 }
 
 impl TrapFrame {
+    /// Sets the instruction pointer that the trap returns to, with `arg` as
+    /// the first argument.
+    #[cfg(feature = "uspace")]
+    pub(crate) fn set_fault_return(&mut self, pc: usize, arg: usize) {
+        self.sepc = pc;
+        self.regs.a0 = arg;
+    }
+
     /// Gets the 0th syscall argument.
     pub const fn arg0(&self) -> usize {
         self.regs.a0
@@ -39,6 +39,14 @@ impl TrapFrame {
     pub const fn is_user(&self) -> bool {
         self.cs & 0b11 == 3
     }
+
+    /// Sets the instruction pointer that the trap returns to, with `arg` as
+    /// the first argument.
+    #[cfg(feature = "uspace")]
+    pub(crate) fn set_fault_return(&mut self, pc: usize, arg: usize) {
+        self.rip = pc as u64;
+        self.rdi = arg as u64;
+    }
 }
 
 #[repr(C)]
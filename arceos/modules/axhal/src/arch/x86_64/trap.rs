@@ -9,14 +9,16 @@ core::arch::global_asm!(include_str!("trap.S"));
 const IRQ_VECTOR_START: u8 = 0x20;
 const IRQ_VECTOR_END: u8 = 0xff;
 
-fn handle_page_fault(tf: &TrapFrame) {
+fn handle_page_fault(tf: &mut TrapFrame) {
     let access_flags = err_code_to_flags(tf.error_code)
         .unwrap_or_else(|e| panic!("Invalid #PF error code: {:#x}", e));
     let vaddr = va!(unsafe { cr2() });
-    if !handle_trap!(PAGE_FAULT, vaddr, access_flags, tf.is_user()) {
+    let pc = tf.rip as usize;
+    let is_user = tf.is_user();
+    if !crate::trap::handle_page_fault(tf, pc, vaddr, access_flags, is_user) {
         panic!(
             "Unhandled {} #PF @ {:#x}, fault_vaddr={:#x}, error_code={:#x} ({:?}):\n{:#x?}",
-            if tf.is_user() { "user" } else { "kernel" },
+            if is_user { "user" } else { "kernel" },
             tf.rip,
             vaddr,
             tf.error_code,
@@ -27,7 +29,7 @@ fn handle_page_fault(tf: &TrapFrame) {
 }
 
 #[no_mangle]
-fn x86_trap_handler(tf: &TrapFrame) {
+fn x86_trap_handler(tf: &mut TrapFrame) {
     match tf.vector as u8 {
         PAGE_FAULT_VECTOR => handle_page_fault(tf),
         BREAKPOINT_VECTOR => debug!("#BP @ {:#x} ", tf.rip),
@@ -2,6 +2,7 @@
 
 use axalloc::global_allocator;
 use lazyinit::LazyInit;
+use page_table_entry::GenericPTE;
 use page_table_multiarch::PagingHandler;
 
 use crate::mem::{phys_to_virt, virt_to_phys, MemRegionFlags, PhysAddr, VirtAddr, PAGE_SIZE_4K};
@@ -37,6 +38,9 @@ pub struct PagingHandlerImpl;
 
 impl PagingHandler for PagingHandlerImpl {
     fn alloc_frame() -> Option<PhysAddr> {
+        if let Some(frame) = take_reserved_frame() {
+            return Some(frame);
+        }
         global_allocator()
             .alloc_pages(1, PAGE_SIZE_4K)
             .map(|vaddr| virt_to_phys(vaddr.into()))
@@ -57,12 +61,103 @@ cfg_if::cfg_if! {
     if #[cfg(target_arch = "x86_64")] {
         /// The architecture-specific page table.
         pub type PageTable = page_table_multiarch::x86_64::X64PageTable<PagingHandlerImpl>;
+        type PageTableEntry = page_table_entry::x86_64::X64PTE;
+        const PAGE_TABLE_LEVELS: usize = 4;
     } else if #[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))] {
         /// The architecture-specific page table.
         pub type PageTable = page_table_multiarch::riscv::Sv39PageTable<PagingHandlerImpl>;
+        type PageTableEntry = page_table_entry::riscv::Rv64PTE;
+        const PAGE_TABLE_LEVELS: usize = 3;
     } else if #[cfg(target_arch = "aarch64")]{
         /// The architecture-specific page table.
         pub type PageTable = page_table_multiarch::aarch64::A64PageTable<PagingHandlerImpl>;
+        type PageTableEntry = page_table_entry::aarch64::A64PTE;
+        const PAGE_TABLE_LEVELS: usize = 4;
+    }
+}
+
+/// Maximum number of page table nodes created by mapping a single page.
+pub const MAX_WALK_TABLES: usize = 3;
+
+/// The frames reserved for the page table walk in progress on this CPU, see
+/// [`with_table_reserve`]. Unused slots are 0.
+#[percpu::def_percpu]
+static WALK_RESERVE: [usize; MAX_WALK_TABLES] = [0; MAX_WALK_TABLES];
+
+/// Returns the number of page table nodes that mapping a page of
+/// `page_size` at `vaddr` would create in the page table rooted at `root`.
+pub fn missing_tables(root: PhysAddr, vaddr: VirtAddr, page_size: PageSize) -> usize {
+    let leaf_level = match page_size {
+        PageSize::Size4K => PAGE_TABLE_LEVELS - 1,
+        PageSize::Size2M => PAGE_TABLE_LEVELS - 2,
+        PageSize::Size1G => PAGE_TABLE_LEVELS - 3,
+    };
+    let mut table = root;
+    for level in 0..leaf_level {
+        let shift = 12 + 9 * (PAGE_TABLE_LEVELS - 1 - level);
+        let index = (vaddr.as_usize() >> shift) & 0x1ff;
+        // The page table nodes are accessed through the linear mapping.
+        let entry = unsafe { &*(phys_to_virt(table).as_ptr() as *const PageTableEntry).add(index) };
+        if entry.is_unused() {
+            // The next table and all the ones below it are created.
+            return leaf_level - level;
+        }
+        if entry.is_huge() {
+            // The map fails with `AlreadyMapped` before creating any table.
+            return 0;
+        }
+        table = entry.paddr();
+    }
+    0
+}
+
+/// Runs the page table walk `f`, e.g. a [`PageTable::map`] of a page of
+/// `page_size` at `vaddr` in the page table rooted at `root`, with the frames
+/// of the page table nodes it creates allocated beforehand by `alloc`.
+///
+/// Only the nodes missing from the walk are allocated, see
+/// [`missing_tables`], so nothing is allocated when they all exist. If
+/// `alloc` fails, the frames it has returned so far are freed, and
+/// [`PagingError::NoMemory`] is returned without running `f`, so that an
+/// out-of-memory never leaves a half-built walk behind. The frames not used by
+/// `f` are freed after it returns. Preemption and IRQs are disabled while `f`
+/// runs.
+pub fn with_table_reserve<R>(
+    root: PhysAddr,
+    vaddr: VirtAddr,
+    page_size: PageSize,
+    mut alloc: impl FnMut() -> Option<PhysAddr>,
+    f: impl FnOnce() -> R,
+) -> PagingResult<R> {
+    let _guard = kernel_guard::NoPreemptIrqSave::new();
+    let missing = missing_tables(root, vaddr, page_size);
+    // Preemption and IRQs are disabled, no one else touches the reserve of
+    // this CPU. `f` takes the frames through `PagingHandlerImpl::alloc_frame`.
+    let reserve = unsafe { WALK_RESERVE.current_ref_mut_raw() };
+    let filled = reserve[..missing]
+        .iter_mut()
+        .all(|slot| alloc().map(|frame| *slot = frame.as_usize()).is_some());
+    if !filled {
+        release_reserve();
+        return Err(PagingError::NoMemory);
+    }
+    let res = f();
+    release_reserve();
+    Ok(res)
+}
+
+fn take_reserved_frame() -> Option<PhysAddr> {
+    let _guard = kernel_guard::NoPreemptIrqSave::new();
+    let reserve = unsafe { WALK_RESERVE.current_ref_mut_raw() };
+    let slot = reserve.iter_mut().find(|frame| **frame != 0)?;
+    Some(PhysAddr::from(core::mem::take(slot)))
+}
+
+/// Frees the frames left in the reserve of this CPU.
+fn release_reserve() {
+    let reserve = unsafe { WALK_RESERVE.current_ref_mut_raw() };
+    for frame in reserve.iter_mut().filter(|frame| **frame != 0) {
+        PagingHandlerImpl::dealloc_frame(PhysAddr::from(core::mem::take(frame)));
     }
 }
 
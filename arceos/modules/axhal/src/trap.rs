@@ -4,7 +4,6 @@ use linkme::distributed_slice as def_trap_handler;
 use memory_addr::VirtAddr;
 use page_table_entry::MappingFlags;
 
-#[cfg(feature = "uspace")]
 use crate::arch::TrapFrame;
 
 pub use linkme::distributed_slice as register_trap_handler;
@@ -15,13 +14,57 @@ pub static IRQ: [fn(usize) -> bool];
 
 /// A slice of page fault handler functions.
 #[def_trap_handler]
-pub static PAGE_FAULT: [fn(VirtAddr, MappingFlags, bool) -> bool];
+pub static PAGE_FAULT: [fn(VirtAddr, MappingFlags, bool, &mut FaultContext) -> bool];
 
 /// A slice of syscall handler functions.
 #[cfg(feature = "uspace")]
 #[def_trap_handler]
 pub static SYSCALL: [fn(&TrapFrame, usize) -> isize];
 
+/// The trap context of a page fault, passed to the [`PAGE_FAULT`] handlers.
+///
+/// It is kept on the stack of the faulting task while the fault is handled,
+/// so it stays the one of the fault even if the task is migrated to another
+/// CPU in the handler. The default one is for a fault that does not come
+/// from a trap.
+#[derive(Debug, Default)]
+pub struct FaultContext<'a> {
+    pc: Option<usize>,
+    regs: &'a [usize],
+    #[cfg(feature = "uspace")]
+    divert: Option<(usize, usize)>,
+}
+
+impl FaultContext<'_> {
+    /// Returns the instruction pointer that triggered the page fault, if it
+    /// comes from a trap.
+    pub const fn pc(&self) -> Option<usize> {
+        self.pc
+    }
+
+    /// Returns the registers saved by the trap of the page fault, as the raw
+    /// words of its [`TrapFrame`] in order, or nothing if it does not come
+    /// from a trap.
+    pub const fn regs(&self) -> &[usize] {
+        self.regs
+    }
+
+    /// Diverts the return of the user page fault to `pc`, with `arg` as the
+    /// first argument, e.g., to a signal handler of the task.
+    ///
+    /// It takes effect only if the [`PAGE_FAULT`] handler then returns `true`.
+    #[cfg(feature = "uspace")]
+    pub fn divert(&mut self, pc: usize, arg: usize) {
+        self.divert = Some((pc, arg));
+    }
+
+    /// Returns the diversion set by [`divert`](Self::divert), if any.
+    #[cfg(feature = "uspace")]
+    pub const fn diversion(&self) -> Option<(usize, usize)> {
+        self.divert
+    }
+}
+
 #[allow(unused_macros)]
 macro_rules! handle_trap {
     ($trap:ident, $($args:tt)*) => {{
@@ -38,6 +81,35 @@ macro_rules! handle_trap {
     }}
 }
 
+/// Calls the external page fault handler, with the faulting instruction
+/// pointer `pc` and the registers of the trap frame `tf` in its context.
+///
+/// If the handler diverts a handled user fault, `tf` is updated to return
+/// to the diversion.
+pub(crate) fn handle_page_fault(
+    tf: &mut TrapFrame,
+    pc: usize,
+    vaddr: VirtAddr,
+    access_flags: MappingFlags,
+    is_user: bool,
+) -> bool {
+    let len = core::mem::size_of::<TrapFrame>() / core::mem::size_of::<usize>();
+    // The trap frame only has word-sized fields.
+    let regs = unsafe { core::slice::from_raw_parts(tf as *const _ as *const usize, len) };
+    let mut ctx = FaultContext {
+        pc: Some(pc),
+        regs,
+        #[cfg(feature = "uspace")]
+        divert: None,
+    };
+    let handled = handle_trap!(PAGE_FAULT, vaddr, access_flags, is_user, &mut ctx);
+    #[cfg(feature = "uspace")]
+    if let (true, true, Some((pc, arg))) = (handled, is_user, ctx.divert) {
+        tf.set_fault_return(pc, arg);
+    }
+    handled
+}
+
 /// Call the external syscall handler.
 #[cfg(feature = "uspace")]
 pub(crate) fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
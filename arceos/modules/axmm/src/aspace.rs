@@ -3,14 +3,20 @@ use core::fmt;
 use axerrno::{ax_err, AxError, AxResult};
 use axhal::{
     mem::phys_to_virt,
-    paging::{MappingFlags, PageTable},
+    paging::{MappingFlags, PageSize, PageTable},
 };
 use memory_addr::{
     is_aligned_4k, pa, MemoryAddr, PageIter4K, PhysAddr, VirtAddr, VirtAddrRange, PAGE_SIZE_4K,
 };
 use memory_set::{MemoryArea, MemorySet};
-use crate::backend::Backend;
+use crate::backend::{share_frame, Backend, BackingSource};
+use crate::fault::{FaultKind, PageFaultError, PageFaultResult};
+use crate::lazy::{LazyKind, LazyRegion, LazyRegions};
+use crate::once::PopulateOnce;
+use crate::region::{Region, RegionEntry, Regions};
+use crate::stack::StackGrowth;
 use crate::paging_err_to_ax_err;
+use crate::query_mapped;
 use crate::mapping_err_to_ax_err;
 use alloc::vec::Vec;
 
@@ -19,6 +25,13 @@ pub struct AddrSpace {
     va_range: VirtAddrRange,
     areas: MemorySet<Backend>,
     pt: PageTable,
+    stack: Option<StackGrowth>,
+    regions: Regions,
+    lazy: LazyRegions,
+    once: Vec<PopulateOnce>,
+    spurious: usize,
+    fault_budget: Option<usize>,
+    resident: usize,
 }
 
 impl AddrSpace {
@@ -53,12 +66,28 @@ impl AddrSpace {
             .contains_range(VirtAddrRange::from_start_size(start, size))
     }
 
+    /// Checks if the given address is a user address, i.e., inside both the
+    /// address space and the user part of the virtual address space.
+    ///
+    /// A page fault from user mode on any other address, e.g., a kernel
+    /// address mapped into the user page table, must never be serviced.
+    pub fn is_user_addr(&self, vaddr: VirtAddr) -> bool {
+        self.va_range.contains(vaddr) && crate::is_user_addr(vaddr)
+    }
+
     /// Creates a new empty address space.
     pub fn new_empty(base: VirtAddr, size: usize) -> AxResult<Self> {
         Ok(Self {
             va_range: VirtAddrRange::from_start_size(base, size),
             areas: MemorySet::new(),
             pt: PageTable::try_new().map_err(|_| AxError::NoMemory)?,
+            stack: None,
+            regions: Regions::new(),
+            lazy: LazyRegions::new(),
+            once: Vec::new(),
+            spurious: 0,
+            fault_budget: None,
+            resident: 0,
         })
     }
 
@@ -91,6 +120,18 @@ impl AddrSpace {
         self.areas.find_free_area(hint, size, limit)
     }
 
+    /// Checks that `[start, start + size)` is within the address space and
+    /// 4K aligned, for the methods mapping or changing a range.
+    fn validate_range(&self, start: VirtAddr, size: usize) -> AxResult {
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        if !start.is_aligned_4k() || !is_aligned_4k(size) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+        Ok(())
+    }
+
     /// Add a new linear mapping.
     ///
     /// The mapping is linear, i.e., `start_vaddr` is mapped to `start_paddr`,
@@ -107,10 +148,8 @@ impl AddrSpace {
         size: usize,
         flags: MappingFlags,
     ) -> AxResult {
-        if !self.contains_range(start_vaddr, size) {
-            return ax_err!(InvalidInput, "address out of range");
-        }
-        if !start_vaddr.is_aligned_4k() || !start_paddr.is_aligned_4k() || !is_aligned_4k(size) {
+        self.validate_range(start_vaddr, size)?;
+        if !start_paddr.is_aligned_4k() {
             return ax_err!(InvalidInput, "address not aligned");
         }
 
@@ -144,39 +183,454 @@ impl AddrSpace {
         flags: MappingFlags,
         populate: bool,
     ) -> AxResult {
-        if !self.contains_range(start, size) {
-            return ax_err!(InvalidInput, "address out of range");
-        }
-        if !start.is_aligned_4k() || !is_aligned_4k(size) {
-            return ax_err!(InvalidInput, "address not aligned");
-        }
+        self.validate_range(start, size)?;
 
         let area = MemoryArea::new(start, size, flags, Backend::new_alloc(populate));
         self.areas
             .map(area, &mut self.pt, false)
             .map_err(mapping_err_to_ax_err)?;
+        if populate {
+            self.resident += size / PAGE_SIZE_4K;
+        }
         Ok(())
     }
 
-    /// Removes mappings within the specified virtual address range.
+    /// Add a new demand-zero mapping.
+    ///
+    /// The address range is reserved but no physical frames are allocated.
+    /// The first access to each page triggers a page fault, which allocates a
+    /// zeroed frame and maps it with `flags`.
     ///
     /// Returns an error if the address range is out of the address space or not
     /// aligned.
-    pub fn unmap(&mut self, start: VirtAddr, size: usize) -> AxResult {
+    pub fn map_demand_zero(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+    ) -> AxResult {
+        self.map_alloc(start, size, flags, false)
+    }
+
+    /// Add a new demand-zero mapping, whose page faults are resolved by
+    /// mapping pages of `fault_size`.
+    ///
+    /// With a huge `fault_size`, e.g. [`PageSize::Size2M`], a fault maps the
+    /// whole huge page around the fault address, so that the following
+    /// accesses to it do not fault again. It falls back to 4K pages when the
+    /// huge page is not within the range, or no huge frame is available.
+    ///
+    /// Returns an error if the address range is out of the address space or not
+    /// aligned.
+    pub fn map_demand_zero_with(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        fault_size: PageSize,
+    ) -> AxResult {
+        self.validate_range(start, size)?;
+
+        let backend = Backend::new_alloc_with_fault_size(fault_size);
+        let area = MemoryArea::new(start, size, flags, backend);
+        self.areas
+            .map(area, &mut self.pt, false)
+            .map_err(mapping_err_to_ax_err)?;
+        Ok(())
+    }
+
+    /// Add a new mapping whose pages are given by `source` on page faults.
+    ///
+    /// It is for device or file backed regions: the first access to each page
+    /// maps the frame returned by `source` for the offset of the page, with
+    /// the flags it returns, e.g., an MMIO frame with
+    /// [`MappingFlags::DEVICE`]. No memory is allocated. Accesses not allowed
+    /// by `flags` are rejected before `source` is consulted.
+    ///
+    /// Returns an error if the address range is out of the address space or not
+    /// aligned.
+    pub fn map_at_fault(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        source: BackingSource,
+    ) -> AxResult {
+        self.validate_range(start, size)?;
+
+        let area = MemoryArea::new(start, size, flags, Backend::new_source(source));
+        self.areas
+            .map(area, &mut self.pt, false)
+            .map_err(mapping_err_to_ax_err)?;
+        Ok(())
+    }
+
+    /// Registers `[start, start + size)` as a guard range.
+    ///
+    /// The range is expected to be left unmapped. A page fault in it is
+    /// reported as [`PageFaultError::GuardPage`] instead of a generic failure.
+    ///
+    /// Returns an error if the address range is out of the address space or not
+    /// aligned.
+    pub fn add_guard_range(&mut self, start: VirtAddr, size: usize) -> AxResult {
+        self.validate_range(start, size)?;
+        let range = VirtAddrRange::from_start_size(start, size);
+        self.regions.update(range, |region| region.guard = true);
+        Ok(())
+    }
+
+    /// Checks if the given address is in a registered guard range.
+    pub fn is_guard_page(&self, vaddr: VirtAddr) -> bool {
+        self.region_of(vaddr).is_some_and(|region| region.guard)
+    }
+
+    /// Returns the region containing `vaddr`, with the attributes set by
+    /// [`add_guard_range`](Self::add_guard_range),
+    /// [`name_region`](Self::name_region) and
+    /// [`set_populate_once`](Self::set_populate_once), or `None` if none of
+    /// them covers it.
+    ///
+    /// The regions are sorted by their start address, so the lookup takes
+    /// `O(log n)` time.
+    pub fn region_of(&self, vaddr: VirtAddr) -> Option<&Region> {
+        self.regions.find(vaddr)
+    }
+
+    /// Names `[start, start + size)` for the diagnostics, e.g. `"user-heap"`
+    /// or `"mmio:uart"`, see [`region_name`](Self::region_name).
+    ///
+    /// The name does not change how the range is mapped, and is kept when
+    /// the range is unmapped. Naming a range again overrides the old names
+    /// in it.
+    ///
+    /// Returns an error if the address range is out of the address space.
+    pub fn name_region(&mut self, start: VirtAddr, size: usize, name: &'static str) -> AxResult {
         if !self.contains_range(start, size) {
             return ax_err!(InvalidInput, "address out of range");
         }
-        if !start.is_aligned_4k() || !is_aligned_4k(size) {
-            return ax_err!(InvalidInput, "address not aligned");
+        let range = VirtAddrRange::from_start_size(start, size);
+        self.regions
+            .update(range, |region| region.name = Some(name));
+        Ok(())
+    }
+
+    /// Returns the name of the region containing `vaddr` for the
+    /// diagnostics.
+    ///
+    /// It is the latest name given by [`name_region`](Self::name_region),
+    /// or `"anon"` for an unnamed address in an area, or `"unmapped"` for an
+    /// address in no area.
+    pub fn region_name(&self, vaddr: VirtAddr) -> &'static str {
+        if let Some(name) = self.region_of(vaddr).and_then(|region| region.name) {
+            name
+        } else if self.areas.find(vaddr).is_some() {
+            "anon"
+        } else {
+            "unmapped"
+        }
+    }
+
+    /// Writes the table of the areas to `f`, e.g. to see how far off a fault
+    /// in no area is.
+    ///
+    /// There is one line per area, sorted by address, with its start and end
+    /// addresses, its `rwxu` permissions and the
+    /// [`region_name`](Self::region_name) of its start, e.g.:
+    ///
+    /// ```text
+    /// 0x0000000000001000-0x0000000000003000 r-xu text
+    /// ```
+    pub fn dump_regions(&self, f: &mut dyn fmt::Write) -> fmt::Result {
+        for area in self.areas.iter() {
+            let entry = RegionEntry {
+                range: VirtAddrRange::new(area.start(), area.end()),
+                flags: area.flags(),
+                name: self.region_name(area.start()),
+            };
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+
+    /// Marks `[start, start + size)` as populated at most once, e.g. for a
+    /// one-shot scratch buffer.
+    ///
+    /// Once page faults have populated every page of the range, a fault that
+    /// would populate one of them again, after it has been unmapped, fails
+    /// with [`PageFaultError::Repopulated`] instead of silently mapping a
+    /// fresh page, since it usually means a logic bug.
+    ///
+    /// Returns an error if the address range is out of the address space or
+    /// not aligned.
+    pub fn set_populate_once(&mut self, start: VirtAddr, size: usize) -> AxResult {
+        self.validate_range(start, size)?;
+        let range = VirtAddrRange::from_start_size(start, size);
+        self.regions
+            .update(range, |region| region.populate_once = true);
+        self.once.push(PopulateOnce::new(range));
+        Ok(())
+    }
+
+    /// Whether a fault populating `vaddr` is rejected, see
+    /// [`set_populate_once`](Self::set_populate_once).
+    fn is_repopulated(&self, vaddr: VirtAddr) -> bool {
+        if !self
+            .region_of(vaddr)
+            .is_some_and(|region| region.populate_once)
+        {
+            return false;
+        }
+        self.once
+            .iter()
+            .any(|once| once.contains(vaddr) && once.is_done())
+    }
+
+    /// Records the page just populated at `vaddr` in the populate-once
+    /// regions, whatever its size.
+    fn record_populated(&mut self, vaddr: VirtAddr) {
+        if self.once.is_empty() {
+            return;
+        }
+        if let Ok((_, _, page_size)) = self.pt.query(vaddr) {
+            let size: usize = page_size.into();
+            let start = vaddr.align_down(size);
+            for once in self.once.iter_mut() {
+                once.record(start, size);
+            }
+        }
+    }
+
+    /// Registers `[start, start + size)` as a lazy region, whose page faults
+    /// are serviced as `kind` describes.
+    ///
+    /// A fault in the region is rejected unless `flags` allow the access and
+    /// the area mapped there services faults that way, e.g., a
+    /// [`LazyKind::Device`] region must be mapped by
+    /// [`map_at_fault`](Self::map_at_fault). Faults outside of any lazy
+    /// region are handled by the areas alone.
+    ///
+    /// Returns an error if the address range is out of the address space, not
+    /// aligned, or overlaps with another lazy region.
+    pub fn add_lazy_region(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        kind: LazyKind,
+    ) -> AxResult {
+        self.validate_range(start, size)?;
+        let range = VirtAddrRange::from_start_size(start, size);
+        if !self.lazy.add(LazyRegion { range, flags, kind }) {
+            return ax_err!(AlreadyExists, "lazy region overlapped");
+        }
+        Ok(())
+    }
+
+    /// Removes the lazy region registered with exactly
+    /// `[start, start + size)`.
+    ///
+    /// Returns an error if there is no such region.
+    pub fn remove_lazy_region(&mut self, start: VirtAddr, size: usize) -> AxResult {
+        let range = VirtAddrRange::from_start_size(start, size);
+        match self.lazy.remove(range) {
+            Some(_) => Ok(()),
+            None => ax_err!(NotFound, "lazy region not found"),
+        }
+    }
+
+    /// Returns the lazy region containing `vaddr`, see
+    /// [`add_lazy_region`](Self::add_lazy_region).
+    pub fn lazy_region(&self, vaddr: VirtAddr) -> Option<&LazyRegion> {
+        self.lazy.find(vaddr)
+    }
+
+    /// Checks a fault of `kind` at `vaddr` against the lazy region there, if
+    /// any.
+    fn check_lazy_region(
+        &self,
+        vaddr: VirtAddr,
+        access_flags: MappingFlags,
+        kind: FaultKind,
+    ) -> PageFaultResult {
+        let Some(region) = self.lazy.find(vaddr) else {
+            return Ok(());
+        };
+        if !region.flags.contains(access_flags) {
+            return Err(PageFaultError::Protection {
+                required: access_flags,
+                present: region.flags,
+            });
         }
+        match self.areas.find(vaddr) {
+            Some(area) if region.kind.serves(area.backend(), kind) => Ok(()),
+            // 区域的缺页策略与映射不符
+            _ => Err(PageFaultError::Unhandled),
+        }
+    }
 
+    /// Lets the stack `[bottom, top)` grow downward on page faults.
+    ///
+    /// A fault at most `guard` bytes below the bottom of the stack extends
+    /// the stack mapping by one page, until its size reaches `max_size`. The
+    /// stack must already be mapped.
+    pub fn set_stack_growth(
+        &mut self,
+        bottom: VirtAddr,
+        top: VirtAddr,
+        max_size: usize,
+        guard: usize,
+    ) {
+        self.stack = Some(StackGrowth::new(bottom, top, max_size, guard));
+    }
+
+    /// Tries to grow the stack by one page for a fault at `vaddr`.
+    fn grow_stack(&mut self, vaddr: VirtAddr, access_flags: MappingFlags) -> PageFaultResult {
+        let stack = self.stack.as_mut().ok_or(PageFaultError::NoRegion)?;
+        let new_bottom = stack.grow_page(vaddr).ok_or(PageFaultError::NoRegion)?;
+        let flags = self
+            .areas
+            .find(stack.bottom())
+            .map(|area| area.flags())
+            .ok_or(PageFaultError::NoRegion)?;
+        if !flags.contains(access_flags) {
+            return Err(PageFaultError::Protection {
+                required: access_flags,
+                present: flags,
+            });
+        }
+        let area = MemoryArea::new(new_bottom, PAGE_SIZE_4K, flags, Backend::new_alloc(true));
+        self.areas
+            .map(area, &mut self.pt, false)
+            .map_err(|_| PageFaultError::NoMemory)?;
+        debug!(
+            "grow stack: bottom {:#x} -> {:#x}",
+            stack.bottom(),
+            new_bottom
+        );
+        stack.set_bottom(new_bottom);
+        Ok(())
+    }
+
+    /// Shares all allocation mappings with `dst` in a copy-on-write manner.
+    ///
+    /// The same areas are created in `dst`, and the pages already populated
+    /// are mapped to the same physical frames, read-only in both address
+    /// spaces. The first write to such a page from either side triggers a
+    /// page fault, which gives the writer a private copy of the page.
+    ///
+    /// Returns an error if the areas overlap with existing ones in `dst`.
+    pub fn share_cow(&mut self, dst: &mut AddrSpace) -> AxResult {
+        for area in self.areas.iter() {
+            if !matches!(area.backend(), Backend::Alloc { .. }) {
+                continue;
+            }
+            let (start, size, flags) = (area.start(), area.size(), area.flags());
+            let new_area = MemoryArea::new(start, size, flags, Backend::new_alloc(false));
+            dst.areas
+                .map(new_area, &mut dst.pt, false)
+                .map_err(mapping_err_to_ax_err)?;
+
+            let ro_flags = flags - MappingFlags::WRITE;
+            for vaddr in PageIter4K::new(start, start + size).unwrap() {
+                let Some((frame, _, page_size)) = query_mapped(&self.pt, vaddr) else {
+                    continue; // 尚未分配的页，两边各自按需分配
+                };
+                if page_size.is_huge() {
+                    return ax_err!(Unsupported, "huge page in allocation mapping");
+                }
+                self.pt
+                    .protect(vaddr, ro_flags)
+                    .map_err(paging_err_to_ax_err)?
+                    .1
+                    .flush();
+                dst.pt
+                    .remap(vaddr, frame, ro_flags)
+                    .map_err(paging_err_to_ax_err)?
+                    .1
+                    .ignore();
+                share_frame(frame);
+                dst.resident += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes mappings within the specified virtual address range.
+    ///
+    /// Returns an error if the address range is out of the address space or not
+    /// aligned.
+    pub fn unmap(&mut self, start: VirtAddr, size: usize) -> AxResult {
+        self.validate_range(start, size)?;
+
+        let resident = self.resident_in(start, size);
         self.pt
             .unmap_region(start, size, true)
             .map_err(paging_err_to_ax_err)?
             .ignore();
+        debug_assert!(resident <= self.resident, "resident pages underflow");
+        self.resident = self.resident.saturating_sub(resident);
         Ok(())
     }
 
+    /// Removes all the areas, freeing the frames allocated for them, e.g.
+    /// when the task owning the address space exits.
+    pub fn clear(&mut self) {
+        self.areas.clear(&mut self.pt).unwrap();
+        self.resident = 0;
+    }
+
+    /// Returns the number of 4K pages backed by frames of the page
+    /// allocator, i.e. the resident set size of the address space.
+    ///
+    /// It counts the pages populated by [`map_alloc`](Self::map_alloc), by
+    /// page faults and shared by [`share_cow`](Self::share_cow), until they
+    /// are unmapped or the address space is [`clear`](Self::clear)ed. Linear
+    /// and [`map_at_fault`](Self::map_at_fault) mappings are not counted.
+    pub const fn resident_pages(&self) -> usize {
+        self.resident
+    }
+
+    /// Calls `f` with the physical address of each 4K frame counted by
+    /// [`resident_pages`](Self::resident_pages), in the order of the virtual
+    /// addresses, e.g. to check that a teardown frees them all.
+    ///
+    /// A huge page is given as its 4K frames. A frame shared by
+    /// [`share_cow`](Self::share_cow) is given by both address spaces.
+    pub fn for_each_resident_frame(&self, mut f: impl FnMut(PhysAddr)) {
+        for area in self.areas.iter() {
+            if !matches!(area.backend(), Backend::Alloc { .. }) {
+                continue;
+            }
+            for vaddr in PageIter4K::new(area.start(), area.end()).unwrap() {
+                if let Some((paddr, _, _)) = query_mapped(&self.pt, vaddr) {
+                    f(paddr); // 跳过按需映射的空表项
+                }
+            }
+        }
+    }
+
+    /// Whether a page is mapped at `vaddr`, e.g. populated by a fault.
+    ///
+    /// The pages reserved by a lazy mapping are not populated until their
+    /// first access, whatever the page table keeps for them.
+    pub fn is_populated(&self, vaddr: VirtAddr) -> bool {
+        query_mapped(&self.pt, vaddr).is_some()
+    }
+
+    /// 统计 `[start, start + size)` 中由页分配器提供的已映射的 4K 页
+    fn resident_in(&self, start: VirtAddr, size: usize) -> usize {
+        PageIter4K::new(start, start + size)
+            .unwrap()
+            .filter(|&vaddr| self.is_populated(vaddr) && self.is_alloc_backed(vaddr))
+            .count()
+    }
+
+    fn is_alloc_backed(&self, vaddr: VirtAddr) -> bool {
+        self.areas
+            .find(vaddr)
+            .is_some_and(|area| matches!(area.backend(), Backend::Alloc { .. }))
+    }
+
     /// To process data in this area with the given function.
     ///
     /// Now it supports reading and writing data in the given interval.
@@ -193,7 +647,7 @@ impl AddrSpace {
         for vaddr in PageIter4K::new(start.align_down_4k(), end_align_up)
             .expect("Failed to create page iterator")
         {
-            let (mut paddr, _, _) = self.pt.query(vaddr).map_err(|_| AxError::BadAddress)?;
+            let (mut paddr, _, _) = query_mapped(&self.pt, vaddr).ok_or(AxError::BadAddress)?;
 
             let mut copy_size = (size - cnt).min(PAGE_SIZE_4K);
 
@@ -240,12 +694,7 @@ impl AddrSpace {
     /// Returns an error if the address range is out of the address space or not
     /// aligned.
     pub fn protect(&mut self, start: VirtAddr, size: usize, flags: MappingFlags) -> AxResult {
-        if !self.contains_range(start, size) {
-            return ax_err!(InvalidInput, "address out of range");
-        }
-        if !start.is_aligned_4k() || !is_aligned_4k(size) {
-            return ax_err!(InvalidInput, "address not aligned");
-        }
+        self.validate_range(start, size)?;
 
         self.pt
             .protect_region(start, size, flags, true)
@@ -254,25 +703,295 @@ impl AddrSpace {
         Ok(())
     }
 
+    /// Splits the huge page mapped at `vaddr` into 4K pages mapping the same
+    /// frames with the same permissions, so that the permissions of each 4K
+    /// page can be changed, e.g. before [`protect`](Self::protect) on a part
+    /// of the huge page. Does nothing on a 4K page.
+    ///
+    /// Returns an error if `vaddr` is not mapped, or if no memory is left
+    /// for the new page table, in which case the huge page is kept.
+    pub fn split_huge_page(&mut self, vaddr: VirtAddr) -> AxResult {
+        let (frame, flags, page_size) = self.pt.query(vaddr).map_err(paging_err_to_ax_err)?;
+        if !page_size.is_huge() {
+            return Ok(());
+        }
+        let start = vaddr.align_down(page_size);
+        let frame = frame.align_down(page_size);
+        self.pt
+            .unmap(start)
+            .map_err(paging_err_to_ax_err)?
+            .2
+            .flush();
+        let size: usize = page_size.into();
+        for offset in (0..size).step_by(PAGE_SIZE_4K) {
+            match self
+                .pt
+                .map(start + offset, frame + offset, PageSize::Size4K, flags)
+            {
+                Ok(tlb) => tlb.ignore(),
+                Err(err) => {
+                    // 只有第一页需要分配新的页表，失败时恢复大页
+                    debug_assert_eq!(offset, 0);
+                    self.pt
+                        .map(start, frame, page_size, flags)
+                        .unwrap()
+                        .ignore();
+                    return Err(paging_err_to_ax_err(err));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Decodes the cause of a page fault at the given address.
+    ///
+    /// `access_flags` indicates the access type that caused the page fault.
+    pub fn fault_kind(&self, vaddr: VirtAddr, access_flags: MappingFlags) -> FaultKind {
+        let pte_flags = query_mapped(&self.pt, vaddr).map(|(_, flags, _)| flags);
+        FaultKind::decode(access_flags, pte_flags)
+    }
+
     /// Handles a page fault at the given address.
     ///
     /// `access_flags` indicates the access type that caused the page fault.
     ///
-    /// Returns `true` if the page fault is handled successfully (not a real
-    /// fault).
-    pub fn handle_page_fault(&mut self, vaddr: VirtAddr, access_flags: MappingFlags) -> bool {
+    /// Returns `Ok(())` if the page fault is handled successfully (not a real
+    /// fault), or the reason why it cannot be handled.
+    ///
+    /// The lazy region containing `vaddr`, if any, is looked up first, and
+    /// decides whether the fault may be serviced, see
+    /// [`add_lazy_region`](Self::add_lazy_region).
+    ///
+    /// A fault on a page mapped without some permissions of its area, e.g.,
+    /// left read-only in a writable area by [`protect`](Self::protect), gets
+    /// the permissions of the area in place, without allocating a frame
+    /// unless the page is shared copy-on-write. A huge page is
+    /// [split](Self::split_huge_page) first, so that only the faulting 4K
+    /// page gets them and the rest of the huge page keeps its permissions.
+    ///
+    /// A fault on a page already mapped with the access permissions, e.g.,
+    /// mapped by another CPU faulting on the same page meanwhile, is a
+    /// spurious fault. It succeeds without touching the mapping, and is
+    /// counted by [`spurious_faults`](Self::spurious_faults).
+    ///
+    /// A fault on an unmapped page takes one from the
+    /// [fault budget](Self::set_fault_budget) once resolved, and fails with
+    /// [`PageFaultError::BudgetExceeded`] if none is left, or with
+    /// [`PageFaultError::Repopulated`] in a
+    /// [populate-once](Self::set_populate_once) region fully populated
+    /// before.
+    pub fn try_handle_page_fault(
+        &mut self,
+        vaddr: VirtAddr,
+        access_flags: MappingFlags,
+    ) -> PageFaultResult {
+        #[cfg(feature = "fault-inject")]
+        if let Some(err) = crate::inject::take_page_fault() {
+            return Err(err);
+        }
+        self.resolve_page(vaddr, access_flags, true)
+    }
+
+    /// Resolves a page fault like [`try_handle_page_fault`], or populates
+    /// the page in advance if `is_fault` is `false`, which is then not
+    /// counted as a spurious fault and does not take from the fault budget.
+    ///
+    /// [`try_handle_page_fault`]: Self::try_handle_page_fault
+    fn resolve_page(
+        &mut self,
+        vaddr: VirtAddr,
+        access_flags: MappingFlags,
+        is_fault: bool,
+    ) -> PageFaultResult {
         if !self.va_range.contains(vaddr) {
+            return Err(PageFaultError::NoRegion);
+        }
+        if self.is_guard_page(vaddr) {
+            return Err(PageFaultError::GuardPage);
+        }
+        let pte = query_mapped(&self.pt, vaddr);
+        let pte_flags = pte.map(|(_, flags, _)| flags);
+        if pte_flags.is_some_and(|flags| flags.contains(access_flags)) {
+            // 已被其他 CPU 上的缺页处理映射，不能重复分配
+            if is_fault {
+                self.spurious += 1;
+            }
+            return Ok(());
+        }
+        let kind = FaultKind::decode(access_flags, pte_flags);
+        // 先查询登记的延迟映射区域
+        self.check_lazy_region(vaddr, access_flags, kind)?;
+        let populates = pte_flags.is_none();
+        if is_fault && populates && self.fault_budget == Some(0) {
+            return Err(PageFaultError::BudgetExceeded);
+        }
+        if populates && self.is_repopulated(vaddr) {
+            return Err(PageFaultError::Repopulated);
+        }
+        let is_huge = pte.is_some_and(|(_, _, page_size)| page_size.is_huge());
+        if is_huge
+            && self
+                .areas
+                .find(vaddr)
+                .is_some_and(|area| area.flags().contains(access_flags))
+        {
+            self.split_huge_page(vaddr).map_err(|err| match err {
+                AxError::NoMemory => PageFaultError::NoMemory,
+                _ => PageFaultError::Unhandled,
+            })?;
+        }
+        let res = if let Some(area) = self.areas.find(vaddr) {
+            let orig_flags = area.flags();
+            if !orig_flags.contains(access_flags) {
+                return Err(PageFaultError::Protection {
+                    required: access_flags,
+                    present: orig_flags,
+                });
+            }
+            // 区域允许该访问，页表项权限不足时由后端更新
+            area.backend()
+                .handle_page_fault(vaddr, kind, area.va_range(), orig_flags, &mut self.pt)
+        } else if !kind.is_handleable() {
+            Err(PageFaultError::Protection {
+                required: access_flags,
+                present: pte_flags.unwrap_or(MappingFlags::empty()),
+            })
+        } else {
+            // 栈下方的缺页，尝试扩展栈
+            self.grow_stack(vaddr, access_flags)
+        };
+        if res.is_ok() && populates {
+            if let Some(budget) = self.fault_budget.as_mut().filter(|_| is_fault) {
+                *budget -= 1; // 映射了新页，消耗预算
+            }
+            self.record_populated(vaddr);
+            if self.is_alloc_backed(vaddr) {
+                let (_, _, page_size) = self.pt.query(vaddr).unwrap();
+                self.resident += usize::from(page_size) / PAGE_SIZE_4K;
+            }
+        }
+        res
+    }
+
+    /// Checks whether a page fault at the given address would be resolved by
+    /// [`try_handle_page_fault`](Self::try_handle_page_fault), without
+    /// changing anything.
+    ///
+    /// `access_flags` indicates the access type that caused the page fault,
+    /// and `is_user` whether it is from user mode, which requires the `USER`
+    /// permission. A recoverable fault may still fail if no memory is
+    /// available.
+    pub fn fault_is_recoverable(
+        &self,
+        vaddr: VirtAddr,
+        access_flags: MappingFlags,
+        is_user: bool,
+    ) -> bool {
+        let access_flags = if is_user {
+            access_flags | MappingFlags::USER
+        } else {
+            access_flags
+        };
+        if !self.va_range.contains(vaddr) || self.is_guard_page(vaddr) {
+            return false;
+        }
+        let pte_flags = query_mapped(&self.pt, vaddr).map(|(_, flags, _)| flags);
+        if pte_flags.is_some_and(|flags| flags.contains(access_flags)) {
+            return true; // 虚假的缺页
+        }
+        let kind = FaultKind::decode(access_flags, pte_flags);
+        if self.check_lazy_region(vaddr, access_flags, kind).is_err() {
             return false;
         }
+        if pte_flags.is_none() && (self.fault_budget == Some(0) || self.is_repopulated(vaddr)) {
+            return false; // 预算已用完，或不允许再次填充
+        }
         if let Some(area) = self.areas.find(vaddr) {
             let orig_flags = area.flags();
-            if orig_flags.contains(access_flags) {
-                return area
-                    .backend()
-                    .handle_page_fault(vaddr, orig_flags, &mut self.pt);
+            orig_flags.contains(access_flags) && area.backend().can_handle_fault(kind)
+        } else if !kind.is_handleable() {
+            false
+        } else {
+            // 栈下方的缺页，检查能否扩展栈
+            self.stack.as_ref().is_some_and(|stack| {
+                stack.grow_page(vaddr).is_some()
+                    && self
+                        .areas
+                        .find(stack.bottom())
+                        .is_some_and(|area| area.flags().contains(access_flags))
+            })
+        }
+    }
+
+    /// Returns the number of spurious page faults, see
+    /// [`try_handle_page_fault`](Self::try_handle_page_fault).
+    pub const fn spurious_faults(&self) -> usize {
+        self.spurious
+    }
+
+    /// Limits the number of pages that page faults may map from now on, or
+    /// removes the limit with `None`, which is the default.
+    ///
+    /// Each resolved fault on an unmapped page takes one from the budget,
+    /// whatever the size of the page mapped. The pages populated in advance
+    /// by [`populate_range`](Self::populate_range) are not faults, and are
+    /// not limited. Once it is used up, such faults
+    /// fail with [`PageFaultError::BudgetExceeded`], so that a task demand
+    /// paging a huge sparse array cannot take all the memory. Faults on
+    /// mapped pages, e.g. copy-on-write or spurious ones, are not limited.
+    pub fn set_fault_budget(&mut self, pages: Option<usize>) {
+        self.fault_budget = pages;
+    }
+
+    /// Returns the number of pages that page faults may still map, or `None`
+    /// if unlimited, see [`set_fault_budget`](Self::set_fault_budget).
+    pub const fn fault_budget(&self) -> Option<usize> {
+        self.fault_budget
+    }
+
+    /// Populates the pages in `[start, start + size)` in advance, as if each
+    /// of them were accessed with `access_flags`, so that the accesses later
+    /// do not fault.
+    ///
+    /// Pages already mapped with `access_flags` are skipped. Others go
+    /// through the same path as [`try_handle_page_fault`](Self::try_handle_page_fault),
+    /// but are not counted as spurious faults nor limited by the fault budget.
+    ///
+    /// Returns the number of pages populated. Stops at the first page that
+    /// cannot be populated, e.g., with [`PageFaultError::NoMemory`], and
+    /// returns the reason. The pages populated before stay mapped.
+    pub fn populate_range(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        access_flags: MappingFlags,
+    ) -> Result<usize, PageFaultError> {
+        if !self.contains_range(start, size) {
+            return Err(PageFaultError::NoRegion);
+        }
+        let end = (start + size).align_up_4k();
+        let mut populated = 0;
+        for vaddr in PageIter4K::new(start.align_down_4k(), end).unwrap() {
+            if let Some((_, flags, _)) = query_mapped(&self.pt, vaddr) {
+                if flags.contains(access_flags) {
+                    continue; // 已经映射
+                }
             }
+            self.resolve_page(vaddr, access_flags, false)?;
+            populated += 1;
         }
-        false
+        Ok(populated)
+    }
+
+    /// Handles a page fault at the given address.
+    ///
+    /// `access_flags` indicates the access type that caused the page fault.
+    ///
+    /// Returns `true` if the page fault is handled successfully (not a real
+    /// fault). See [`try_handle_page_fault`](Self::try_handle_page_fault) for
+    /// the reason of a failure.
+    pub fn handle_page_fault(&mut self, vaddr: VirtAddr, access_flags: MappingFlags) -> bool {
+        self.try_handle_page_fault(vaddr, access_flags).is_ok()
     }
 
     pub fn translated_byte_buffer(
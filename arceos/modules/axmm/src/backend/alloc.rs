@@ -1,11 +1,64 @@
+use alloc::collections::BTreeMap;
+
 use axalloc::global_allocator;
 use axhal::mem::{phys_to_virt, virt_to_phys};
-use axhal::paging::{MappingFlags, PageSize, PageTable};
-use memory_addr::{PageIter4K, PhysAddr, VirtAddr, PAGE_SIZE_4K};
+use axhal::paging::{with_table_reserve, MappingFlags, PageSize, PageTable, PagingError};
+use kspin::SpinNoIrq;
+use memory_addr::{MemoryAddr, PageIter4K, PhysAddr, VirtAddr, VirtAddrRange, PAGE_SIZE_4K};
 
 use super::Backend;
+use crate::fault::{FaultKind, PageFaultError, PageFaultResult};
+use crate::populate::Yielder;
+use crate::query_mapped;
+
+/// Reference counts of the frames shared by copy-on-write mappings.
+static FRAME_REFS: SpinNoIrq<FrameRefs> = SpinNoIrq::new(FrameRefs::new());
+
+/// Number of owners of each shared physical frame.
+///
+/// Frames not in the table have a single owner.
+pub(crate) struct FrameRefs(BTreeMap<PhysAddr, usize>);
+
+impl FrameRefs {
+    pub(crate) const fn new() -> Self {
+        Self(BTreeMap::new())
+    }
+
+    /// Adds an owner to the frame.
+    pub(crate) fn share(&mut self, frame: PhysAddr) {
+        *self.0.entry(frame).or_insert(1) += 1;
+    }
+
+    /// Removes an owner from the frame.
+    ///
+    /// Returns `true` if it was the last owner, i.e., the frame can be freed.
+    pub(crate) fn release(&mut self, frame: PhysAddr) -> bool {
+        match self.0.get_mut(&frame) {
+            Some(n) if *n > 2 => *n -= 1,
+            Some(_) => {
+                self.0.remove(&frame);
+            }
+            None => return true,
+        }
+        false
+    }
+
+    /// Whether the frame is owned by more than one mapping.
+    pub(crate) fn is_shared(&self, frame: PhysAddr) -> bool {
+        self.0.contains_key(&frame)
+    }
+}
+
+/// Adds an owner to a frame shared by copy-on-write mappings.
+pub(crate) fn share_frame(frame: PhysAddr) {
+    FRAME_REFS.lock().share(frame);
+}
 
 fn alloc_frame(zeroed: bool) -> Option<PhysAddr> {
+    #[cfg(feature = "fault-inject")]
+    if crate::inject::take_oom() {
+        return None;
+    }
     let vaddr = VirtAddr::from(global_allocator().alloc_pages(1, PAGE_SIZE_4K).ok()?);
     if zeroed {
         unsafe { core::ptr::write_bytes(vaddr.as_mut_ptr(), 0, PAGE_SIZE_4K) };
@@ -14,15 +67,131 @@ fn alloc_frame(zeroed: bool) -> Option<PhysAddr> {
     Some(paddr)
 }
 
+/// Allocates a zeroed huge frame of `page_size`, aligned to its size.
+fn alloc_huge_frame(page_size: PageSize) -> Option<PhysAddr> {
+    #[cfg(feature = "fault-inject")]
+    if crate::inject::take_oom() {
+        return None;
+    }
+    let size: usize = page_size.into();
+    let vaddr = global_allocator()
+        .alloc_pages(size / PAGE_SIZE_4K, size)
+        .ok()?;
+    unsafe { core::ptr::write_bytes(vaddr as *mut u8, 0, size) };
+    Some(virt_to_phys(vaddr.into()))
+}
+
+/// Allocates a frame for a page table node, see [`with_table_reserve`].
+fn alloc_table_frame() -> Option<PhysAddr> {
+    #[cfg(feature = "fault-inject")]
+    if crate::inject::take_table_oom() {
+        return None;
+    }
+    let vaddr = global_allocator().alloc_pages(1, PAGE_SIZE_4K).ok()?;
+    Some(virt_to_phys(vaddr.into()))
+}
+
+/// Frees a huge frame allocated by [`alloc_huge_frame`].
+fn dealloc_huge_frame(frame: PhysAddr, page_size: PageSize) {
+    let size: usize = page_size.into();
+    let vaddr = phys_to_virt(frame);
+    global_allocator().dealloc_pages(vaddr.as_usize(), size / PAGE_SIZE_4K);
+}
+
 fn dealloc_frame(frame: PhysAddr) {
+    if !FRAME_REFS.lock().release(frame) {
+        return; // 仍被其他写时复制映射共享
+    }
     let vaddr = phys_to_virt(frame);
     global_allocator().dealloc_pages(vaddr.as_usize(), 1);
 }
 
+/// Maps `num_pages` zeroed 4K frames from `start` with `flags`.
+///
+/// This is the only place where the allocation backend maps new frames, both
+/// for populated mappings and on page faults. The empty entries left by lazy
+/// mappings are replaced. If a frame cannot be allocated or mapped midway,
+/// the pages mapped by this call are unmapped and their frames freed, so
+/// nothing is leaked. The page table nodes missing for each page are
+/// allocated before walking the table, so that it fails with
+/// [`PageFaultError::PageTableOom`] without any node half built.
+///
+/// It yields the CPU between the pages as set by
+/// [`set_populate_yield`](crate::set_populate_yield).
+pub(crate) fn map_pages(
+    pt: &mut PageTable,
+    start: VirtAddr,
+    num_pages: usize,
+    flags: MappingFlags,
+) -> PageFaultResult {
+    let mut yielder = Yielder::current();
+    for i in 0..num_pages {
+        let vaddr = start + i * PAGE_SIZE_4K;
+        let res = match alloc_frame(true) {
+            Some(frame) => map_frame(pt, vaddr, frame, flags).inspect_err(|_| dealloc_frame(frame)),
+            None => Err(PageFaultError::NoMemory),
+        };
+        if let Err(err) = res {
+            // 回滚本次已映射的页
+            unmap_pages(pt, start, i);
+            return Err(err);
+        }
+        yielder.page_done();
+    }
+    Ok(())
+}
+
+fn map_frame(
+    pt: &mut PageTable,
+    vaddr: VirtAddr,
+    frame: PhysAddr,
+    flags: MappingFlags,
+) -> PageFaultResult {
+    let root = pt.root_paddr();
+    let res = with_table_reserve(root, vaddr, PageSize::Size4K, alloc_table_frame, || {
+        pt.map(vaddr, frame, PageSize::Size4K, flags)
+    })
+    .map_err(|_| PageFaultError::PageTableOom)?;
+    match res {
+        // 之前没有映射，无需刷新 TLB
+        Ok(tlb) => tlb.ignore(),
+        // 按需映射预先建立的空表项
+        Err(PagingError::AlreadyMapped) => pt
+            .remap(vaddr, frame, flags)
+            .map_err(|_| PageFaultError::Unhandled)?
+            .1
+            .flush(),
+        Err(_) => return Err(PageFaultError::Unhandled),
+    }
+    Ok(())
+}
+
+/// 撤销 [`map_pages`] 映射的前 `num_pages` 页
+fn unmap_pages(pt: &mut PageTable, start: VirtAddr, num_pages: usize) {
+    for i in 0..num_pages {
+        if let Ok((frame, _, tlb)) = pt.unmap(start + i * PAGE_SIZE_4K) {
+            tlb.flush();
+            dealloc_frame(frame);
+        }
+    }
+}
+
 impl Backend {
     /// Creates a new allocation mapping backend.
     pub const fn new_alloc(populate: bool) -> Self {
-        Self::Alloc { populate }
+        Self::Alloc {
+            populate,
+            fault_size: PageSize::Size4K,
+        }
+    }
+
+    /// Creates a new lazy allocation mapping backend, which maps pages of
+    /// `fault_size` on page faults if possible.
+    pub const fn new_alloc_with_fault_size(fault_size: PageSize) -> Self {
+        Self::Alloc {
+            populate: false,
+            fault_size,
+        }
     }
 
     pub(crate) fn map_alloc(
@@ -32,25 +201,21 @@ impl Backend {
         flags: MappingFlags,
         pt: &mut PageTable,
         populate: bool,
+        fault_size: PageSize,
     ) -> bool {
         debug!(
-            "map_alloc: [{:#x}, {:#x}) {:?} (populate={})",
+            "map_alloc: [{:#x}, {:#x}) {:?} (populate={}, fault_size={:?})",
             start,
             start + size,
             flags,
-            populate
+            populate,
+            fault_size
         );
         if populate {
-            // allocate all possible physical frames for populated mapping.
-            for addr in PageIter4K::new(start, start + size).unwrap() {
-                if let Some(frame) = alloc_frame(true) {
-                    if let Ok(tlb) = pt.map(addr, frame, PageSize::Size4K, flags) {
-                        tlb.ignore(); // TLB flush on map is unnecessary, as there are no outdated mappings.
-                    } else {
-                        return false;
-                    }
-                }
-            }
+            // allocate all physical frames for populated mapping.
+            map_pages(pt, start, size / PAGE_SIZE_4K, flags).is_ok()
+        } else if fault_size.is_huge() {
+            // 不预先建立空表项，否则缺页时无法再映射为大页
             true
         } else {
             // Map to a empty entry for on-demand mapping.
@@ -70,14 +235,20 @@ impl Backend {
     ) -> bool {
         debug!("unmap_alloc: [{:#x}, {:#x})", start, start + size);
         for addr in PageIter4K::new(start, start + size).unwrap() {
+            let populated = query_mapped(pt, addr).is_some();
             if let Ok((frame, page_size, tlb)) = pt.unmap(addr) {
                 // Deallocate the physical frame if there is a mapping in the
                 // page table.
+                tlb.flush();
+                if !populated {
+                    continue; // 按需映射的空表项，没有物理页
+                }
                 if page_size.is_huge() {
-                    return false;
+                    // 大页只会由缺页处理映射，不会被写时复制共享
+                    dealloc_huge_frame(frame, page_size);
+                } else {
+                    dealloc_frame(frame);
                 }
-                tlb.flush();
-                dealloc_frame(frame);
             } else {
                 // Deallocation is needn't if the page is not mapped.
             }
@@ -88,21 +259,174 @@ impl Backend {
     pub(crate) fn handle_page_fault_alloc(
         &self,
         vaddr: VirtAddr,
+        kind: FaultKind,
         orig_flags: MappingFlags,
         pt: &mut PageTable,
         populate: bool,
-    ) -> bool {
-        if populate {
-            false // Populated mappings should not trigger page faults.
-        } else if let Some(frame) = alloc_frame(true) {
-            // Allocate a physical frame lazily and map it to the fault address.
-            // `vaddr` does not need to be aligned. It will be automatically
-            // aligned during `pt.remap` regardless of the page size.
-            pt.remap(vaddr, frame, orig_flags)
-                .map(|(_, tlb)| tlb.flush())
-                .is_ok()
+    ) -> PageFaultResult {
+        if !kind.is_unmapped() {
+            // The page is mapped without some permissions of the area.
+            self.handle_upgrade_fault_alloc(vaddr, kind, orig_flags, pt)
+        } else if populate {
+            Err(PageFaultError::Unhandled) // Populated mappings should not trigger page faults.
         } else {
-            false
+            // Allocate a physical frame lazily and map it to the fault address.
+            map_pages(pt, vaddr.align_down_4k(), 1, orig_flags).inspect_err(|err| {
+                if matches!(err, PageFaultError::NoMemory | PageFaultError::PageTableOom) {
+                    warn!(
+                        "handle_page_fault_alloc: no memory for {:#x}: {:?}",
+                        vaddr, err
+                    );
+                }
+            })
+        }
+    }
+
+    /// Handles a fault on an unmapped page of a lazy mapping with huge
+    /// `fault_size`.
+    ///
+    /// The whole huge page around `vaddr` is mapped if it is within `area`,
+    /// not partially mapped yet, and a huge frame can be allocated. Otherwise
+    /// it falls back to mapping a 4K page.
+    pub(crate) fn handle_huge_fault_alloc(
+        &self,
+        vaddr: VirtAddr,
+        area: VirtAddrRange,
+        orig_flags: MappingFlags,
+        pt: &mut PageTable,
+        fault_size: PageSize,
+    ) -> PageFaultResult {
+        let huge_start = vaddr.align_down(fault_size);
+        let huge_range = VirtAddrRange::from_start_size(huge_start, fault_size.into());
+        if area.contains_range(huge_range) {
+            if let Some(frame) = alloc_huge_frame(fault_size) {
+                let root = pt.root_paddr();
+                let res =
+                    with_table_reserve(root, huge_start, fault_size, alloc_table_frame, || {
+                        pt.map(huge_start, frame, fault_size, orig_flags)
+                    });
+                match res.and_then(|res| res) {
+                    Ok(tlb) => {
+                        tlb.flush();
+                        return Ok(());
+                    }
+                    // 已有 4K 页映射在这个范围内，或没有内存建立页表
+                    Err(_) => dealloc_huge_frame(frame, fault_size),
+                }
+            } else {
+                debug!("handle_huge_fault_alloc: fall back to 4K for {:#x}", vaddr);
+            }
+        }
+        map_pages(pt, vaddr.align_down_4k(), 1, orig_flags).inspect_err(|err| {
+            if matches!(err, PageFaultError::NoMemory | PageFaultError::PageTableOom) {
+                warn!(
+                    "handle_huge_fault_alloc: no memory for {:#x}: {:?}",
+                    vaddr, err
+                );
+            }
+        })
+    }
+
+    /// Gives the mapped page at `vaddr` the permissions of its area.
+    ///
+    /// A write to a frame shared by copy-on-write mappings gets a private
+    /// copy. Otherwise, the page table entry is updated in place, e.g., for
+    /// the last owner of a copy-on-write frame, or a page whose permissions
+    /// were reduced by `protect` below the ones of the area.
+    fn handle_upgrade_fault_alloc(
+        &self,
+        vaddr: VirtAddr,
+        kind: FaultKind,
+        orig_flags: MappingFlags,
+        pt: &mut PageTable,
+    ) -> PageFaultResult {
+        let Ok((frame, _, page_size)) = pt.query(vaddr) else {
+            return Err(PageFaultError::Unhandled);
+        };
+        // 大页不会被写时复制共享
+        if kind == FaultKind::WriteProtection && !page_size.is_huge() {
+            if let Some(res) = self.handle_cow_fault_alloc(vaddr, frame, orig_flags, pt) {
+                return res;
+            }
+        }
+        pt.protect(vaddr, orig_flags)
+            .map(|(_, tlb)| tlb.flush())
+            .map_err(|_| PageFaultError::Unhandled)
+    }
+
+    /// Maps a private copy of the shared frame at `vaddr`, or returns `None`
+    /// if the frame is not shared (anymore), so that it is kept in place.
+    ///
+    /// The other owners are in other address spaces, whose faults do not
+    /// take the lock of this one. The reference counts stay locked from the
+    /// check to the release of the shared frame, so that two owners faulting
+    /// at the same time do not both copy it, and the last one keeps it.
+    fn handle_cow_fault_alloc(
+        &self,
+        vaddr: VirtAddr,
+        old_frame: PhysAddr,
+        orig_flags: MappingFlags,
+        pt: &mut PageTable,
+    ) -> Option<PageFaultResult> {
+        let mut refs = FRAME_REFS.lock();
+        if !refs.is_shared(old_frame) {
+            return None; // 其他持有者已各自复制，该页已是私有的
+        }
+        let Some(frame) = alloc_frame(false) else {
+            warn!("handle_cow_fault_alloc: no memory for {:#x}", vaddr);
+            return Some(Err(PageFaultError::NoMemory));
+        };
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                phys_to_virt(old_frame).as_ptr(),
+                phys_to_virt(frame).as_mut_ptr(),
+                PAGE_SIZE_4K,
+            );
         }
+        let Ok((_, tlb)) = pt.remap(vaddr, frame, orig_flags) else {
+            drop(refs);
+            dealloc_frame(frame);
+            return Some(Err(PageFaultError::Unhandled));
+        };
+        tlb.flush();
+        refs.release(old_frame); // 仍被共享，不会是最后一个持有者
+        Some(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memory_addr::pa;
+
+    #[test]
+    fn test_frame_refs() {
+        let mut refs = FrameRefs::new();
+        let frame = pa!(0x8000_0000);
+        assert!(!refs.is_shared(frame));
+        refs.share(frame);
+        refs.share(frame);
+        assert!(refs.is_shared(frame));
+        assert!(!refs.release(frame));
+        assert!(!refs.release(frame));
+        assert!(!refs.is_shared(frame));
+        assert!(refs.release(frame)); // 最后一个持有者
+    }
+
+    #[test]
+    fn test_frame_refs_interleaved_fork() {
+        let mut refs = FrameRefs::new();
+        let frame = pa!(0x8000_0000);
+        // 父进程 fork 出第一个子进程，子进程写入前又 fork 出第二个
+        refs.share(frame);
+        refs.share(frame);
+        // 第一个子进程复制后释放，父进程仍与第二个子进程共享
+        assert!(!refs.release(frame));
+        assert!(refs.is_shared(frame));
+        // 父进程复制后释放，第二个子进程成为唯一的持有者，原地恢复可写
+        assert!(!refs.release(frame));
+        assert!(!refs.is_shared(frame));
+        // 解除映射时由它释放
+        assert!(refs.release(frame));
     }
 }
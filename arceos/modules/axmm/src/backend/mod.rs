@@ -1,21 +1,29 @@
 //! Memory mapping backends.
 #![allow(dead_code)]
 
-use axhal::paging::{MappingFlags, PageTable};
-use memory_addr::VirtAddr;
+use axhal::paging::{MappingFlags, PageSize, PageTable};
+use memory_addr::{VirtAddr, VirtAddrRange};
 use memory_set::MappingBackend;
 
+use crate::fault::{FaultKind, PageFaultError, PageFaultResult};
+
 mod alloc;
 mod linear;
+mod source;
+
+pub(crate) use self::alloc::share_frame;
+pub use self::source::BackingSource;
 
 /// A unified enum type for different memory mapping backends.
 ///
-/// Currently, two backends are implemented:
+/// Currently, three backends are implemented:
 ///
 /// - **Linear**: used for linear mappings. The target physical frames are
 ///   contiguous and their addresses should be known when creating the mapping.
 /// - **Allocation**: used in general, or for lazy mappings. The target physical
 ///   frames are obtained from the global allocator.
+/// - **Source**: used for device or file backed regions. The target physical
+///   frames are given by a [`BackingSource`] on page faults.
 #[derive(Clone)]
 pub enum Backend {
     /// Linear mapping backend.
@@ -36,6 +44,22 @@ pub enum Backend {
     Alloc {
         /// Whether to populate the physical frames when creating the mapping.
         populate: bool,
+        /// The preferred page size to allocate and map on page faults.
+        ///
+        /// If it is a huge page size, a fault maps the whole huge page around
+        /// the fault address, as long as the huge page is within the area and
+        /// the allocator can provide it. Otherwise a 4K page is mapped.
+        fault_size: PageSize,
+    },
+    /// Source mapping backend.
+    ///
+    /// Nothing is mapped when the mapping is created. On a page fault, the
+    /// frame and the flags of the page are obtained from `source`, e.g., the
+    /// MMIO frame of a device with device attributes, instead of allocating
+    /// memory. The frames are not freed on unmapping.
+    Source {
+        /// Gives the frame to map for an offset in the area.
+        source: BackingSource,
     },
 }
 
@@ -46,14 +70,19 @@ impl MappingBackend for Backend {
     fn map(&self, start: VirtAddr, size: usize, flags: MappingFlags, pt: &mut PageTable) -> bool {
         match *self {
             Self::Linear { pa_va_offset } => self.map_linear(start, size, flags, pt, pa_va_offset),
-            Self::Alloc { populate } => self.map_alloc(start, size, flags, pt, populate),
+            Self::Alloc {
+                populate,
+                fault_size,
+            } => self.map_alloc(start, size, flags, pt, populate, fault_size),
+            Self::Source { .. } => self.map_source(start, size, flags),
         }
     }
 
     fn unmap(&self, start: VirtAddr, size: usize, pt: &mut PageTable) -> bool {
         match *self {
             Self::Linear { pa_va_offset } => self.unmap_linear(start, size, pt, pa_va_offset),
-            Self::Alloc { populate } => self.unmap_alloc(start, size, pt, populate),
+            Self::Alloc { populate, .. } => self.unmap_alloc(start, size, pt, populate),
+            Self::Source { .. } => self.unmap_source(start, size, pt),
         }
     }
 
@@ -75,14 +104,38 @@ impl Backend {
     pub(crate) fn handle_page_fault(
         &self,
         vaddr: VirtAddr,
+        kind: FaultKind,
+        area: VirtAddrRange,
         orig_flags: MappingFlags,
         page_table: &mut PageTable,
-    ) -> bool {
+    ) -> PageFaultResult {
         match *self {
-            Self::Linear { .. } => false, // Linear mappings should not trigger page faults.
-            Self::Alloc { populate } => {
-                self.handle_page_fault_alloc(vaddr, orig_flags, page_table, populate)
+            // Linear mappings should not trigger page faults.
+            Self::Linear { .. } => Err(PageFaultError::Unhandled),
+            Self::Alloc {
+                populate,
+                fault_size,
+            } => {
+                if !populate && fault_size.is_huge() && kind.is_unmapped() {
+                    self.handle_huge_fault_alloc(vaddr, area, orig_flags, page_table, fault_size)
+                } else {
+                    self.handle_page_fault_alloc(vaddr, kind, orig_flags, page_table, populate)
+                }
             }
+            Self::Source { source } => {
+                self.handle_page_fault_source(vaddr, kind, area, page_table, source)
+            }
+        }
+    }
+
+    /// Whether [`handle_page_fault`](Self::handle_page_fault) may resolve a
+    /// fault of `kind`, barring running out of memory.
+    pub(crate) fn can_handle_fault(&self, kind: FaultKind) -> bool {
+        match *self {
+            Self::Linear { .. } => false,
+            // 与 `handle_page_fault_alloc` 的判断保持一致
+            Self::Alloc { populate, .. } => !populate || !kind.is_unmapped(),
+            Self::Source { .. } => kind.is_unmapped(),
         }
     }
 }
@@ -0,0 +1,61 @@
+use axhal::paging::{MappingFlags, PageSize, PageTable};
+use memory_addr::{MemoryAddr, PageIter4K, PhysAddr, VirtAddr, VirtAddrRange};
+
+use super::Backend;
+use crate::fault::{FaultKind, PageFaultError, PageFaultResult};
+
+/// Produces the physical frame and the mapping flags of the page at `offset`
+/// bytes from the start of a [`Backend::Source`] area, or `None` if there is
+/// nothing to map there.
+///
+/// `offset` is 4K aligned, and so must be the returned frame.
+pub type BackingSource = fn(offset: usize) -> Option<(PhysAddr, MappingFlags)>;
+
+impl Backend {
+    /// Creates a new source mapping backend.
+    pub const fn new_source(source: BackingSource) -> Self {
+        Self::Source { source }
+    }
+
+    pub(crate) fn map_source(&self, start: VirtAddr, size: usize, flags: MappingFlags) -> bool {
+        // 缺页时才向来源查询页帧
+        debug!(
+            "map_source: [{:#x}, {:#x}) {:?}",
+            start,
+            start + size,
+            flags
+        );
+        true
+    }
+
+    pub(crate) fn unmap_source(&self, start: VirtAddr, size: usize, pt: &mut PageTable) -> bool {
+        debug!("unmap_source: [{:#x}, {:#x})", start, start + size);
+        for addr in PageIter4K::new(start, start + size).unwrap() {
+            // 页帧属于来源，不需要释放
+            if let Ok((_, _, tlb)) = pt.unmap(addr) {
+                tlb.flush();
+            }
+        }
+        true
+    }
+
+    pub(crate) fn handle_page_fault_source(
+        &self,
+        vaddr: VirtAddr,
+        kind: FaultKind,
+        area: VirtAddrRange,
+        pt: &mut PageTable,
+        source: BackingSource,
+    ) -> PageFaultResult {
+        if !kind.is_unmapped() {
+            // Pages are mapped with the flags given by the source.
+            return Err(PageFaultError::PermissionDenied);
+        }
+        let page = vaddr.align_down_4k();
+        let offset = page.as_usize() - area.start.as_usize();
+        let (frame, flags) = source(offset).ok_or(PageFaultError::NoRegion)?;
+        pt.map(page, frame, PageSize::Size4K, flags)
+            .map(|tlb| tlb.flush())
+            .map_err(|_| PageFaultError::Unhandled)
+    }
+}
@@ -0,0 +1,155 @@
+//! Page fault classification.
+
+use axhal::paging::MappingFlags;
+
+/// The cause of a page fault.
+///
+/// It is decoded from the access flags reported by the trap handler and the
+/// flags of the page table entry currently mapped at the fault address (if
+/// any), see [`FaultKind::decode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultKind {
+    /// Read from an unmapped page.
+    ReadUnmapped,
+    /// Write to an unmapped page.
+    WriteUnmapped,
+    /// Instruction fetch from an unmapped page.
+    ExecUnmapped,
+    /// Read from a mapped page without the `READ` permission.
+    ReadProtection,
+    /// Write to a mapped page without the `WRITE` permission.
+    WriteProtection,
+    /// Instruction fetch from a mapped page without the `EXECUTE` permission.
+    ExecNoExec,
+    /// User-mode access to a mapped page without the `USER` permission.
+    UserNoAccess,
+}
+
+impl FaultKind {
+    /// Decodes the fault cause.
+    ///
+    /// `access_flags` is the access type that caused the page fault, and
+    /// `pte_flags` is the flags of the page currently mapped at the fault
+    /// address, or `None` if the page is not mapped.
+    pub fn decode(access_flags: MappingFlags, pte_flags: Option<MappingFlags>) -> Self {
+        let Some(pte_flags) = pte_flags else {
+            return if access_flags.contains(MappingFlags::EXECUTE) {
+                Self::ExecUnmapped
+            } else if access_flags.contains(MappingFlags::WRITE) {
+                Self::WriteUnmapped
+            } else {
+                Self::ReadUnmapped
+            };
+        };
+        if access_flags.contains(MappingFlags::USER) && !pte_flags.contains(MappingFlags::USER) {
+            Self::UserNoAccess
+        } else if access_flags.contains(MappingFlags::EXECUTE) {
+            Self::ExecNoExec
+        } else if access_flags.contains(MappingFlags::WRITE) {
+            Self::WriteProtection
+        } else {
+            Self::ReadProtection
+        }
+    }
+
+    /// Whether the fault is caused by accessing an unmapped page.
+    pub const fn is_unmapped(self) -> bool {
+        matches!(
+            self,
+            Self::ReadUnmapped | Self::WriteUnmapped | Self::ExecUnmapped
+        )
+    }
+
+    /// Whether the fault may be resolved by the address space, i.e., it is
+    /// not a permission violation by nature.
+    pub const fn is_handleable(self) -> bool {
+        self.is_unmapped() || matches!(self, Self::WriteProtection)
+    }
+}
+
+/// The reason why a page fault cannot be resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageFaultError {
+    /// The address is not in the address space or in any of its areas.
+    NoRegion,
+    /// The address is in a guard page, usually caused by a stack overflow.
+    GuardPage,
+    /// The access is not allowed by the page, which is not expected to
+    /// fault.
+    PermissionDenied,
+    /// The access requires the `required` permissions, but the area or the
+    /// page only has the `present` ones, e.g., a write to a read-only
+    /// mapping.
+    Protection {
+        /// The access type that caused the fault.
+        required: MappingFlags,
+        /// The permissions of the area, or of the page if the area allows
+        /// the access.
+        present: MappingFlags,
+    },
+    /// No physical memory is available to resolve the fault.
+    NoMemory,
+    /// No physical memory is available for the page table nodes needed to
+    /// map the page. The page table is left as it was before the fault.
+    PageTableOom,
+    /// The address space is locked and cannot be accessed right now.
+    Busy,
+    /// The fault would map a new page, but the paging budget of the address
+    /// space is used up, see [`AddrSpace::set_fault_budget`].
+    ///
+    /// [`AddrSpace::set_fault_budget`]: crate::AddrSpace::set_fault_budget
+    BudgetExceeded,
+    /// The fault would populate again a page of a populate-once region,
+    /// all of whose pages have been populated before, see
+    /// [`AddrSpace::set_populate_once`].
+    ///
+    /// [`AddrSpace::set_populate_once`]: crate::AddrSpace::set_populate_once
+    Repopulated,
+    /// The area is not expected to fault, e.g., a linear or populated
+    /// mapping, or the page table is in an unexpected state.
+    Unhandled,
+}
+
+/// A result type for page fault handling.
+pub type PageFaultResult = Result<(), PageFaultError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const R: MappingFlags = MappingFlags::READ;
+    const W: MappingFlags = MappingFlags::WRITE;
+    const X: MappingFlags = MappingFlags::EXECUTE;
+    const U: MappingFlags = MappingFlags::USER;
+
+    #[test]
+    fn test_decode_unmapped() {
+        assert_eq!(FaultKind::decode(R, None), FaultKind::ReadUnmapped);
+        assert_eq!(FaultKind::decode(W, None), FaultKind::WriteUnmapped);
+        assert_eq!(FaultKind::decode(X, None), FaultKind::ExecUnmapped);
+        assert_eq!(FaultKind::decode(W | U, None), FaultKind::WriteUnmapped);
+        assert!(FaultKind::decode(R | U, None).is_unmapped());
+    }
+
+    #[test]
+    fn test_decode_protection() {
+        assert_eq!(FaultKind::decode(W, Some(R)), FaultKind::WriteProtection);
+        assert_eq!(FaultKind::decode(X, Some(R | W)), FaultKind::ExecNoExec);
+        assert_eq!(FaultKind::decode(R, Some(W)), FaultKind::ReadProtection);
+        assert_eq!(FaultKind::decode(R | U, Some(R)), FaultKind::UserNoAccess);
+        assert_eq!(
+            FaultKind::decode(W | U, Some(R | U)),
+            FaultKind::WriteProtection
+        );
+    }
+
+    #[test]
+    fn test_handleable() {
+        assert!(FaultKind::ReadUnmapped.is_handleable());
+        assert!(FaultKind::WriteProtection.is_handleable());
+        assert!(!FaultKind::WriteProtection.is_unmapped());
+        assert!(!FaultKind::ExecNoExec.is_handleable());
+        assert!(!FaultKind::ReadProtection.is_handleable());
+        assert!(!FaultKind::UserNoAccess.is_handleable());
+    }
+}
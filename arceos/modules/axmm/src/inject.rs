@@ -0,0 +1,65 @@
+//! Fault injection for testing the page fault handling paths.
+//!
+//! Only available with the `fault-inject` feature.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use kspin::SpinNoIrq;
+
+use crate::fault::PageFaultError;
+
+static NEXT_FAULT: SpinNoIrq<Option<PageFaultError>> = SpinNoIrq::new(None);
+/// The frame allocation to fail, counting from 1, or 0 for none.
+static NEXT_OOM: AtomicUsize = AtomicUsize::new(0);
+/// The same for the page table node allocations.
+static NEXT_TABLE_OOM: AtomicUsize = AtomicUsize::new(0);
+
+/// Forces the next [`AddrSpace::try_handle_page_fault`] call to fail with
+/// `err`, without touching the address space.
+///
+/// [`AddrSpace::try_handle_page_fault`]: crate::AddrSpace::try_handle_page_fault
+pub fn inject_page_fault(err: PageFaultError) {
+    *NEXT_FAULT.lock() = Some(err);
+}
+
+/// Forces the next physical frame allocation of the allocation backend to
+/// fail, as if the page allocator is out of memory.
+pub fn inject_oom() {
+    inject_oom_after(0);
+}
+
+/// Forces the physical frame allocation after the next `n` ones to fail,
+/// e.g., in the middle of mapping several pages.
+pub fn inject_oom_after(n: usize) {
+    NEXT_OOM.store(n + 1, Ordering::Relaxed);
+}
+
+/// Forces the page table node allocation after the next `n` ones to fail,
+/// e.g., in the middle of the walk mapping a page on a fault.
+pub fn inject_table_oom_after(n: usize) {
+    NEXT_TABLE_OOM.store(n + 1, Ordering::Relaxed);
+}
+
+/// Clears all pending injections.
+pub fn clear_injections() {
+    NEXT_FAULT.lock().take();
+    NEXT_OOM.store(0, Ordering::Relaxed);
+    NEXT_TABLE_OOM.store(0, Ordering::Relaxed);
+}
+
+pub(crate) fn take_page_fault() -> Option<PageFaultError> {
+    NEXT_FAULT.lock().take()
+}
+
+pub(crate) fn take_oom() -> bool {
+    take(&NEXT_OOM)
+}
+
+pub(crate) fn take_table_oom() -> bool {
+    take(&NEXT_TABLE_OOM)
+}
+
+fn take(next: &AtomicUsize) -> bool {
+    let prev = next.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+    prev == Ok(1)
+}
@@ -0,0 +1,163 @@
+//! Registry of the regions mapped on page faults.
+
+use alloc::vec::Vec;
+
+use axhal::paging::MappingFlags;
+use memory_addr::{VirtAddr, VirtAddrRange};
+
+use crate::backend::Backend;
+use crate::fault::FaultKind;
+
+/// How the page faults in a [`LazyRegion`] are serviced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LazyKind {
+    /// The first access to a page maps a zeroed frame.
+    DemandZero,
+    /// The pages are shared, and the first write to a page gives the writer
+    /// a private copy. The pages not populated yet are demand-zero.
+    Cow,
+    /// The pages are the frames of a device.
+    Device,
+    /// The pages are the frames of a file.
+    File,
+}
+
+impl LazyKind {
+    /// Whether a fault of `kind` in an area of `backend` is serviced this
+    /// way.
+    pub(crate) fn serves(self, backend: &Backend, kind: FaultKind) -> bool {
+        match (self, backend) {
+            (Self::DemandZero, Backend::Alloc { populate, .. }) => !populate && kind.is_unmapped(),
+            (Self::Cow, Backend::Alloc { populate, .. }) => {
+                kind == FaultKind::WriteProtection || (!populate && kind.is_unmapped())
+            }
+            (Self::Device | Self::File, Backend::Source { .. }) => kind.is_unmapped(),
+            _ => false,
+        }
+    }
+}
+
+/// A region whose pages are mapped on page faults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LazyRegion {
+    /// The address range of the region.
+    pub range: VirtAddrRange,
+    /// The accesses allowed in the region.
+    pub flags: MappingFlags,
+    /// How the faults in the region are serviced.
+    pub kind: LazyKind,
+}
+
+/// The lazy regions of an address space, sorted by the start address and
+/// not overlapping.
+pub(crate) struct LazyRegions {
+    regions: Vec<LazyRegion>,
+}
+
+impl LazyRegions {
+    pub(crate) const fn new() -> Self {
+        Self {
+            regions: Vec::new(),
+        }
+    }
+
+    /// Adds a region, or returns `false` if it overlaps with an existing
+    /// one.
+    pub(crate) fn add(&mut self, region: LazyRegion) -> bool {
+        let idx = self
+            .regions
+            .partition_point(|r| r.range.start < region.range.start);
+        // 只需检查前后相邻的区域
+        let overlaps_prev = idx > 0 && self.regions[idx - 1].range.end > region.range.start;
+        let overlaps_next = self
+            .regions
+            .get(idx)
+            .is_some_and(|next| next.range.start < region.range.end);
+        if overlaps_prev || overlaps_next {
+            return false;
+        }
+        self.regions.insert(idx, region);
+        true
+    }
+
+    /// Removes the region of exactly `range`, and returns it.
+    pub(crate) fn remove(&mut self, range: VirtAddrRange) -> Option<LazyRegion> {
+        let idx = self.regions.iter().position(|r| r.range == range)?;
+        Some(self.regions.remove(idx))
+    }
+
+    /// Returns the region containing `vaddr`.
+    pub(crate) fn find(&self, vaddr: VirtAddr) -> Option<&LazyRegion> {
+        let idx = self.regions.partition_point(|r| r.range.start <= vaddr);
+        let region = self.regions.get(idx.checked_sub(1)?)?;
+        region.range.contains(vaddr).then_some(region)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memory_addr::va;
+
+    fn region(start: usize, size: usize, kind: LazyKind) -> LazyRegion {
+        LazyRegion {
+            range: VirtAddrRange::from_start_size(va!(start), size),
+            flags: MappingFlags::READ | MappingFlags::WRITE,
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_adjacent() {
+        let mut regions = LazyRegions::new();
+        assert!(regions.add(region(0x2000, 0x2000, LazyKind::DemandZero)));
+        assert!(regions.add(region(0x4000, 0x1000, LazyKind::Device)));
+        assert!(regions.add(region(0x1000, 0x1000, LazyKind::File)));
+
+        // 边界上的地址属于后一个区域
+        assert_eq!(regions.find(va!(0x1fff)).unwrap().kind, LazyKind::File);
+        assert_eq!(
+            regions.find(va!(0x2000)).unwrap().kind,
+            LazyKind::DemandZero
+        );
+        assert_eq!(
+            regions.find(va!(0x3fff)).unwrap().kind,
+            LazyKind::DemandZero
+        );
+        assert_eq!(regions.find(va!(0x4000)).unwrap().kind, LazyKind::Device);
+        assert_eq!(regions.find(va!(0x0fff)), None);
+        assert_eq!(regions.find(va!(0x5000)), None);
+    }
+
+    #[test]
+    fn test_overlapping() {
+        let mut regions = LazyRegions::new();
+        assert!(regions.add(region(0x2000, 0x2000, LazyKind::DemandZero)));
+        assert!(!regions.add(region(0x1000, 0x2000, LazyKind::Cow)));
+        assert!(!regions.add(region(0x3000, 0x2000, LazyKind::Cow)));
+        assert!(!regions.add(region(0x2800, 0x100, LazyKind::Cow)));
+        assert!(!regions.add(region(0x1000, 0x4000, LazyKind::Cow)));
+        // 被拒绝的区域不影响查找
+        assert_eq!(regions.find(va!(0x1800)), None);
+        assert_eq!(
+            regions.find(va!(0x3800)).unwrap().kind,
+            LazyKind::DemandZero
+        );
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut regions = LazyRegions::new();
+        let zero = region(0x2000, 0x2000, LazyKind::DemandZero);
+        assert!(regions.add(zero));
+        // 只能移除完全相同的范围
+        assert_eq!(
+            regions.remove(region(0x2000, 0x1000, LazyKind::Cow).range),
+            None
+        );
+        assert_eq!(regions.remove(zero.range), Some(zero));
+        assert_eq!(regions.find(va!(0x2000)), None);
+        assert!(regions.add(region(0x3000, 0x2000, LazyKind::Cow)));
+        assert_eq!(regions.find(va!(0x4800)).unwrap().kind, LazyKind::Cow);
+    }
+}
@@ -8,12 +8,26 @@ extern crate alloc;
 
 mod aspace;
 mod backend;
+mod fault;
+mod lazy;
+mod once;
+mod populate;
+mod region;
+mod stack;
+
+#[cfg(feature = "fault-inject")]
+pub mod inject;
 
 pub use self::aspace::AddrSpace;
+pub use self::backend::BackingSource;
+pub use self::fault::{FaultKind, PageFaultError, PageFaultResult};
+pub use self::lazy::{LazyKind, LazyRegion};
+pub use self::populate::{set_populate_yield, PopulateYield};
+pub use self::region::Region;
 
 use axerrno::{AxError, AxResult};
 use axhal::mem::phys_to_virt;
-use axhal::paging::PagingError;
+use axhal::paging::{MappingFlags, PageSize, PageTable, PagingError};
 use kspin::SpinNoIrq;
 use lazyinit::LazyInit;
 use memory_addr::{va, PhysAddr, VirtAddr};
@@ -44,6 +58,21 @@ fn paging_err_to_ax_err(err: PagingError) -> AxError {
     }
 }
 
+/// Returns the page mapped at `vaddr`, or `None` if it is not mapped.
+///
+/// The empty entries left by lazy mappings are not mapped pages. They are
+/// zero on x86_64, but not on riscv64 or aarch64, where the accessed bits are
+/// set in them, so that `query` still finds them with empty flags.
+fn query_mapped(pt: &PageTable, vaddr: VirtAddr) -> Option<(PhysAddr, MappingFlags, PageSize)> {
+    pt.query(vaddr).ok().filter(|(_, flags, _)| !flags.is_empty())
+}
+
+/// Checks if the given address is in the user part of the virtual address
+/// space, see [`AddrSpace::is_user_addr`].
+fn is_user_addr(vaddr: VirtAddr) -> bool {
+    (USER_ASPACE_BASE..USER_ASPACE_BASE + USER_ASPACE_SIZE).contains(&vaddr.as_usize())
+}
+
 /// Creates a new address space for user processes.
 pub fn new_user_aspace() -> AxResult<AddrSpace> {
     let mut aspace = AddrSpace::new_empty(VirtAddr::from(USER_ASPACE_BASE), USER_ASPACE_SIZE)?;
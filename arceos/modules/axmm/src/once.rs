@@ -0,0 +1,90 @@
+//! Regions whose pages may only be populated once.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use memory_addr::{VirtAddr, VirtAddrRange, PAGE_SIZE_4K};
+
+/// A region whose pages are populated on faults at most once.
+///
+/// Once every page of the region has been populated, e.g. a one-shot scratch
+/// buffer fully written, a fault populating a page again means that it was
+/// unmapped and touched afterwards, which is rejected.
+pub(crate) struct PopulateOnce {
+    range: VirtAddrRange,
+    /// One bit per 4K page, set once the page has been populated.
+    populated: Vec<u64>,
+    /// Number of pages not populated yet.
+    remaining: usize,
+}
+
+impl PopulateOnce {
+    pub(crate) fn new(range: VirtAddrRange) -> Self {
+        let pages = range.size() / PAGE_SIZE_4K;
+        Self {
+            range,
+            populated: vec![0; pages.div_ceil(64)],
+            remaining: pages,
+        }
+    }
+
+    pub(crate) fn contains(&self, vaddr: VirtAddr) -> bool {
+        self.range.contains(vaddr)
+    }
+
+    /// Whether all the pages have been populated.
+    pub(crate) const fn is_done(&self) -> bool {
+        self.remaining == 0
+    }
+
+    /// Records that the pages in `[start, start + size)` have been populated.
+    /// The part outside of the region is ignored.
+    pub(crate) fn record(&mut self, start: VirtAddr, size: usize) {
+        let base = self.range.start.as_usize();
+        let first = start.as_usize().max(base);
+        let end = (start.as_usize() + size).min(self.range.end.as_usize());
+        let pages = (first - base) / PAGE_SIZE_4K..end.saturating_sub(base).div_ceil(PAGE_SIZE_4K);
+        for page in pages {
+            let (word, bit) = (page / 64, 1 << (page % 64));
+            if self.populated[word] & bit == 0 {
+                self.populated[word] |= bit;
+                self.remaining -= 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memory_addr::va;
+
+    #[test]
+    fn test_record() {
+        let range = VirtAddrRange::from_start_size(va!(0x1000), 0x3000);
+        let mut once = PopulateOnce::new(range);
+        assert!(!once.is_done());
+        once.record(va!(0x1000), 0x1000);
+        // 重复记录同一页不计数
+        once.record(va!(0x1000), 0x1000);
+        assert!(!once.is_done());
+        // 超出区域的部分被忽略
+        once.record(va!(0x0), 0x3000);
+        assert!(!once.is_done());
+        once.record(va!(0x3000), 0x20_0000);
+        assert!(once.is_done());
+        assert!(once.contains(va!(0x3fff)));
+        assert!(!once.contains(va!(0x4000)));
+    }
+
+    #[test]
+    fn test_record_many_pages() {
+        let range = VirtAddrRange::from_start_size(va!(0x10_0000), 0x41000);
+        let mut once = PopulateOnce::new(range);
+        for i in (0..65).rev() {
+            assert!(!once.is_done());
+            once.record(va!(0x10_0000 + i * 0x1000), 0x1000);
+        }
+        assert!(once.is_done());
+    }
+}
@@ -0,0 +1,93 @@
+//! Cooperative yields while populating many pages at once.
+
+use kspin::SpinNoIrq;
+
+static POPULATE_YIELD: SpinNoIrq<Option<PopulateYield>> = SpinNoIrq::new(None);
+
+/// How often a population of many pages yields the CPU, see
+/// [`set_populate_yield`].
+#[derive(Debug, Clone, Copy)]
+pub struct PopulateYield {
+    /// Number of pages mapped between two yields.
+    pub every: usize,
+    /// The function yielding the CPU, e.g. `axtask::yield_now`.
+    pub yield_now: fn(),
+}
+
+/// Makes the populations of many pages, e.g. of a populated
+/// [`map_alloc`](crate::AddrSpace::map_alloc), yield the CPU regularly, so
+/// that a big population does not stall the other tasks. `None`, the
+/// default, never yields.
+///
+/// The address space stays locked across the yields. A population never
+/// yields with IRQs disabled, e.g. in a trap handler.
+pub fn set_populate_yield(policy: Option<PopulateYield>) {
+    *POPULATE_YIELD.lock() = policy;
+}
+
+/// Counts the pages of a population, and yields after every
+/// [`PopulateYield::every`] pages.
+pub(crate) struct Yielder {
+    policy: Option<PopulateYield>,
+    pages: usize,
+}
+
+impl Yielder {
+    /// Creates a yielder following `policy` if yielding is allowed.
+    pub(crate) fn new(policy: Option<PopulateYield>, can_yield: bool) -> Self {
+        Self {
+            policy: policy.filter(|policy| can_yield && policy.every != 0),
+            pages: 0,
+        }
+    }
+
+    /// Creates a yielder following the policy set by [`set_populate_yield`]
+    /// in the current context.
+    pub(crate) fn current() -> Self {
+        Self::new(*POPULATE_YIELD.lock(), axhal::arch::irqs_enabled())
+    }
+
+    /// Counts a populated page, and yields if it is the turn.
+    pub(crate) fn page_done(&mut self) {
+        let Some(policy) = self.policy else {
+            return;
+        };
+        self.pages += 1;
+        if self.pages % policy.every == 0 {
+            (policy.yield_now)();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_yielder() {
+        static YIELDS: AtomicUsize = AtomicUsize::new(0);
+        fn yield_now() {
+            YIELDS.fetch_add(1, Ordering::Relaxed);
+        }
+        let policy = Some(PopulateYield {
+            every: 4,
+            yield_now,
+        });
+
+        let mut yielder = Yielder::new(policy, true);
+        for _ in 0..10 {
+            yielder.page_done();
+        }
+        assert_eq!(YIELDS.load(Ordering::Relaxed), 2);
+
+        // 不能让出时，如陷入处理中，从不让出
+        let mut yielder = Yielder::new(policy, false);
+        for _ in 0..10 {
+            yielder.page_done();
+        }
+        let mut yielder = Yielder::new(None, true);
+        yielder.page_done();
+        assert_eq!(YIELDS.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,219 @@
+//! Attributes of address ranges, looked up on page faults.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use axhal::paging::MappingFlags;
+use memory_addr::{VirtAddr, VirtAddrRange};
+
+/// An address range of an [`AddrSpace`] with the same attributes, see
+/// [`AddrSpace::region_of`].
+///
+/// The attributes are set by [`AddrSpace::add_guard_range`],
+/// [`AddrSpace::name_region`] and [`AddrSpace::set_populate_once`]. Setting
+/// one on a part of a region splits it.
+///
+/// [`AddrSpace`]: crate::AddrSpace
+/// [`AddrSpace::region_of`]: crate::AddrSpace::region_of
+/// [`AddrSpace::add_guard_range`]: crate::AddrSpace::add_guard_range
+/// [`AddrSpace::name_region`]: crate::AddrSpace::name_region
+/// [`AddrSpace::set_populate_once`]: crate::AddrSpace::set_populate_once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    /// The address range of the region.
+    pub range: VirtAddrRange,
+    /// The name of the region for the diagnostics, if any.
+    pub name: Option<&'static str>,
+    /// Whether the region is a guard range.
+    pub guard: bool,
+    /// Whether the pages of the region are populated at most once.
+    pub populate_once: bool,
+}
+
+impl Region {
+    const fn new(range: VirtAddrRange) -> Self {
+        Self {
+            range,
+            name: None,
+            guard: false,
+            populate_once: false,
+        }
+    }
+}
+
+/// A line of the region table of an address space, see
+/// [`AddrSpace::dump_regions`].
+///
+/// [`AddrSpace::dump_regions`]: crate::AddrSpace::dump_regions
+pub(crate) struct RegionEntry {
+    pub(crate) range: VirtAddrRange,
+    pub(crate) flags: MappingFlags,
+    pub(crate) name: &'static str,
+}
+
+impl fmt::Display for RegionEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let perm = |flag, c| if self.flags.contains(flag) { c } else { '-' };
+        write!(
+            f,
+            "{:#018x}-{:#018x} {}{}{}{} {}",
+            self.range.start,
+            self.range.end,
+            perm(MappingFlags::READ, 'r'),
+            perm(MappingFlags::WRITE, 'w'),
+            perm(MappingFlags::EXECUTE, 'x'),
+            perm(MappingFlags::USER, 'u'),
+            self.name
+        )
+    }
+}
+
+/// The regions of an address space, sorted by the start address and not
+/// overlapping, so that a fault looks its region up in `O(log n)`.
+pub(crate) struct Regions {
+    regions: Vec<Region>,
+}
+
+impl Regions {
+    pub(crate) const fn new() -> Self {
+        Self {
+            regions: Vec::new(),
+        }
+    }
+
+    /// Returns the region containing `vaddr`.
+    pub(crate) fn find(&self, vaddr: VirtAddr) -> Option<&Region> {
+        let idx = self.regions.partition_point(|r| r.range.start <= vaddr);
+        let region = self.regions.get(idx.checked_sub(1)?)?;
+        region.range.contains(vaddr).then_some(region)
+    }
+
+    /// Applies `f` to the regions covering `range`, after splitting the
+    /// regions across its boundaries and creating the missing ones.
+    pub(crate) fn update(&mut self, range: VirtAddrRange, f: impl Fn(&mut Region)) {
+        self.split_at(range.start);
+        self.split_at(range.end);
+        let mut idx = self
+            .regions
+            .partition_point(|r| r.range.start < range.start);
+        let mut cursor = range.start;
+        while cursor < range.end {
+            match self.regions.get_mut(idx) {
+                Some(region) if region.range.start == cursor => {
+                    f(region);
+                    cursor = region.range.end;
+                }
+                next => {
+                    // 填充空隙
+                    let gap_end = next.map_or(range.end, |r| r.range.start.min(range.end));
+                    let mut region = Region::new(VirtAddrRange::new(cursor, gap_end));
+                    f(&mut region);
+                    self.regions.insert(idx, region);
+                    cursor = gap_end;
+                }
+            }
+            idx += 1;
+        }
+    }
+
+    /// Splits the region strictly containing `vaddr` in two at `vaddr`.
+    fn split_at(&mut self, vaddr: VirtAddr) {
+        let idx = self.regions.partition_point(|r| r.range.start < vaddr);
+        let Some(prev) = idx.checked_sub(1) else {
+            return;
+        };
+        let region = self.regions[prev];
+        if region.range.end > vaddr {
+            self.regions[prev].range.end = vaddr;
+            let mut tail = region;
+            tail.range.start = vaddr;
+            self.regions.insert(idx, tail);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    use core::fmt::Write;
+    use memory_addr::va;
+
+    fn range(start: usize, size: usize) -> VirtAddrRange {
+        VirtAddrRange::from_start_size(va!(start), size)
+    }
+
+    #[test]
+    fn test_find() {
+        let mut regions = Regions::new();
+        regions.update(range(0x2000, 0x2000), |r| r.name = Some("heap"));
+        regions.update(range(0x4000, 0x1000), |r| r.guard = true);
+        regions.update(range(0x8000, 0x1000), |r| r.populate_once = true);
+
+        // 起点、内部、边界与空隙
+        assert_eq!(regions.find(va!(0x2000)).unwrap().name, Some("heap"));
+        assert_eq!(regions.find(va!(0x3fff)).unwrap().name, Some("heap"));
+        assert!(regions.find(va!(0x4000)).unwrap().guard);
+        assert!(!regions.find(va!(0x4000)).unwrap().populate_once);
+        assert_eq!(regions.find(va!(0x1fff)), None);
+        assert_eq!(regions.find(va!(0x5000)), None);
+        assert_eq!(regions.find(va!(0x7fff)), None);
+        assert!(regions.find(va!(0x8fff)).unwrap().populate_once);
+        assert_eq!(regions.find(va!(0x9000)), None);
+        assert_eq!(regions.find(va!(0x0)), None);
+    }
+
+    #[test]
+    fn test_update_split() {
+        let mut regions = Regions::new();
+        regions.update(range(0x2000, 0x4000), |r| r.name = Some("heap"));
+        // 覆盖区域的中间部分以及之后的空隙
+        regions.update(range(0x3000, 0x5000), |r| r.populate_once = true);
+
+        let heap = regions.find(va!(0x2fff)).unwrap();
+        assert_eq!(heap.range, range(0x2000, 0x1000));
+        assert!(!heap.populate_once);
+        let both = regions.find(va!(0x3000)).unwrap();
+        assert_eq!(both.range, range(0x3000, 0x3000));
+        assert_eq!(both.name, Some("heap"));
+        assert!(both.populate_once);
+        let gap = regions.find(va!(0x7fff)).unwrap();
+        assert_eq!(gap.range, range(0x6000, 0x2000));
+        assert_eq!(gap.name, None);
+        assert!(gap.populate_once);
+
+        // 再次命名覆盖原有的名字
+        regions.update(range(0x2800, 0x1000), |r| r.name = Some("scratch"));
+        assert_eq!(regions.find(va!(0x27ff)).unwrap().name, Some("heap"));
+        assert_eq!(regions.find(va!(0x2800)).unwrap().name, Some("scratch"));
+        assert_eq!(regions.find(va!(0x37ff)).unwrap().name, Some("scratch"));
+        assert!(regions.find(va!(0x37ff)).unwrap().populate_once);
+        assert_eq!(regions.find(va!(0x3800)).unwrap().name, Some("heap"));
+        assert_eq!(regions.regions.len(), 5);
+    }
+
+    #[test]
+    fn test_region_entry() {
+        let entries = [
+            RegionEntry {
+                range: range(0x1000, 0x2000),
+                flags: MappingFlags::READ | MappingFlags::EXECUTE | MappingFlags::USER,
+                name: "text",
+            },
+            RegionEntry {
+                range: range(0x3f_ffff_0000, 0x1_0000),
+                flags: MappingFlags::READ | MappingFlags::WRITE,
+                name: "anon",
+            },
+        ];
+        let mut table = String::new();
+        for entry in &entries {
+            writeln!(table, "{}", entry).unwrap();
+        }
+        assert_eq!(
+            table,
+            "0x0000000000001000-0x0000000000003000 r-xu text\n\
+             0x0000003fffff0000-0x0000004000000000 rw-- anon\n"
+        );
+    }
+}
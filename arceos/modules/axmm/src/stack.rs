@@ -0,0 +1,72 @@
+//! Automatic growth of user stacks.
+
+use memory_addr::{VirtAddr, PAGE_SIZE_4K};
+
+/// A stack region growing downward on page faults.
+///
+/// A fault at most `guard` bytes below the current bottom of the stack grows
+/// it by one page, as long as the total size does not exceed `max_size`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct StackGrowth {
+    top: usize,
+    bottom: usize,
+    max_size: usize,
+    guard: usize,
+}
+
+impl StackGrowth {
+    pub(crate) fn new(bottom: VirtAddr, top: VirtAddr, max_size: usize, guard: usize) -> Self {
+        Self {
+            top: top.as_usize(),
+            bottom: bottom.as_usize(),
+            max_size,
+            guard,
+        }
+    }
+
+    /// Returns the current bottom of the stack.
+    pub(crate) fn bottom(&self) -> VirtAddr {
+        VirtAddr::from(self.bottom)
+    }
+
+    /// Moves the bottom of the stack after it has been grown.
+    pub(crate) fn set_bottom(&mut self, bottom: VirtAddr) {
+        self.bottom = bottom.as_usize();
+    }
+
+    /// Returns the new bottom of the stack if a fault at `vaddr` should grow
+    /// it by one page.
+    pub(crate) fn grow_page(&self, vaddr: VirtAddr) -> Option<VirtAddr> {
+        let vaddr = vaddr.as_usize();
+        if vaddr >= self.bottom || vaddr < self.bottom.saturating_sub(self.guard) {
+            return None;
+        }
+        let new_bottom = self.bottom.checked_sub(PAGE_SIZE_4K)?;
+        if self.top - new_bottom > self.max_size {
+            return None; // 超过最大栈大小
+        }
+        Some(VirtAddr::from(new_bottom))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memory_addr::va;
+
+    #[test]
+    fn test_grow_page() {
+        let mut stack = StackGrowth::new(va!(0x3000), va!(0x4000), 0x3000, 0x2000);
+        // 栈内以及距离过远的访问不扩展
+        assert_eq!(stack.grow_page(va!(0x3800)), None);
+        assert_eq!(stack.grow_page(va!(0x0fff)), None);
+        assert_eq!(stack.grow_page(va!(0x2ff8)), Some(va!(0x2000)));
+        assert_eq!(stack.grow_page(va!(0x1000)), Some(va!(0x2000)));
+
+        stack.set_bottom(va!(0x2000));
+        assert_eq!(stack.grow_page(va!(0x1ff8)), Some(va!(0x1000)));
+        stack.set_bottom(va!(0x1000));
+        // 达到最大栈大小
+        assert_eq!(stack.grow_page(va!(0x0ff8)), None);
+    }
+}
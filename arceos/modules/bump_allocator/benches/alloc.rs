@@ -0,0 +1,76 @@
+//! Throughput of the bytes allocation hot path.
+//!
+//! Run with `cargo bench --features test-support`. Each case is sampled
+//! several times, and the median time per operation is reported, so that
+//! regressions stand out between runs.
+
+use core::alloc::Layout;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use allocator::ByteAllocator;
+use bump_allocator::test_support::with_backing;
+
+const PAGE_SIZE: usize = 0x1000;
+const ARENA_SIZE: usize = 64 * PAGE_SIZE;
+const SAMPLES: usize = 21;
+const ROUNDS: usize = 200;
+
+/// Runs `f` for `SAMPLES` times, and prints the median time of one of the
+/// `ops` operations it performs.
+fn bench(name: &str, ops: usize, mut f: impl FnMut()) {
+    f(); // 预热
+    let mut samples: Vec<Duration> = (0..SAMPLES)
+        .map(|_| {
+            let begin = Instant::now();
+            f();
+            begin.elapsed()
+        })
+        .collect();
+    samples.sort();
+    let median = samples[SAMPLES / 2];
+    println!(
+        "{:<24} {:>8.2} ns/op ({} ops in {:?})",
+        name,
+        median.as_nanos() as f64 / ops as f64,
+        ops,
+        median
+    );
+}
+
+fn main() {
+    let (mut early, _backing) = with_backing::<PAGE_SIZE>(ARENA_SIZE);
+    let word = Layout::new::<usize>();
+    let num = early.available_bytes() / word.size() / 2;
+
+    bench("alloc word", ROUNDS * num, || {
+        for _ in 0..ROUNDS {
+            for _ in 0..num {
+                black_box(early.alloc(black_box(word)).unwrap());
+            }
+            early.reset_bytes();
+        }
+    });
+
+    // 每次分配后立即释放，b_pos 不断回退
+    bench("alloc+dealloc word", ROUNDS * num, || {
+        for _ in 0..ROUNDS * num {
+            let ptr = early.alloc(black_box(word)).unwrap();
+            early.dealloc(black_box(ptr), word);
+        }
+    });
+
+    let mixed = [
+        Layout::from_size_align(24, 8).unwrap(),
+        Layout::from_size_align(3, 1).unwrap(),
+        Layout::from_size_align(64, 64).unwrap(),
+    ];
+    bench("alloc mixed align", ROUNDS * num / 4, || {
+        for _ in 0..ROUNDS {
+            for layout in mixed.iter().cycle().take(num / 4) {
+                black_box(early.alloc(black_box(*layout)).unwrap());
+            }
+            early.reset_bytes();
+        }
+    });
+}
@@ -0,0 +1,110 @@
+use allocator::{AllocResult, BaseAllocator, ByteAllocator, PageAllocator};
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+use crate::EarlyAllocator;
+
+/// The page size used by [`DynEarlyAllocator`] before a page size is given.
+const DEFAULT_PAGE_SIZE: usize = 0x1000;
+
+/// Early memory allocator whose page size is only known at runtime.
+///
+/// It is an [`EarlyAllocator`] whose page size is given to
+/// [`init`](Self::init) instead of being a const generic parameter, so both
+/// share the same implementation and semantics. It must be a power of two.
+///
+/// [`PageAllocator::PAGE_SIZE`] is only the default page size, use
+/// [`page_size`](Self::page_size) to get the one in effect.
+pub struct DynEarlyAllocator {
+    inner: EarlyAllocator<DEFAULT_PAGE_SIZE>,
+}
+
+impl DynEarlyAllocator {
+    pub const fn new() -> Self {
+        Self {
+            inner: EarlyAllocator::new(),
+        }
+    }
+
+    /// Initializes the allocator with the given region and page size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `page_size` is not a power of two, or if the region is
+    /// rejected by [`EarlyAllocator::try_init`] for that page size.
+    pub fn init(&mut self, start: usize, size: usize, page_size: usize) {
+        assert!(
+            page_size.is_power_of_two(),
+            "page size must be a power of two"
+        );
+        self.inner.page_size = page_size;
+        self.inner.init(start, size);
+    }
+
+    /// Returns the page size in effect.
+    pub const fn page_size(&self) -> usize {
+        self.inner.page_size()
+    }
+}
+
+impl Default for DynEarlyAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BaseAllocator for DynEarlyAllocator {
+    fn init(&mut self, start: usize, size: usize) {
+        self.inner.init(start, size);
+    }
+
+    fn add_memory(&mut self, start: usize, size: usize) -> AllocResult {
+        self.inner.add_memory(start, size)
+    }
+}
+
+impl ByteAllocator for DynEarlyAllocator {
+    fn alloc(&mut self, layout: Layout) -> AllocResult<NonNull<u8>> {
+        self.inner.alloc(layout)
+    }
+
+    fn dealloc(&mut self, pos: NonNull<u8>, layout: Layout) {
+        self.inner.dealloc(pos, layout)
+    }
+
+    fn total_bytes(&self) -> usize {
+        self.inner.total_bytes()
+    }
+
+    fn used_bytes(&self) -> usize {
+        self.inner.used_bytes()
+    }
+
+    fn available_bytes(&self) -> usize {
+        self.inner.available_bytes()
+    }
+}
+
+impl PageAllocator for DynEarlyAllocator {
+    const PAGE_SIZE: usize = DEFAULT_PAGE_SIZE;
+
+    fn alloc_pages(&mut self, num_pages: usize, align_pow2: usize) -> AllocResult<usize> {
+        self.inner.alloc_pages(num_pages, align_pow2)
+    }
+
+    fn dealloc_pages(&mut self, pos: usize, num_pages: usize) {
+        self.inner.dealloc_pages(pos, num_pages)
+    }
+
+    fn total_pages(&self) -> usize {
+        self.inner.total_pages()
+    }
+
+    fn used_pages(&self) -> usize {
+        self.inner.used_pages()
+    }
+
+    fn available_pages(&self) -> usize {
+        self.inner.available_pages()
+    }
+}
@@ -1,9 +1,42 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
+
+#[cfg(any(test, feature = "test-support"))]
+extern crate alloc;
+
+mod dynamic;
+#[cfg(feature = "spin")]
+mod locked;
+pub mod object;
+#[cfg(feature = "self-test")]
+mod self_test;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+#[cfg(test)]
+mod tests;
+#[cfg(feature = "alloc-trace")]
+mod trace;
+
+pub use self::dynamic::DynEarlyAllocator;
+#[cfg(feature = "spin")]
+pub use self::locked::{GlobalEarlyAllocator, SpinEarlyAllocator};
+#[cfg(feature = "alloc-trace")]
+pub use self::trace::{AllocTrace, TraceEntry};
 
 use allocator::{BaseAllocator, ByteAllocator, PageAllocator, AllocResult, AllocError};
 use core::alloc::Layout;
+use core::fmt;
+use core::ops::Range;
 use core::ptr::NonNull;
 
+/// Number of alignment classes of [`EarlyAllocator::alloc_align_histogram`].
+#[cfg(feature = "alloc-stats")]
+pub const ALIGN_CLASSES: usize = 8;
+
+/// The pattern written over freed bytes when the `debug-poison` feature is
+/// enabled.
+#[cfg(feature = "debug-poison")]
+pub const POISON_BYTE: u8 = 0xDE;
+
 /// Early memory allocator
 /// Use it before formal bytes-allocator and pages-allocator can work!
 /// This is a double-end memory range:
@@ -16,19 +49,175 @@ use core::ptr::NonNull;
 ///
 /// For bytes area, 'count' records number of allocations.
 /// When it goes down to ZERO, free bytes-used area.
+/// Freeing the most recent allocation also moves `b_pos` back.
+/// The padding skipped by the last aligned allocation is remembered, and the
+/// next allocations small enough to fit in it are served from it first.
+/// Zero-size allocations take no space and are not counted: like with
+/// `GlobalAlloc`, they get a dangling pointer at the requested alignment.
+/// A bytes allocation that would not fit even in the empty arena fails with
+/// [`AllocError::InvalidParam`], while one failing only because of the live
+/// allocations fails with [`AllocError::NoMemory`].
 /// For pages area, it will never be freed!
 ///
-pub struct EarlyAllocator<const PAGE_SIZE: usize> {
+/// `PAGE_SIZE` must be a power of two, otherwise it fails to compile:
+///
+/// ```compile_fail
+/// let _ = bump_allocator::EarlyAllocator::<3000>::new();
+/// ```
+///
+/// With the `alloc-trace` feature, the last `TRACE_LEN` bytes allocations are
+/// recorded, see [`alloc_tagged`](Self::alloc_tagged). With the
+/// `alloc-stats` feature, the bytes allocations are counted by alignment,
+/// see [`alloc_align_histogram`](Self::alloc_align_histogram).
+///
+/// Every bytes allocation is aligned to at least `MIN_ALIGN`, whatever the
+/// alignment of the requested layout, e.g., 64 to keep allocations on
+/// separate cache lines. It must be a power of two.
+///
+/// With `AUTO_RESET` set to `false`, e.g. `EarlyAllocator<PAGE_SIZE, 16, 1,
+/// false>`, freeing bytes never moves `b_pos`, neither when the count goes
+/// down to zero nor for the last allocation: the bytes area is only
+/// reclaimed by [`reset`](Self::reset), [`reset_bytes`](Self::reset_bytes)
+/// or [`dealloc_all_bytes`](Self::dealloc_all_bytes), like a classic arena.
+///
+/// Before `init`, all the usage reporting methods return 0.
+///
+/// Cloning copies the bookkeeping only, not the memory. The clone manages the
+/// same region as the original, so it must not be used to hand out memory,
+/// only to try a sequence of allocations and see whether it would fit.
+#[derive(Clone)]
+pub struct EarlyAllocator<
+    const PAGE_SIZE: usize,
+    const TRACE_LEN: usize = 16,
+    const MIN_ALIGN: usize = 1,
+    const AUTO_RESET: bool = true,
+> {
     start: usize,
     end: usize,
     b_pos: usize,
     p_pos: usize,
     count: usize,
     page_count: usize,
+    /// 页大小，`DynEarlyAllocator` 在 `init` 时设置，否则为 `PAGE_SIZE`
+    page_size: usize,
+    peak_bytes: usize,
+    peak_pages: usize,
+    min_gap: usize,
+    wasted: usize,
+    on_oom: Option<fn(Layout)>,
+    retry_on_oom: Option<fn() -> bool>,
+    on_event: Option<fn(AllocEvent)>,
+    max_alloc: usize,
+    /// 字节分配的大小按此向上取整，1 表示不取整
+    granularity: usize,
+    byte_reserve: usize,
+    slack: Option<(usize, usize)>,
+    padding: Option<(usize, usize)>,
+    frag_page: Option<usize>,
+    frag_off: usize,
+    frozen: bool,
+    single_ended: bool,
+    sealed: Option<(usize, usize)>,
+    reserved_pages: usize,
+    /// 临时池中存活的分配数，及临时池的起点
+    scratch: usize,
+    scratch_base: usize,
+    /// 临时池起点之上存活的永久分配数
+    pinned: usize,
+    #[cfg(feature = "alloc-trace")]
+    trace: AllocTrace<TRACE_LEN>,
+    #[cfg(feature = "alloc-stats")]
+    align_hist: [u32; ALIGN_CLASSES],
+    #[cfg(feature = "debug-warn")]
+    on_warn: Option<fn(fmt::Arguments)>,
+    #[cfg(feature = "debug-warn")]
+    warned_non_lifo: bool,
+    /// 剩余多少次分配后注入失败，0 表示不注入
+    #[cfg(feature = "test-inject")]
+    fail_after: usize,
 }
 
-impl<const PAGE_SIZE: usize> EarlyAllocator<PAGE_SIZE> {
+/// A saved copy of the cursors and counters of an [`EarlyAllocator`].
+///
+/// See [`EarlyAllocator::snapshot`] and [`EarlyAllocator::restore`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EarlyState {
+    b_pos: usize,
+    p_pos: usize,
+    count: usize,
+    page_count: usize,
+    scratch: usize,
+    scratch_base: usize,
+    pinned: usize,
+}
+
+/// The pool of a bytes allocation, see [`EarlyAllocator::alloc_pool`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pool {
+    /// Allocations kept until the whole bytes area is reclaimed, e.g. boot
+    /// data. [`ByteAllocator::alloc`] allocates from it.
+    Permanent,
+    /// Allocations reclaimed as soon as they are all freed, if no permanent
+    /// allocation was made above them.
+    Scratch,
+}
+
+/// A successful allocation or free, passed to the sink set by
+/// [`EarlyAllocator::set_event_sink`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocEvent {
+    /// Bytes allocated by [`ByteAllocator::alloc`], with the requested size.
+    Alloc { addr: usize, size: usize },
+    /// Bytes freed by [`ByteAllocator::dealloc`].
+    Dealloc { addr: usize, size: usize },
+    /// Pages allocated by [`PageAllocator::alloc_pages`].
+    AllocPages { addr: usize, num_pages: usize },
+    /// Pages freed by [`PageAllocator::dealloc_pages`].
+    DeallocPages { addr: usize, num_pages: usize },
+}
+
+/// A consistent view of the usage of an [`EarlyAllocator`], returned by
+/// [`EarlyAllocator::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemStats {
+    /// Total bytes of the arena.
+    pub total_bytes: usize,
+    /// Bytes used by the bytes area.
+    pub used_bytes: usize,
+    /// Bytes between the bytes area and the pages area.
+    pub available_bytes: usize,
+    /// Pages of the whole arena.
+    pub total_pages: usize,
+    /// Pages used by the pages area.
+    pub used_pages: usize,
+    /// Pages that can still be allocated.
+    pub available_pages: usize,
+    /// Number of live bytes allocations.
+    pub byte_allocs: usize,
+    /// Number of live pages.
+    pub page_allocs: usize,
+}
+
+impl<
+        const PAGE_SIZE: usize,
+        const TRACE_LEN: usize,
+        const MIN_ALIGN: usize,
+        const AUTO_RESET: bool,
+    > EarlyAllocator<PAGE_SIZE, TRACE_LEN, MIN_ALIGN, AUTO_RESET>
+{
+    const PAGE_SIZE_CHECK: () = assert!(
+        PAGE_SIZE.is_power_of_two(),
+        "PAGE_SIZE must be a power of two"
+    );
+
+    const MIN_ALIGN_CHECK: () = assert!(
+        MIN_ALIGN.is_power_of_two(),
+        "MIN_ALIGN must be a power of two"
+    );
+
     pub const fn new() -> Self {
+        let () = Self::PAGE_SIZE_CHECK; // 编译期检查 PAGE_SIZE
+        let () = Self::MIN_ALIGN_CHECK;
         Self {
             start: 0,
             end: 0,
@@ -36,97 +225,2033 @@ impl<const PAGE_SIZE: usize> EarlyAllocator<PAGE_SIZE> {
             p_pos: 0,
             count: 0,
             page_count: 0,
+            page_size: PAGE_SIZE,
+            peak_bytes: 0,
+            peak_pages: 0,
+            min_gap: 0,
+            wasted: 0,
+            on_oom: None,
+            retry_on_oom: None,
+            on_event: None,
+            max_alloc: usize::MAX,
+            granularity: 1,
+            byte_reserve: 0,
+            slack: None,
+            padding: None,
+            frag_page: None,
+            frag_off: 0,
+            frozen: false,
+            single_ended: false,
+            sealed: None,
+            reserved_pages: 0,
+            scratch: 0,
+            scratch_base: 0,
+            pinned: 0,
+            #[cfg(feature = "alloc-trace")]
+            trace: AllocTrace::new(),
+            #[cfg(feature = "alloc-stats")]
+            align_hist: [0; ALIGN_CLASSES],
+            #[cfg(feature = "debug-warn")]
+            on_warn: None,
+            #[cfg(feature = "debug-warn")]
+            warned_non_lifo: false,
+            #[cfg(feature = "test-inject")]
+            fail_after: 0,
+        }
+    }
+
+    /// Creates an allocator initialized with the region
+    /// `[start, start + size)`, so that it cannot be used before `init`.
+    ///
+    /// Use [`new`](Self::new) for a `static` allocator that is initialized
+    /// later.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the region is rejected by [`try_init`](Self::try_init).
+    pub fn with_region(start: usize, size: usize) -> Self {
+        let mut early = Self::new();
+        early.init(start, size);
+        early
+    }
+
+    /// Creates an allocator like [`with_region`](Self::with_region), but
+    /// returns [`AllocError::InvalidParam`] instead of panicking if the
+    /// region is rejected by [`try_init`](Self::try_init).
+    pub fn try_with_region(start: usize, size: usize) -> AllocResult<Self> {
+        let mut early = Self::new();
+        early.try_init(start, size)?;
+        Ok(early)
+    }
+
+    /// Returns the number of pages needed to hold `size` bytes.
+    pub const fn pages_for(size: usize) -> usize {
+        size.div_ceil(PAGE_SIZE)
+    }
+
+    /// Returns the layout of `num_pages` pages, aligned to `PAGE_SIZE`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pages cannot be described by a [`Layout`], i.e., their
+    /// total size overflows `isize`.
+    pub fn layout_for_pages(num_pages: usize) -> Layout {
+        num_pages
+            .checked_mul(PAGE_SIZE)
+            .and_then(|size| Layout::from_size_align(size, PAGE_SIZE).ok())
+            .expect("too many pages for a layout")
+    }
+
+    /// Whether `init` has not been called yet, so the cursors do not describe
+    /// any region.
+    const fn is_uninit(&self) -> bool {
+        self.end == 0
+    }
+
+    /// Returns the start address of the arena.
+    pub const fn start_addr(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the (page-aligned) end address of the arena.
+    pub const fn end_addr(&self) -> usize {
+        self.end
+    }
+
+    /// Returns the page size, i.e. `PAGE_SIZE`, for the code not naming the
+    /// const parameter.
+    pub const fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Returns the bytes cursor, i.e. the end of the bytes area.
+    pub const fn byte_cursor(&self) -> usize {
+        self.b_pos
+    }
+
+    /// Returns the pages cursor, i.e. the start of the pages area.
+    pub const fn page_cursor(&self) -> usize {
+        self.p_pos
+    }
+
+    /// Initializes the allocator like [`BaseAllocator::init`], but validates
+    /// the region first.
+    ///
+    /// Returns [`AllocError::InvalidParam`] without changing any state if
+    /// `size` is zero, `start + size` overflows, or the region cannot hold a
+    /// single aligned page.
+    pub fn try_init(&mut self, start: usize, size: usize) -> AllocResult {
+        Self::check_region(start, size, self.page_size).map_err(|_| AllocError::InvalidParam)?;
+        self.init(start, size);
+        Ok(())
+    }
+
+    /// Initializes the allocator like [`try_init`](Self::try_init), but also
+    /// aligns the top of the pages area down to `page_region_align`.
+    ///
+    /// Pages are allocated downward from the top, so with e.g. a 2 MiB
+    /// alignment, allocations of whole 2 MiB blocks are naturally aligned
+    /// without any alignment slack. The tradeoff is that up to
+    /// `page_region_align - PAGE_SIZE` bytes at the end of the region are
+    /// left unused, as they lie above the pages area.
+    ///
+    /// Returns [`AllocError::InvalidParam`] without changing any state if
+    /// `page_region_align` is not a power of two or smaller than
+    /// `PAGE_SIZE`, or if the aligned region is rejected by `try_init`.
+    pub fn init_aligned(
+        &mut self,
+        start: usize,
+        size: usize,
+        page_region_align: usize,
+    ) -> AllocResult {
+        if !page_region_align.is_power_of_two() || page_region_align < self.page_size {
+            return Err(AllocError::InvalidParam);
+        }
+        Self::check_region(start, size, self.page_size).map_err(|_| AllocError::InvalidParam)?;
+        let size = align_down(start + size, page_region_align)
+            .checked_sub(start)
+            .ok_or(AllocError::InvalidParam)?;
+        self.try_init(start, size)
+    }
+
+    /// Points the allocator at a new region `[start, start + size)`, e.g.
+    /// when handing over to a secondary boot stage.
+    ///
+    /// It is identical to `init`: all the prior state of the old region,
+    /// i.e. the cursors, `count` and `page_count`, is wiped. Everything
+    /// allocated from the old region must have been freed before, which is
+    /// checked with a debug assertion.
+    pub fn reinit(&mut self, start: usize, size: usize) {
+        debug_assert!(
+            self.is_empty(),
+            "reinit over live allocations: count {}, page_count {}",
+            self.count,
+            self.page_count
+        );
+        self.init(start, size);
+    }
+
+    fn check_region(start: usize, size: usize, page_size: usize) -> Result<(), &'static str> {
+        if size == 0 {
+            return Err("empty region");
+        }
+        let end = start.checked_add(size).ok_or("region end overflows")?;
+        match align_up(start, page_size) {
+            Some(page) if page < align_down(end, page_size) => Ok(()),
+            _ => Err("region too small to hold a page"),
+        }
+    }
+
+    /// Splits the region `[start, start + size)` into `N` shards of about the
+    /// same size, each managed by an independent allocator, e.g. one per CPU.
+    ///
+    /// The shards are contiguous and cover the whole region. The boundaries
+    /// between them are page aligned.
+    ///
+    /// Returns [`AllocError::InvalidParam`] if `N` is zero, or the region is
+    /// rejected by [`try_init`](Self::try_init), or a shard is too small to
+    /// hold a page.
+    pub fn shard<const N: usize>(start: usize, size: usize) -> AllocResult<[Self; N]> {
+        if N == 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        Self::check_region(start, size, PAGE_SIZE).map_err(|_| AllocError::InvalidParam)?;
+        let end = align_down(start + size, PAGE_SIZE);
+        let chunk = (end - start) / N;
+        let bound = |i: usize| match i {
+            0 => start,
+            i if i == N => end,
+            i => align_down(start + chunk * i, PAGE_SIZE),
+        };
+        for i in 0..N {
+            let size = bound(i + 1).checked_sub(bound(i)).ok_or(AllocError::InvalidParam)?;
+            Self::check_region(bound(i), size, PAGE_SIZE).map_err(|_| AllocError::InvalidParam)?;
+        }
+        Ok(core::array::from_fn(|i| {
+            let mut shard = Self::new();
+            shard.init(bound(i), bound(i + 1) - bound(i));
+            shard
+        }))
+    }
+
+    /// Merges two allocators over adjacent regions into one over their
+    /// union, the inverse of [`shard`](Self::shard).
+    ///
+    /// The allocators may be given in any order. The merged one keeps the
+    /// settings of the allocator of the lower region, e.g. its hooks.
+    ///
+    /// Returns [`AllocError::InvalidParam`] if an allocator is not
+    /// initialized or has live allocations, or if the regions are not
+    /// adjacent.
+    pub fn merge_adjacent(a: Self, b: Self) -> AllocResult<Self> {
+        if a.is_uninit() || b.is_uninit() || !a.is_empty() || !b.is_empty() {
+            return Err(AllocError::InvalidParam);
+        }
+        let (mut low, high) = if a.end == b.start {
+            (a, b)
+        } else if b.end == a.start {
+            (b, a)
+        } else {
+            return Err(AllocError::InvalidParam); // 区域不相邻
+        };
+        let start = low.start;
+        low.reinit(start, high.end - start);
+        Ok(low)
+    }
+
+    /// Checks if `addr` is inside the arena, i.e. `start <= addr < end`.
+    pub const fn contains(&self, addr: usize) -> bool {
+        self.start <= addr && addr < self.end
+    }
+
+    /// Returns the range used by the bytes area, `[start, b_pos)`.
+    pub const fn used_bytes_range(&self) -> Range<usize> {
+        self.start..self.b_pos
+    }
+
+    /// Returns the available range between the two areas, `[b_pos, p_pos)`.
+    pub const fn free_range(&self) -> Range<usize> {
+        self.b_pos..self.p_pos
+    }
+
+    /// Returns the available window between the two areas as its base and
+    /// length, like [`free_range`](Self::free_range).
+    pub const fn free_window(&self) -> (usize, usize) {
+        (self.b_pos, self.p_pos - self.b_pos)
+    }
+
+    /// Seals the available window, so that none of it is handed out
+    /// afterwards, e.g., to donate it to a buddy allocator taking over the
+    /// unused middle of the arena.
+    ///
+    /// The bytes cursor is moved up to the pages cursor, and the window is
+    /// counted as one bytes allocation that is never freed, so that frees
+    /// cannot give it back either. Returns the sealed window as its base
+    /// and length, see also [`sealed_window`](Self::sealed_window).
+    ///
+    /// [`reset`](Self::reset), [`reset_bytes`](Self::reset_bytes),
+    /// [`dealloc_all_bytes`](Self::dealloc_all_bytes) and
+    /// [`restore`](Self::restore) to a state before the call reclaim the
+    /// window like other bytes allocations, so they must not be called while
+    /// the donated memory is in use.
+    ///
+    /// Returns `None` if the window is empty or the allocator is frozen.
+    pub fn seal_window(&mut self) -> Option<(usize, usize)> {
+        let (base, len) = self.free_window();
+        if self.frozen || len == 0 {
+            return None;
+        }
+        self.claim_bytes(base, base + len);
+        self.sealed = Some((base, len));
+        Some((base, len))
+    }
+
+    /// Returns the window sealed by the last
+    /// [`seal_window`](Self::seal_window), until it is reclaimed.
+    pub const fn sealed_window(&self) -> Option<(usize, usize)> {
+        self.sealed
+    }
+
+    /// Returns the range used by the pages area, `[p_pos, end)`.
+    pub const fn used_pages_range(&self) -> Range<usize> {
+        self.p_pos..self.end
+    }
+
+    /// Returns `true` if there is no live bytes allocation and no live page,
+    /// i.e. both areas are fully reclaimed.
+    pub const fn is_empty(&self) -> bool {
+        self.count == 0 && self.page_count == 0
+    }
+
+    /// Reclaims the whole arena at once, as if nothing had been allocated.
+    ///
+    /// The region set by `init` is kept. All pointers and pages handed out
+    /// before the call become invalid, so the caller must make sure none of
+    /// them is still in use.
+    pub fn reset(&mut self) {
+        self.reset_bytes();
+        self.reset_pages();
+    }
+
+    /// Reclaims the whole bytes area, leaving the pages area untouched.
+    ///
+    /// All bytes allocations handed out before the call become invalid,
+    /// while live pages stay valid.
+    pub fn reset_bytes(&mut self) {
+        self.b_pos = self.start;
+        self.count = 0;
+        self.scratch = 0;
+        self.pinned = 0;
+        self.peak_bytes = 0;
+        self.wasted = 0;
+        self.min_gap = self.p_pos - self.b_pos;
+        self.padding = None;
+        self.sealed = None;
+        self.drop_forward_frag_page();
+        self.verify_invariants();
+    }
+
+    /// Frees all bytes allocations at once, whatever the allocation count.
+    ///
+    /// By calling it, the caller asserts that nothing allocated from the
+    /// bytes area is still live, e.g., at the end of a bounded phase whose
+    /// allocations were leaked on purpose. Unlike
+    /// [`reset_bytes`](Self::reset_bytes), the statistics such as the peak
+    /// usage are kept, as if every allocation had been freed with `dealloc`.
+    pub fn dealloc_all_bytes(&mut self) {
+        if self.b_pos != self.start {
+            // 未初始化时 `start` 为 0，不能写入
+            self.poison(self.start, self.b_pos - self.start);
+        }
+        self.b_pos = self.start;
+        self.count = 0;
+        self.scratch = 0;
+        self.pinned = 0;
+        self.padding = None;
+        self.sealed = None;
+        self.drop_forward_frag_page();
+        self.verify_invariants();
+    }
+
+    /// 单端模式下碎片页位于字节区，随字节区一起回收
+    fn drop_forward_frag_page(&mut self) {
+        if self.single_ended {
+            self.frag_page = None;
+        }
+    }
+
+    /// Reclaims the whole pages area, leaving the bytes area untouched.
+    ///
+    /// All pages handed out before the call become invalid, while live bytes
+    /// allocations stay valid.
+    pub fn reset_pages(&mut self) {
+        self.p_pos = self.end;
+        self.page_count = 0;
+        self.reserved_pages = 0;
+        self.peak_pages = 0;
+        self.min_gap = self.p_pos - self.b_pos;
+        self.slack = None;
+        self.frag_page = None;
+        self.verify_invariants();
+    }
+
+    /// Checks that the cursors and the counters are consistent, with debug
+    /// assertions, so that a corrupted state is caught early. It is called
+    /// after every change of the cursors.
+    ///
+    /// The cursors must be ordered as `start <= b_pos <= p_pos <= end`, the
+    /// bytes and pages areas must be empty exactly when there is no live
+    /// allocation in them, and the pages area must be page aligned.
+    pub fn verify_invariants(&self) {
+        let (start, b_pos, p_pos, end) = (self.start, self.b_pos, self.p_pos, self.end);
+        debug_assert!(
+            start <= b_pos && b_pos <= p_pos && p_pos <= end,
+            "cursors out of order: start {:#x}, b_pos {:#x}, p_pos {:#x}, end {:#x}",
+            start,
+            b_pos,
+            p_pos,
+            end
+        );
+        debug_assert!(
+            if AUTO_RESET {
+                (self.count == 0) == (b_pos == start)
+            } else {
+                b_pos != start || self.count == 0
+            },
+            "bytes count mismatches the bytes area: count {}, [{:#x}, {:#x})",
+            self.count,
+            start,
+            b_pos
+        );
+        debug_assert!(
+            (self.page_count == 0) == (p_pos == end),
+            "pages count mismatches the pages area: page_count {}, [{:#x}, {:#x})",
+            self.page_count,
+            p_pos,
+            end
+        );
+        debug_assert!(
+            !matches!(self.padding, Some((pad_start, pad_end)) if pad_start < start || pad_end > b_pos),
+            "alignment padding outside of the bytes area: {:x?}",
+            self.padding
+        );
+        debug_assert!(
+            p_pos % self.page_size == 0 && end % self.page_size == 0,
+            "pages area [{:#x}, {:#x}) not page aligned",
+            p_pos,
+            end
+        );
+    }
+
+    /// Sets the cursors without any check, to build inconsistent states.
+    #[cfg(test)]
+    pub(crate) fn set_cursors(&mut self, b_pos: usize, p_pos: usize) {
+        self.b_pos = b_pos;
+        self.p_pos = p_pos;
+    }
+
+    /// Zeroes the whole pages area when it is reclaimed, so that stale data
+    /// is never handed out again.
+    #[cfg(feature = "secure-pages")]
+    fn scrub(&self, pos: usize, size: usize) {
+        unsafe { core::ptr::write_bytes(pos as *mut u8, 0, size) };
+    }
+
+    #[cfg(not(feature = "secure-pages"))]
+    #[inline(always)]
+    fn scrub(&self, _pos: usize, _size: usize) {}
+
+    /// Sets a hook called with the requested layout every time an allocation
+    /// fails with [`AllocError::NoMemory`], or removes it with `None`.
+    ///
+    /// For pages allocations, the layout covers all the requested pages with
+    /// the requested alignment.
+    pub fn set_oom_hook(&mut self, hook: Option<fn(Layout)>) {
+        self.on_oom = hook;
+    }
+
+    /// Sets a policy called when a bytes allocation would fail with
+    /// [`AllocError::NoMemory`], or removes it with `None`. There is none by
+    /// default.
+    ///
+    /// The policy may clean up and return `true` to retry the allocation
+    /// once. By returning `true`, it promises that all the outstanding bytes
+    /// allocations have been dropped: the bytes area is reclaimed with
+    /// [`dealloc_all_bytes`](Self::dealloc_all_bytes) before the retry.
+    ///
+    /// The policy is not called once the arena is
+    /// [`fully_exhausted`](Self::fully_exhausted).
+    pub fn set_retry_on_oom(&mut self, policy: Option<fn() -> bool>) {
+        self.retry_on_oom = policy;
+    }
+
+    /// Sets a sink called with every successful `alloc`, `dealloc`,
+    /// `alloc_pages` and `dealloc_pages`, e.g. to track the leaks outside of
+    /// the allocator, or removes it with `None`. There is none by default.
+    ///
+    /// Frees ignored by the allocator, e.g. while it is frozen, are not
+    /// reported.
+    pub fn set_event_sink(&mut self, sink: Option<fn(AllocEvent)>) {
+        self.on_event = sink;
+    }
+
+    fn emit(&self, event: AllocEvent) {
+        if let Some(sink) = self.on_event {
+            sink(event);
+        }
+    }
+
+    /// Sets a hook printing the usage warnings, e.g., with `ax_println!`, or
+    /// removes it with `None`. There is none by default.
+    ///
+    /// The first `dealloc` that is neither the most recent allocation nor
+    /// the last live one is warned about once: its bytes are only reclaimed
+    /// when all the bytes allocations are freed, which hints that the
+    /// allocations are not freed in LIFO order.
+    #[cfg(feature = "debug-warn")]
+    pub fn set_warn_hook(&mut self, hook: Option<fn(fmt::Arguments)>) {
+        self.on_warn = hook;
+    }
+
+    /// Makes the `n`-th allocation from now on fail with
+    /// [`AllocError::NoMemory`] regardless of the free space, e.g. to check
+    /// that callers clean up on allocation failures. `0` cancels it.
+    ///
+    /// Both bytes and pages allocations are counted. The OOM hook and retry
+    /// policy are not called for the injected failure.
+    #[cfg(feature = "test-inject")]
+    pub fn fail_after(&mut self, n: usize) {
+        self.fail_after = n;
+    }
+
+    /// Limits the size of a single bytes allocation to `bytes`.
+    ///
+    /// Larger requests fail with [`AllocError::InvalidParam`] before any
+    /// state is touched. There is no limit by default.
+    pub fn set_max_alloc(&mut self, bytes: usize) {
+        self.max_alloc = bytes;
+    }
+
+    /// Rounds the size of each bytes allocation up to a multiple of `g`, so
+    /// that the next allocation starts on a `g` boundary, e.g. to avoid
+    /// aligning it later. It is 1 by default, which disables the rounding.
+    ///
+    /// The rounding is counted by [`wasted_bytes`](Self::wasted_bytes) when
+    /// a block is allocated. Returns [`AllocError::InvalidParam`] if `g` is
+    /// not a power of two, or if bytes are allocated, since they would be
+    /// freed with another size.
+    pub fn set_size_granularity(&mut self, g: usize) -> AllocResult {
+        if !g.is_power_of_two() || self.count != 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        self.granularity = g;
+        Ok(())
+    }
+
+    /// Returns the size granularity set by
+    /// [`set_size_granularity`](Self::set_size_granularity).
+    pub const fn size_granularity(&self) -> usize {
+        self.granularity
+    }
+
+    /// 按粒度取整后的大小，溢出时保持原值，由之后的检查拒绝
+    fn granular_size(&self, size: usize) -> usize {
+        align_up(size, self.granularity).unwrap_or(size)
+    }
+
+    /// Keeps at least `bytes` free for the bytes area, so that the pages
+    /// area cannot starve later critical bytes allocations.
+    ///
+    /// A pages allocation fails with [`AllocError::NoMemory`] if it would
+    /// leave fewer than `bytes` between the two cursors. Bytes allocations
+    /// may still use the reserved bytes. There is no reserve by default.
+    pub fn set_byte_reserve(&mut self, bytes: usize) {
+        self.byte_reserve = bytes;
+    }
+
+    fn notify_oom<T>(&self, res: &AllocResult<T>, layout: impl FnOnce() -> Option<Layout>) {
+        if let (Err(AllocError::NoMemory), Some(hook)) = (res, self.on_oom) {
+            if let Some(layout) = layout() {
+                hook(layout);
+            }
+        }
+    }
+
+    /// Whether the bytes cursor has met the pages cursor, so that neither
+    /// bytes nor pages can be allocated any more, and retrying either kind
+    /// is useless until something is freed.
+    ///
+    /// Returns `false` before `init`.
+    pub fn fully_exhausted(&self) -> bool {
+        !self.is_uninit() && self.b_pos == self.p_pos
+    }
+
+    /// Freezes the allocator, e.g., when the arena is handed off as read-only
+    /// metadata, so that nothing can move the cursors by accident.
+    ///
+    /// While frozen, all the allocations fail with [`AllocError::NoMemory`],
+    /// and `dealloc`, `dealloc_pages` and [`shrink`](Self::shrink) do
+    /// nothing. Explicit resets such as [`reset`](Self::reset) still work.
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    /// Restores normal operation after [`freeze`](Self::freeze).
+    pub fn unfreeze(&mut self) {
+        self.frozen = false;
+    }
+
+    /// Whether the allocator is frozen, see [`freeze`](Self::freeze).
+    pub const fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Switches the single-ended mode on or off. It is off by default.
+    ///
+    /// In the single-ended mode, pages are allocated forward from the bytes
+    /// cursor like bytes, page aligned, and the pages cursor stays at `end`.
+    /// The whole back of the arena is left untouched, e.g., to be handed off
+    /// to another allocator. Each pages allocation then counts as one bytes
+    /// allocation: it is reported by [`ByteAllocator::used_bytes`], and
+    /// reclaimed like bytes, when it is on top of the bytes area or when all
+    /// the bytes allocations are freed.
+    ///
+    /// Returns [`AllocError::InvalidParam`] if anything is allocated.
+    pub fn set_single_ended(&mut self, enabled: bool) -> AllocResult {
+        if !self.is_empty() {
+            return Err(AllocError::InvalidParam);
+        }
+        self.single_ended = enabled;
+        Ok(())
+    }
+
+    /// Whether the single-ended mode is on, see
+    /// [`set_single_ended`](Self::set_single_ended).
+    pub const fn is_single_ended(&self) -> bool {
+        self.single_ended
+    }
+
+    /// Returns the total padding skipped over to align bytes allocations
+    /// since the last `init` or [`reset`](Self::reset), less the bytes of it
+    /// reused by later allocations, plus the bytes added by rounding their
+    /// sizes, see [`set_size_granularity`](Self::set_size_granularity).
+    ///
+    /// The padding is not counted by [`ByteAllocator::used_bytes`].
+    pub fn wasted_bytes(&self) -> usize {
+        self.wasted
+    }
+
+    /// Returns the bytes handed out to the live bytes allocations, i.e.,
+    /// [`ByteAllocator::used_bytes`] less [`wasted_bytes`](Self::wasted_bytes).
+    ///
+    /// `payload_bytes() + wasted_bytes() == used_bytes()` as long as no block
+    /// with padding before it has been freed, since the padding stays counted
+    /// as wasted until the next reset. Then it is saturated at 0.
+    pub fn payload_bytes(&self) -> usize {
+        self.used_bytes().saturating_sub(self.wasted)
+    }
+
+    /// Returns all usage figures at once, so that they are consistent with
+    /// each other when read under a single lock.
+    pub fn stats(&self) -> MemStats {
+        MemStats {
+            total_bytes: self.total_bytes(),
+            used_bytes: self.used_bytes(),
+            available_bytes: self.available_bytes(),
+            total_pages: self.total_pages(),
+            used_pages: self.used_pages(),
+            available_pages: self.available_pages(),
+            byte_allocs: self.count,
+            page_allocs: self.page_count,
+        }
+    }
+
+    /// Writes the usage figures to `out` as `key value` lines, for a text
+    /// scrape endpoint.
+    ///
+    /// The keys are stable: `early_used_bytes`, `early_available_bytes`,
+    /// `early_used_pages`, `early_available_pages`, `early_byte_allocs`,
+    /// `early_page_allocs` and `early_peak_used_bytes`, in this order.
+    pub fn write_metrics(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        let stats = self.stats();
+        let metrics = [
+            ("early_used_bytes", stats.used_bytes),
+            ("early_available_bytes", stats.available_bytes),
+            ("early_used_pages", stats.used_pages),
+            ("early_available_pages", stats.available_pages),
+            ("early_byte_allocs", stats.byte_allocs),
+            ("early_page_allocs", stats.page_allocs),
+            ("early_peak_used_bytes", self.peak_bytes),
+        ];
+        for (key, value) in metrics {
+            writeln!(out, "{} {}", key, value)?;
+        }
+        Ok(())
+    }
+
+    /// Draws the split of the arena to `out` as a bar of `width` columns
+    /// between brackets, e.g. `[###......***]`: `#` for the bytes area, `.`
+    /// for the free space, and `*` for the pages area.
+    ///
+    /// The columns of the two areas are rounded down, so a non-empty area
+    /// smaller than a column is not drawn. Before `init`, the whole bar is free.
+    pub fn dump_bar(&self, out: &mut impl fmt::Write, width: usize) -> fmt::Result {
+        let total = self.total_bytes();
+        let columns = |bytes: usize| match total {
+            0 => 0,
+            _ => (bytes as u128 * width as u128 / total as u128) as usize,
+        };
+        let bytes = columns(self.used_bytes());
+        let pages = columns(self.end - self.p_pos);
+        out.write_char('[')?;
+        for (c, n) in [('#', bytes), ('.', width - bytes - pages), ('*', pages)] {
+            for _ in 0..n {
+                out.write_char(c)?;
+            }
+        }
+        out.write_char(']')
+    }
+
+    /// Returns the used part of the arena by the bytes area, in permille
+    /// (0..=1000). Returns 0 before `init`.
+    pub fn byte_used_permille(&self) -> u32 {
+        permille(self.used_bytes(), self.total_bytes())
+    }
+
+    /// Returns the used part of the pages area capacity, in permille
+    /// (0..=1000), see
+    /// [`currently_allocatable_pages`](Self::currently_allocatable_pages).
+    /// Returns 0 before `init`.
+    pub fn page_used_permille(&self) -> u32 {
+        permille(self.used_pages(), self.currently_allocatable_pages())
+    }
+
+    /// Captures the current allocation state, to be rolled back later with
+    /// [`restore`](Self::restore).
+    pub fn snapshot(&self) -> EarlyState {
+        EarlyState {
+            b_pos: self.b_pos,
+            p_pos: self.p_pos,
+            count: self.count,
+            page_count: self.page_count,
+            scratch: self.scratch,
+            scratch_base: self.scratch_base,
+            pinned: self.pinned,
+        }
+    }
+
+    /// Rolls the allocator back to a state captured by
+    /// [`snapshot`](Self::snapshot).
+    ///
+    /// Everything allocated after the snapshot is reclaimed, so all pointers
+    /// and pages handed out since then become invalid. The snapshot must
+    /// come from this allocator, taken after the last `init`.
+    pub fn restore(&mut self, state: EarlyState) {
+        self.b_pos = state.b_pos;
+        self.p_pos = state.p_pos;
+        self.count = state.count;
+        self.page_count = state.page_count;
+        self.scratch = state.scratch;
+        self.scratch_base = state.scratch_base;
+        self.pinned = state.pinned;
+        self.reserved_pages = self.reserved_pages.min(self.page_count); // 预留的页可能被回滚
+        self.slack = None;
+        self.padding = None;
+        self.frag_page = None;
+        if self.sealed.is_some_and(|(base, _)| base >= self.b_pos) {
+            self.sealed = None; // 封存的窗口已被回收
+        }
+        self.verify_invariants();
+    }
+
+    /// Runs `f` as a transaction of several allocations that must all
+    /// succeed, e.g. the buffers of a descriptor ring.
+    ///
+    /// If `f` returns an error, the allocator is [`restore`](Self::restore)d
+    /// to its state before `f`, so everything allocated by `f` is reclaimed,
+    /// and the error is returned.
+    pub fn transaction<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> AllocResult<T>,
+    ) -> AllocResult<T> {
+        let state = self.snapshot();
+        let res = f(self);
+        if res.is_err() {
+            self.restore(state);
+        }
+        res
+    }
+
+    /// Returns the number of live bytes allocations, i.e., the raw `count`.
+    ///
+    /// Zero-size allocations are not counted.
+    pub fn byte_alloc_count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the number of live pages, i.e., the raw `page_count`.
+    ///
+    /// It counts pages, not calls to `alloc_pages`.
+    pub fn page_alloc_count(&self) -> usize {
+        self.page_count
+    }
+
+    /// Returns the maximum number of bytes used at the same time since the
+    /// last `init` or [`reset`](Self::reset).
+    pub fn peak_used_bytes(&self) -> usize {
+        self.peak_bytes
+    }
+
+    /// Returns the maximum number of pages used at the same time since the
+    /// last `init` or [`reset`](Self::reset).
+    pub fn peak_used_pages(&self) -> usize {
+        self.peak_pages
+    }
+
+    /// Returns the smallest number of bytes left between the two areas since
+    /// the last `init` or [`reset`](Self::reset).
+    ///
+    /// These bytes have never been handed out, so the arena could have been
+    /// that much smaller.
+    pub fn untouched_bytes(&self) -> usize {
+        self.min_gap
+    }
+
+    fn update_min_gap(&mut self) {
+        self.min_gap = self.min_gap.min(self.p_pos - self.b_pos);
+    }
+
+    /// 字节游标回退后，丢弃不再位于已分配部分内的对齐空隙
+    fn drop_stale_padding(&mut self) {
+        if self.padding.is_some_and(|(_, end)| end > self.b_pos) {
+            self.padding = None;
+        }
+    }
+
+    /// 尝试在上一次对齐留下的空隙中分配
+    fn alloc_from_padding(&mut self, size: usize, align: usize) -> Option<usize> {
+        let (start, end) = self.padding?;
+        let pos = align_up(start, align)?;
+        if pos.checked_add(size)? > end {
+            return None;
+        }
+        self.padding = (pos + size < end).then_some((pos + size, end));
+        self.wasted -= size;
+        self.count += 1;
+        Some(pos)
+    }
+
+    /// Allocates bytes like [`ByteAllocator::alloc`], but returns `None` on
+    /// failure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use allocator::BaseAllocator;
+    /// use bump_allocator::EarlyAllocator;
+    /// use core::alloc::Layout;
+    ///
+    /// let mut early = EarlyAllocator::<0x1000>::new();
+    /// early.init(0x8000_0000, 0x1000);
+    /// assert!(early.try_alloc(Layout::from_size_align(0x100, 8).unwrap()).is_some());
+    /// assert!(early.try_alloc(Layout::from_size_align(0x1000, 8).unwrap()).is_none());
+    /// ```
+    pub fn try_alloc(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        self.alloc(layout).ok()
+    }
+
+    /// Allocates pages like [`PageAllocator::alloc_pages`], but returns
+    /// `None` on failure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use allocator::BaseAllocator;
+    /// use bump_allocator::EarlyAllocator;
+    ///
+    /// let mut early = EarlyAllocator::<0x1000>::new();
+    /// early.init(0x8000_0000, 0x2000);
+    /// assert_eq!(early.try_alloc_pages(1, 12), Some(0x8000_1000));
+    /// assert_eq!(early.try_alloc_pages(2, 12), None);
+    /// ```
+    pub fn try_alloc_pages(&mut self, num_pages: usize, align_pow2: usize) -> Option<usize> {
+        self.alloc_pages(num_pages, align_pow2).ok()
+    }
+
+    /// Allocates pages like [`PageAllocator::alloc_pages`], but only if the
+    /// whole block ends at or below `max_addr`, e.g., for devices that can
+    /// only address the low 4 GiB of memory.
+    ///
+    /// Pages are taken from the top of the available area, so the pages
+    /// cursor itself must be low enough. Returns [`AllocError::NoMemory`]
+    /// without allocating anything otherwise.
+    pub fn alloc_pages_below(
+        &mut self,
+        num_pages: usize,
+        align_pow2: usize,
+        max_addr: usize,
+    ) -> AllocResult<usize> {
+        // 预先算出下一块的结束位置，参数非法时交给 `alloc_pages` 报错
+        let block_end = num_pages.checked_mul(self.page_size).and_then(|size| {
+            let align = 1usize.checked_shl(align_pow2.try_into().ok()?)?;
+            if self.single_ended {
+                return align_up(self.b_pos, align.max(self.page_size))?.checked_add(size);
+            }
+            Some(align_down(self.p_pos.checked_sub(size)?, align) + size)
+        });
+        if block_end.is_some_and(|block_end| block_end > max_addr) {
+            return Err(AllocError::NoMemory);
+        }
+        self.alloc_pages(num_pages, align_pow2)
+    }
+
+    /// Allocates pages like [`PageAllocator::alloc_pages`], and also returns
+    /// the number of pages skipped to align the block.
+    ///
+    /// The skipped pages lie between the end of the block and the old pages
+    /// cursor. They can be claimed with
+    /// [`release_alignment_slack`](Self::release_alignment_slack).
+    pub fn alloc_aligned_pages(
+        &mut self,
+        num_pages: usize,
+        align_pow2: usize,
+    ) -> AllocResult<(usize, usize)> {
+        let old_p_pos = self.p_pos;
+        let pos = self.alloc_pages(num_pages, align_pow2)?;
+        if self.single_ended {
+            return Ok((pos, 0)); // 对齐空隙留在字节区
+        }
+        let block_end = pos + num_pages * self.page_size;
+        let slack = pages_between(block_end, old_p_pos, self.page_size);
+        if slack > 0 {
+            self.slack = Some((block_end, slack));
+        }
+        Ok((pos, slack))
+    }
+
+    /// Hands the pages skipped by the last
+    /// [`alloc_aligned_pages`](Self::alloc_aligned_pages) to the caller.
+    ///
+    /// Returns the start address and the number of skipped pages, which are
+    /// then counted as allocated and can be freed with
+    /// [`PageAllocator::dealloc_pages`] like other pages. The skipped pages lie
+    /// above the aligned block, so they can never rejoin the available area
+    /// between the cursors.
+    ///
+    /// Returns `None` if there are no skipped pages, or any pages allocation,
+    /// reset or [`restore`](Self::restore) happened since then.
+    pub fn release_alignment_slack(&mut self) -> Option<(usize, usize)> {
+        if self.frozen {
+            return None;
+        }
+        let (pos, num_pages) = self.slack.take()?;
+        self.page_count += num_pages;
+        self.verify_invariants();
+        Some((pos, num_pages))
+    }
+
+    /// Allocates a chunk of at most one page from the pages area.
+    ///
+    /// Fragments are packed into the current fragment page, and a new page is
+    /// allocated only when the fragment does not fit in the rest of it. The
+    /// first fragment of each page is page-aligned. Fragments cannot be freed
+    /// one by one, their pages are reclaimed with the whole pages area.
+    ///
+    /// Returns [`AllocError::InvalidParam`] if `size` is zero, `align` is not
+    /// a power of two, or either of them is larger than a page.
+    pub fn alloc_page_fragment(&mut self, size: usize, align: usize) -> AllocResult<usize> {
+        if size == 0 || size > self.page_size || !align.is_power_of_two() || align > self.page_size {
+            return Err(AllocError::InvalidParam);
+        }
+        if self.frozen {
+            return Err(AllocError::NoMemory);
+        }
+        if let Some(page) = self.frag_page {
+            let off = align_up(self.frag_off, align).ok_or(AllocError::InvalidParam)?;
+            if off + size <= self.page_size { // 当前页还放得下
+                self.frag_off = off + size;
+                return Ok(page + off);
+            }
+        }
+        let page = self.alloc_pages(1, self.page_size.trailing_zeros() as usize)?;
+        self.frag_page = Some(page);
+        self.frag_off = size;
+        Ok(page)
+    }
+
+    /// Carves `bytes` off the front of the available area by moving the bytes
+    /// cursor, and returns the base of the carved region.
+    ///
+    /// Unlike [`ByteAllocator::alloc`], there is no alignment, size limit or
+    /// OOM hook. The region counts as one bytes allocation, so the bytes area
+    /// stays pinned until it is freed with `dealloc`.
+    ///
+    /// Returns [`AllocError::InvalidParam`] if `bytes` is zero, and
+    /// [`AllocError::NoMemory`] if it does not fit.
+    pub fn reserve_front(&mut self, bytes: usize) -> AllocResult<usize> {
+        if bytes == 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        if self.frozen {
+            return Err(AllocError::NoMemory);
+        }
+        let base = self.b_pos;
+        match base.checked_add(bytes) {
+            Some(b_end) if b_end <= self.p_pos => self.b_pos = b_end,
+            _ => return Err(AllocError::NoMemory),
+        }
+        self.count += 1;
+        self.peak_bytes = self.peak_bytes.max(self.b_pos - self.start);
+        self.update_min_gap();
+        self.verify_invariants();
+        Ok(base)
+    }
+
+    /// Carves `bytes`, rounded up to whole pages, off the back of the
+    /// available area by moving the pages cursor, and returns the base of the
+    /// carved region.
+    ///
+    /// Unlike [`PageAllocator::alloc_pages`], there is no OOM hook. The pages
+    /// are counted as allocated, so the pages area stays pinned until they are
+    /// freed with `dealloc_pages`.
+    ///
+    /// Returns [`AllocError::InvalidParam`] if `bytes` is zero, and
+    /// [`AllocError::NoMemory`] if it does not fit.
+    pub fn reserve_back(&mut self, bytes: usize) -> AllocResult<usize> {
+        if bytes == 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        let size = align_up(bytes, self.page_size).ok_or(AllocError::NoMemory)?;
+        self.bump_pages(size / self.page_size, self.page_size.trailing_zeros() as usize, false)
+    }
+
+    /// Marks the `num_pages` pages from `base` as allocated, e.g. a range
+    /// reported as reserved by the firmware, so that `alloc_pages` never
+    /// hands them out.
+    ///
+    /// Only the range right below the pages cursor can be reserved, i.e.
+    /// ending at [`page_cursor`](Self::page_cursor), since a bump allocator
+    /// cannot leave a hole inside the free space. The pages are counted as
+    /// allocated and in [`reserved_pages`](Self::reserved_pages), so the
+    /// pages area stays pinned until they are freed with `dealloc_pages`.
+    ///
+    /// Returns [`AllocError::InvalidParam`] if `num_pages` is zero, `base`
+    /// is not page aligned, the range does not end at the pages cursor, or
+    /// in single-ended mode. Returns [`AllocError::NoMemory`] if the range
+    /// runs into the bytes area, or if the allocator is frozen.
+    pub fn reserve_pages_at(&mut self, base: usize, num_pages: usize) -> AllocResult {
+        if num_pages == 0 || base % self.page_size != 0 || self.single_ended {
+            return Err(AllocError::InvalidParam);
+        }
+        let size = num_pages
+            .checked_mul(self.page_size)
+            .ok_or(AllocError::InvalidParam)?;
+        if base.checked_add(size) != Some(self.p_pos) {
+            return Err(AllocError::InvalidParam); // 只能紧贴页游标之下
+        }
+        if self.frozen || base < self.b_pos {
+            return Err(AllocError::NoMemory);
+        }
+        self.p_pos = base;
+        self.page_count += num_pages;
+        self.reserved_pages += num_pages;
+        self.slack = None;
+        self.peak_pages = self.peak_pages.max(self.used_pages());
+        self.update_min_gap();
+        self.verify_invariants();
+        Ok(())
+    }
+
+    /// Returns the number of pages reserved by
+    /// [`reserve_pages_at`](Self::reserve_pages_at) since the pages area
+    /// was last reset.
+    pub const fn reserved_pages(&self) -> usize {
+        self.reserved_pages
+    }
+
+    /// Allocates bytes at the fixed address `addr`.
+    ///
+    /// The bytes cursor is moved to the end of the block, so the gap between
+    /// the old cursor and `addr` is wasted. A zero-size allocation takes no
+    /// space and is not counted, it gets a dangling pointer like with
+    /// [`ByteAllocator::alloc`]. Returns [`AllocError::InvalidParam`] if
+    /// `addr` is below the bytes cursor or not aligned to `layout`, and
+    /// [`AllocError::NoMemory`] if the block runs into the pages area.
+    pub fn alloc_at(&mut self, addr: usize, layout: Layout) -> AllocResult<NonNull<u8>> {
+        if self.frozen {
+            return Err(AllocError::NoMemory);
         }
+        if addr < self.b_pos || addr % Self::align_of(layout) != 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        if layout.size() == 0 {
+            // 不占用空间，与 `alloc` 一样返回悬空指针
+            return Ok(NonNull::new(Self::align_of(layout) as *mut u8).unwrap());
+        }
+        let size = self.granular_size(layout.size());
+        let b_end = addr.checked_add(size).ok_or(AllocError::NoMemory)?;
+        if b_end > self.p_pos {
+            return Err(AllocError::NoMemory);
+        }
+        let ptr = NonNull::new(addr as *mut u8).ok_or(AllocError::InvalidParam)?;
+        self.pin_bytes(addr);
+        self.wasted += addr - self.b_pos + size - layout.size();
+        self.b_pos = b_end;
+        self.count += 1;
+        self.peak_bytes = self.peak_bytes.max(self.b_pos - self.start);
+        self.update_min_gap();
+        self.verify_invariants();
+        Ok(ptr)
+    }
+
+    /// Allocates an array of `count` elements of `elem_layout` at once.
+    ///
+    /// Each element is padded to its alignment, so element `i` starts at
+    /// `i * elem_layout.pad_to_align().size()` from the returned pointer. The
+    /// array counts as a single allocation and must be freed as a whole, with
+    /// a layout covering all the `count` padded elements.
+    ///
+    /// An empty array gets a dangling pointer like a zero-size allocation.
+    /// Returns [`AllocError::InvalidParam`] if the size of the array
+    /// overflows.
+    pub fn alloc_array(&mut self, elem_layout: Layout, count: usize) -> AllocResult<NonNull<u8>> {
+        let elem = elem_layout.pad_to_align();
+        let size = elem
+            .size()
+            .checked_mul(count)
+            .ok_or(AllocError::InvalidParam)?;
+        let layout =
+            Layout::from_size_align(size, elem.align()).map_err(|_| AllocError::InvalidParam)?;
+        self.alloc(layout)
+    }
+
+    /// Allocates bytes like [`ByteAllocator::alloc`] and fills them with zero.
+    pub fn alloc_zeroed(&mut self, layout: Layout) -> AllocResult<NonNull<u8>> {
+        let ptr = self.alloc(layout)?;
+        unsafe { core::ptr::write_bytes(ptr.as_ptr(), 0, layout.size()) };
+        Ok(ptr)
+    }
+
+    /// Allocates pages like [`PageAllocator::alloc_pages`] and fills them with
+    /// zero.
+    pub fn alloc_pages_zeroed(
+        &mut self,
+        num_pages: usize,
+        align_pow2: usize,
+    ) -> AllocResult<usize> {
+        let pos = self.alloc_pages(num_pages, align_pow2)?;
+        unsafe { core::ptr::write_bytes(pos as *mut u8, 0, num_pages * self.page_size) };
+        Ok(pos)
+    }
+
+    /// Allocates pages like [`PageAllocator::alloc_pages`], but returns the
+    /// start as a pointer.
+    ///
+    /// Returns [`AllocError::NoMemory`] if the start address would be zero,
+    /// which can only happen when the arena itself is based at address 0.
+    /// Nothing is allocated then.
+    pub fn alloc_pages_ptr(
+        &mut self,
+        num_pages: usize,
+        align_pow2: usize,
+    ) -> AllocResult<NonNull<u8>> {
+        let pos = self.alloc_pages_with(num_pages, align_pow2, true)?;
+        NonNull::new(pos as *mut u8).ok_or(AllocError::NoMemory)
+    }
+
+    /// Allocates pages like [`PageAllocator::alloc_pages`], failing with
+    /// [`AllocError::NoMemory`] before touching any state if `non_null` is
+    /// set and the pages would start at address 0.
+    fn alloc_pages_with(
+        &mut self,
+        num_pages: usize,
+        align_pow2: usize,
+        non_null: bool,
+    ) -> AllocResult<usize> {
+        if self.inject_oom() {
+            return Err(AllocError::NoMemory);
+        }
+        let res = self.bump_pages(num_pages, align_pow2, non_null);
+        if let Ok(pos) = res {
+            debug_assert!(
+                pos % (1 << align_pow2) == 0,
+                "pages base {:#x} not aligned to {:#x}",
+                pos,
+                1usize << align_pow2
+            );
+        }
+        self.notify_oom(&res, || {
+            let size = num_pages.checked_mul(self.page_size)?;
+            Layout::from_size_align(size, 1 << align_pow2).ok()
+        });
+        if let Ok(addr) = res {
+            self.emit(AllocEvent::AllocPages { addr, num_pages });
+        }
+        res
+    }
+
+    /// Allocates pages like [`PageAllocator::alloc_pages`], and returns them
+    /// as a byte slice.
+    ///
+    /// Early boot pages are usually never freed, hence the `'static`
+    /// lifetime.
+    ///
+    /// # Safety
+    ///
+    /// The slice must not be used after the pages are freed, or the arena is
+    /// reset or restored to a state before this call, since the same memory
+    /// may then be handed out again and aliased. The arena must also be
+    /// backed by real memory which is not accessed otherwise.
+    pub unsafe fn alloc_pages_slice(
+        &mut self,
+        num_pages: usize,
+        align_pow2: usize,
+    ) -> AllocResult<&'static mut [u8]> {
+        let ptr = self.alloc_pages_ptr(num_pages, align_pow2)?;
+        let size = num_pages * self.page_size;
+        Ok(unsafe { core::slice::from_raw_parts_mut(ptr.as_ptr(), size) })
+    }
+
+    /// Gives back pages like [`PageAllocator::dealloc_pages`], but checks the
+    /// request first.
+    ///
+    /// Returns [`AllocError::InvalidParam`] without changing any state if more
+    /// pages are freed than allocated, or if the range is not a page-aligned
+    /// range inside the pages area.
+    pub fn try_dealloc_pages(&mut self, pos: usize, num_pages: usize) -> AllocResult {
+        let live = if self.single_ended {
+            self.count > 0
+        } else {
+            num_pages <= self.page_count
+        };
+        if !live || !self.in_pages_area(pos, num_pages) {
+            return Err(AllocError::InvalidParam);
+        }
+        self.dealloc_pages(pos, num_pages);
+        Ok(())
+    }
+
+    /// 检查 `[pos, pos + num_pages * PAGE_SIZE)` 是否为页区内对齐的范围，
+    /// 单端模式下页位于字节区内
+    fn in_pages_area(&self, pos: usize, num_pages: usize) -> bool {
+        let (low, high) = if self.single_ended {
+            (self.start, self.b_pos)
+        } else {
+            (self.p_pos, self.end)
+        };
+        pos % self.page_size == 0
+            && pos >= low
+            && num_pages
+                .checked_mul(self.page_size)
+                .and_then(|size| pos.checked_add(size))
+                .is_some_and(|pos_end| pos_end <= high)
     }
 
-    /// 向上对齐
-    fn align_up(&self, v: usize, align: usize) -> usize {
-        (v + align - 1) & !(align - 1)
+    /// Resizes a bytes allocation from `old_layout` to `new_layout`.
+    ///
+    /// If `ptr` is the most recent allocation, it is grown or shrunk in
+    /// place by moving the bytes cursor. Otherwise a new block is allocated,
+    /// the contents are copied, and the old block is freed.
+    ///
+    /// Either way, it is accounted like a free of the old block followed by
+    /// an allocation of the new one: the size limit, the injected failures,
+    /// the wasted bytes, the trace and the events all see the new block.
+    pub fn realloc(
+        &mut self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> AllocResult<NonNull<u8>> {
+        if self.frozen {
+            return Err(AllocError::NoMemory);
+        }
+        let pos = ptr.as_ptr() as usize;
+        let old_size = self.granular_size(old_layout.size());
+        let new_size = self.granular_size(new_layout.size());
+        if old_layout.size() != 0
+            && new_layout.size() != 0
+            && pos + old_size == self.b_pos
+            && pos % Self::align_of(new_layout) == 0
+        {
+            // 栈顶块，原地调整
+            if new_layout.size() > self.max_alloc {
+                return Err(AllocError::InvalidParam);
+            }
+            if self.inject_oom() {
+                return Err(AllocError::NoMemory);
+            }
+            let res = match pos.checked_add(new_size) {
+                Some(end) if end <= self.p_pos => Ok(ptr),
+                _ => Err(AllocError::NoMemory),
+            };
+            self.notify_oom(&res, || Some(new_layout));
+            res?;
+            self.b_pos = pos + new_size;
+            // 与分配一样计入取整多出的部分，释放旧块时不扣除
+            self.wasted += new_size - new_layout.size();
+            self.drop_stale_padding();
+            self.peak_bytes = self.peak_bytes.max(self.b_pos - self.start);
+            self.update_min_gap();
+            self.verify_invariants();
+            self.emit(AllocEvent::Dealloc {
+                addr: pos,
+                size: old_layout.size(),
+            });
+            self.record_alloc(ptr, new_layout, 0);
+            return Ok(ptr);
+        }
+        let new_ptr = self.alloc(new_layout)?;
+        let copy_size = old_layout.size().min(new_layout.size());
+        unsafe { core::ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_ptr(), copy_size) };
+        self.dealloc(ptr, old_layout);
+        Ok(new_ptr)
+    }
+
+    /// Shrinks a bytes allocation from `old_layout` to the smaller
+    /// `new_layout`, and returns the same `ptr`.
+    ///
+    /// If `ptr` is the most recent allocation, the freed tail is given back
+    /// by moving the bytes cursor. Otherwise, or if the new size is 0, nothing
+    /// is done, since the bytes inside the area cannot be reclaimed.
+    pub fn shrink(
+        &mut self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> NonNull<u8> {
+        debug_assert!(
+            new_layout.size() <= old_layout.size(),
+            "shrink to a larger layout"
+        );
+        let pos = ptr.as_ptr() as usize;
+        let (old_size, new_size) = (
+            self.granular_size(old_layout.size()),
+            self.granular_size(new_layout.size()),
+        );
+        if !self.frozen
+            && new_layout.size() != 0
+            && new_size < old_size
+            && pos + old_size == self.b_pos
+        {
+            // 栈顶块，回收尾部
+            let new_end = pos + new_size;
+            self.poison(new_end, self.b_pos - new_end);
+            self.b_pos = new_end;
+            self.drop_stale_padding();
+            self.update_min_gap();
+            self.verify_invariants();
+        }
+        ptr
+    }
+
+    /// Returns the largest size that a bytes allocation with the alignment
+    /// of `layout` could get right now, taking the padding needed to align
+    /// the bytes cursor into account.
+    pub fn available_for(&self, layout: Layout) -> usize {
+        if self.is_uninit() {
+            return 0;
+        }
+        match align_up(self.b_pos, Self::align_of(layout)) {
+            Some(b_pos) if b_pos <= self.p_pos => self.p_pos - b_pos,
+            _ => 0,
+        }
+    }
+
+    /// Returns the largest `size` for which
+    /// `alloc(Layout::from_size_align(size, align))` would succeed right now.
+    ///
+    /// Unlike [`available_for`](Self::available_for), it also honours the
+    /// limit set by [`set_max_alloc`](Self::set_max_alloc), and returns 0 if
+    /// `align` is not a power of two.
+    pub fn max_alloc_bytes(&self, align: usize) -> usize {
+        if !align.is_power_of_two() || self.is_uninit() {
+            return 0;
+        }
+        let align = align.max(MIN_ALIGN);
+        let size = match align_up(self.b_pos, align) {
+            Some(b_pos) if b_pos <= self.p_pos => self.p_pos - b_pos,
+            _ => return 0,
+        };
+        // `Layout` 要求按对齐向上取整后不超过 `isize::MAX`
+        let layout_max = isize::MAX as usize - (align - 1);
+        align_down(size, self.granularity)
+            .min(self.max_alloc)
+            .min(layout_max)
+    }
+
+    /// Returns the largest `num_pages` for which
+    /// `alloc_pages(num_pages, align_pow2)` would succeed right now, or 0 if
+    /// none would.
+    ///
+    /// Unlike [`available_pages`](PageAllocator::available_pages), it takes
+    /// the pages lost to align the block into account.
+    pub fn max_aligned_pages(&self, align_pow2: usize) -> usize {
+        if self.is_uninit() || self.frozen || align_pow2 >= usize::BITS as usize {
+            return 0;
+        }
+        let align = (1usize << align_pow2).max(self.page_size);
+        let (low, high) = if self.single_ended {
+            // 块从 b_pos 之上向前分配，其后为字节区保留空间
+            (self.b_pos, self.p_pos.saturating_sub(self.byte_reserve))
+        } else {
+            // 块的起点不低于 b_pos 加上保留的空间
+            (self.b_pos.saturating_add(self.byte_reserve), self.p_pos)
+        };
+        match align_up(low, align) {
+            Some(low) if low <= high => (high - low) / self.page_size,
+            _ => 0,
+        }
+    }
+
+    /// Allocates bytes like [`ByteAllocator::alloc`], and also returns the
+    /// number of bytes that the bytes cursor advanced by, e.g. for an
+    /// allocator built on top that accounts for the arena usage.
+    ///
+    /// It is the requested size plus the alignment padding before the
+    /// returned pointer, which is `size - layout.size()`. The end is only
+    /// rounded up by [`set_size_granularity`](Self::set_size_granularity),
+    /// otherwise exactly `layout.size()` bytes are usable from the pointer.
+    /// It is 0 for a zero-size allocation, or one served from the padding of
+    /// a previous allocation, as the cursor does not move.
+    pub fn alloc_with_size(&mut self, layout: Layout) -> AllocResult<(NonNull<u8>, usize)> {
+        let b_pos = self.b_pos;
+        let ptr = self.alloc(layout)?;
+        let base = if self.b_pos == b_pos {
+            self.b_pos // 游标未移动
+        } else if (ptr.as_ptr() as usize) < b_pos {
+            self.start // 重试策略先回收了整个字节区
+        } else {
+            b_pos
+        };
+        Ok((ptr, self.b_pos - base))
+    }
+
+    /// Allocates bytes in `pool`, which is reclaimed independently of the
+    /// other one.
+    ///
+    /// Both pools bump forward from the same cursor. The allocations of the
+    /// [`Pool::Scratch`] pool, freed with [`dealloc_pool`](Self::dealloc_pool),
+    /// are reclaimed when the last of them is freed, if no live permanent
+    /// allocation lies above the first of them. The permanent ones are
+    /// reclaimed as usual, when the whole bytes area is freed.
+    pub fn alloc_pool(&mut self, layout: Layout, pool: Pool) -> AllocResult<NonNull<u8>> {
+        if pool == Pool::Permanent || layout.size() == 0 {
+            return self.alloc(layout);
+        }
+        // 临时分配不使用对齐空隙，都位于起点之上
+        let padding = self.padding.take();
+        let pinned = self.pinned;
+        let res = self.alloc_with_size(layout);
+        let Ok((ptr, size)) = res else {
+            self.padding = padding;
+            return res.map(|(ptr, _)| ptr);
+        };
+        if self.scratch > 0 {
+            self.pinned = pinned; // 不计为永久分配
+        } else {
+            // 新的临时池，所有存活的分配都在起点之下
+            self.scratch_base = self.b_pos - size;
+            self.pinned = 0;
+        }
+        self.scratch += 1;
+        if self.padding.is_none() && padding.is_some_and(|(_, end)| end <= self.scratch_base) {
+            self.padding = padding;
+        }
+        Ok(ptr)
+    }
+
+    /// Frees bytes allocated by [`alloc_pool`](Self::alloc_pool) in `pool`.
+    pub fn dealloc_pool(&mut self, pos: NonNull<u8>, layout: Layout, pool: Pool) {
+        self.free_bytes(pos.as_ptr() as usize, layout.size(), pool);
+    }
+
+    /// Allocates bytes like [`ByteAllocator::alloc`], recording `site` along
+    /// with the layout in the allocation trace.
+    ///
+    /// `site` is a caller-chosen id of the allocating subsystem. Without the
+    /// `alloc-trace` feature it is ignored.
+    #[inline]
+    pub fn alloc_tagged(&mut self, layout: Layout, site: u16) -> AllocResult<NonNull<u8>> {
+        if self.frozen || self.inject_oom() {
+            return Err(AllocError::NoMemory);
+        }
+        let mut res = self.bump_bytes(layout);
+        if matches!(res, Err(AllocError::NoMemory))
+            && !self.fully_exhausted()
+            && self.retry_on_oom.is_some_and(|f| f())
+        {
+            self.dealloc_all_bytes(); // 调用方保证已没有存活的分配
+            res = self.bump_bytes(layout);
+        }
+        self.notify_oom(&res, || Some(layout));
+        if let Ok(ptr) = res {
+            self.record_alloc(ptr, layout, site);
+        }
+        res
+    }
+
+    /// 记录一次成功的字节分配：追踪、对齐统计和事件
+    fn record_alloc(&mut self, ptr: NonNull<u8>, layout: Layout, site: u16) {
+        #[cfg(feature = "alloc-trace")]
+        self.trace.record(TraceEntry {
+            size: layout.size(),
+            align: layout.align(),
+            site,
+        });
+        #[cfg(not(feature = "alloc-trace"))]
+        let _ = site;
+        #[cfg(feature = "alloc-stats")]
+        {
+            // 最后一档包含所有更大的对齐
+            let class = (layout.align().trailing_zeros() as usize).min(ALIGN_CLASSES - 1);
+            self.align_hist[class] = self.align_hist[class].saturating_add(1);
+        }
+        self.emit(AllocEvent::Alloc {
+            addr: ptr.as_ptr() as usize,
+            size: layout.size(),
+        });
+    }
+
+    /// Returns the number of successful bytes allocations by the requested
+    /// alignment: 1, 2, 4, 8, 16, 32, 64, and 128 or more.
+    ///
+    /// The counts are kept since the allocator was created, across `init`
+    /// and resets.
+    #[cfg(feature = "alloc-stats")]
+    pub fn alloc_align_histogram(&self) -> [u32; ALIGN_CLASSES] {
+        self.align_hist
+    }
+
+    /// Returns the trace of the last `TRACE_LEN` bytes allocations.
+    #[cfg(feature = "alloc-trace")]
+    pub fn trace(&self) -> &AllocTrace<TRACE_LEN> {
+        &self.trace
+    }
+
+    /// Writes the allocation trace to `out`, one allocation per line from the
+    /// oldest to the newest.
+    #[cfg(feature = "alloc-trace")]
+    pub fn dump_trace(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        write!(out, "{}", self.trace)
+    }
+
+    /// Returns the bytes that the bytes area could still grow into, i.e. the
+    /// range from `start` up to the current pages cursor.
+    ///
+    /// Unlike [`ByteAllocator::total_bytes`], it shrinks as pages are
+    /// allocated.
+    pub fn byte_capacity_remaining(&self) -> usize {
+        if self.is_uninit() {
+            return 0;
+        }
+        self.p_pos - self.start
+    }
+
+    /// Returns the pages that the pages area could grow to, i.e. the whole
+    /// pages from the bytes cursor up to `end`, including the used ones.
+    ///
+    /// Unlike [`PageAllocator::total_pages`], it shrinks as bytes are
+    /// allocated.
+    pub fn currently_allocatable_pages(&self) -> usize {
+        if self.is_uninit() {
+            return 0;
+        }
+        pages_between(self.b_pos, self.end, self.page_size)
     }
 
-    /// 向下对齐
-    fn align_down(&self, v: usize, align: usize) -> usize {
-        v &!(align - 1)
+    /// Fills freed bytes with [`POISON_BYTE`] to expose use-after-free.
+    #[cfg(feature = "debug-poison")]
+    fn poison(&self, pos: usize, size: usize) {
+        unsafe { core::ptr::write_bytes(pos as *mut u8, POISON_BYTE, size) };
+    }
+
+    #[cfg(not(feature = "debug-poison"))]
+    #[inline(always)]
+    fn poison(&self, _pos: usize, _size: usize) {}
+
+    /// 第一次非栈顶的释放时提醒一次
+    #[cfg(feature = "debug-warn")]
+    fn warn_non_lifo(&mut self, pos: usize, size: usize) {
+        let Some(hook) = self.on_warn else {
+            return;
+        };
+        if !self.warned_non_lifo {
+            self.warned_non_lifo = true;
+            hook(format_args!(
+                "early allocator: non-LIFO free of {:#x} ({:#x} bytes), \
+                 reclaimed only when all the bytes allocations are freed",
+                pos, size
+            ));
+        }
+    }
+
+    #[cfg(not(feature = "debug-warn"))]
+    #[inline(always)]
+    fn warn_non_lifo(&mut self, _pos: usize, _size: usize) {}
+
+    /// 计数一次分配，返回是否注入失败
+    #[cfg(feature = "test-inject")]
+    fn inject_oom(&mut self) -> bool {
+        if self.fail_after == 0 {
+            return false;
+        }
+        self.fail_after -= 1;
+        self.fail_after == 0
+    }
+
+    #[cfg(not(feature = "test-inject"))]
+    #[inline(always)]
+    fn inject_oom(&mut self) -> bool {
+        false
+    }
+}
+
+/// 计算 `[low, high)` 中的整页数，不会溢出，游标顺序颠倒时返回 0
+#[inline]
+fn pages_between(low: usize, high: usize, page_size: usize) -> usize {
+    debug_assert!(low <= high, "page range [{:#x}, {:#x}) reversed", low, high);
+    high.checked_sub(low).map_or(0, |size| size / page_size)
+}
+
+/// 计算千分比，`total` 为 0 时返回 0
+fn permille(used: usize, total: usize) -> u32 {
+    if total == 0 {
+        return 0;
+    }
+    (used as u128 * 1000 / total as u128).min(1000) as u32
+}
+
+/// 向上对齐，溢出时返回 `None`
+#[inline]
+fn align_up(v: usize, align: usize) -> Option<usize> {
+    debug_assert!(align.is_power_of_two());
+    v.checked_add(align - 1).map(|v| v & !(align - 1))
+}
+
+/// 向下对齐
+#[inline]
+fn align_down(v: usize, align: usize) -> usize {
+    debug_assert!(align.is_power_of_two());
+    v & !(align - 1)
+}
+
+impl<
+        const PAGE_SIZE: usize,
+        const TRACE_LEN: usize,
+        const MIN_ALIGN: usize,
+        const AUTO_RESET: bool,
+    > fmt::Debug for EarlyAllocator<PAGE_SIZE, TRACE_LEN, MIN_ALIGN, AUTO_RESET>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // 单行输出，便于在串口日志中检索
+        write!(
+            f,
+            "EarlyAllocator {{ start: {:#x}, end: {:#x}, b_pos: {:#x}, p_pos: {:#x}, \
+             count: {:#x}, page_count: {:#x}, available_bytes: {:#x}, available_pages: {:#x} }}",
+            self.start,
+            self.end,
+            self.b_pos,
+            self.p_pos,
+            self.count,
+            self.page_count,
+            self.available_bytes(),
+            self.available_pages(),
+        )
+    }
+}
+
+impl<
+        const PAGE_SIZE: usize,
+        const TRACE_LEN: usize,
+        const MIN_ALIGN: usize,
+        const AUTO_RESET: bool,
+    > Default for EarlyAllocator<PAGE_SIZE, TRACE_LEN, MIN_ALIGN, AUTO_RESET>
+{
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-impl<const PAGE_SIZE: usize> BaseAllocator for EarlyAllocator<PAGE_SIZE> {
+impl<
+        const PAGE_SIZE: usize,
+        const TRACE_LEN: usize,
+        const MIN_ALIGN: usize,
+        const AUTO_RESET: bool,
+    > BaseAllocator for EarlyAllocator<PAGE_SIZE, TRACE_LEN, MIN_ALIGN, AUTO_RESET>
+{
+    /// Initializes the allocator with the region `[start, start + size)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the region is rejected by
+    /// [`try_init`](EarlyAllocator::try_init).
     fn init(&mut self, start: usize, size: usize){
+        if let Err(msg) = Self::check_region(start, size, self.page_size) {
+            panic!("invalid early allocator region [{:#x}, +{:#x}): {}", start, size, msg);
+        }
         self.start = start;
-        self.end = (start + size) / PAGE_SIZE * PAGE_SIZE; // 对齐到页边界
-        self.b_pos = start;
-        self.p_pos = start + size;
+        self.end = align_down(start + size, self.page_size); // 对齐到页边界
+        self.p_pos = self.end; // 使游标先落在新区域内
+        self.page_count = 0;
+        self.reset();
     }
+    /// Extends the arena with a region that directly follows the current
+    /// `end`. Only contiguous regions are accepted, since the arena must stay
+    /// a single range.
+    ///
+    /// The pages area is at the top of the arena, so it cannot grow while
+    /// pages are live: the new pages would lie above them, out of reach of
+    /// the pages cursor.
+    ///
+    /// Returns [`AllocError::InvalidParam`] if the allocator is not
+    /// initialized, pages are live, or the region is not adjacent, overflows,
+    /// or does not add at least one whole page.
     fn add_memory(&mut self, start: usize, size: usize) -> AllocResult {
-        unimplemented!() // 不支持
+        if self.is_uninit() || self.p_pos != self.end {
+            return Err(AllocError::InvalidParam);
+        }
+        if start != self.end {
+            return Err(AllocError::InvalidParam); // 必须与当前区域相邻
+        }
+        let new_end = start.checked_add(size).ok_or(AllocError::InvalidParam)?;
+        let new_end = align_down(new_end, self.page_size);
+        if new_end == self.end {
+            return Err(AllocError::InvalidParam); // 不足一页
+        }
+        self.p_pos = new_end;
+        self.end = new_end;
+        self.verify_invariants();
+        Ok(())
     }
 }
 
-impl<const PAGE_SIZE: usize> ByteAllocator for EarlyAllocator<PAGE_SIZE> {
-    fn alloc(&mut self, layout: Layout) -> AllocResult<NonNull<u8>> {
+impl<
+        const PAGE_SIZE: usize,
+        const TRACE_LEN: usize,
+        const MIN_ALIGN: usize,
+        const AUTO_RESET: bool,
+    > EarlyAllocator<PAGE_SIZE, TRACE_LEN, MIN_ALIGN, AUTO_RESET>
+{
+    /// Returns the alignment of a bytes allocation of `layout`.
+    #[inline]
+    const fn align_of(layout: Layout) -> usize {
+        if layout.align() < MIN_ALIGN {
+            MIN_ALIGN
+        } else {
+            layout.align()
+        }
+    }
+
+    #[inline]
+    fn bump_bytes(&mut self, layout: Layout) -> AllocResult<NonNull<u8>> {
         let size = layout.size();
-        let align = layout.align();
-        let b_pos = self.align_up(self.b_pos, align); // 起始位置对齐
-        if b_pos + size <= self.p_pos { // 检查空间是否足够
-            self.b_pos = b_pos + size;
-            self.count += 1;
+        if size == 0 {
+            // 不占用空间，返回按对齐悬空的指针
+            return Ok(NonNull::new(Self::align_of(layout) as *mut u8).unwrap());
+        }
+        let align = Self::align_of(layout);
+        if size > self.max_alloc {
+            return Err(AllocError::InvalidParam);
+        }
+        let size = self.granular_size(size);
+        if self.exceeds_arena(size, align) {
+            return Err(AllocError::InvalidParam);
+        }
+        if let Some(pos) = self.alloc_from_padding(size, align) {
+            self.wasted += size - layout.size(); // 取整多出的部分
+            self.pin_bytes(pos);
+            self.verify_invariants();
+            return Ok(NonNull::new(pos as *mut u8).unwrap());
+        }
+        let b_pos = if align <= core::mem::align_of::<usize>() && self.b_pos & (align - 1) == 0 {
+            self.b_pos // 常见的小对齐且已对齐，无需计算
+        } else {
+            align_up(self.b_pos, align).ok_or(AllocError::NoMemory)? // 起始位置对齐
+        };
+        if b_pos > self.p_pos {
+            return Err(AllocError::NoMemory); // 对齐后已越过空闲区
+        }
+        let b_end = b_pos.checked_add(size).ok_or(AllocError::NoMemory)?;
+        if b_end <= self.p_pos { // 检查空间是否足够
+            self.claim_bytes(b_pos, b_end);
+            self.wasted += size - layout.size();
             Ok(NonNull::new(b_pos as *mut u8).unwrap())
         } else {
             Err(AllocError::NoMemory)
         }
     }
 
-    fn dealloc(&mut self, pos: NonNull<u8>, layout: Layout) {
+    /// 即使整个区域为空也放不下，重试或释放都无济于事
+    fn exceeds_arena(&self, size: usize, align: usize) -> bool {
+        if self.is_uninit() {
+            return false;
+        }
+        let end = align_up(self.start, align).and_then(|pos| pos.checked_add(size));
+        !matches!(end, Some(end) if end <= self.end)
+    }
+
+    /// 临时池起点之上的永久分配会阻止临时池的回收
+    fn pin_bytes(&mut self, pos: usize) {
+        if self.scratch > 0 && pos >= self.scratch_base {
+            self.pinned += 1;
+        }
+    }
+
+    /// 返回释放是否被接受
+    fn free_bytes(&mut self, pos: usize, size: usize, pool: Pool) -> bool {
+        if self.frozen {
+            return false; // 冻结时忽略释放
+        }
+        if size == 0 {
+            return true; // 零大小的分配没有计数
+        }
+        if self.count == 0 {
+            return false; // 多余的释放
+        }
+        let size = self.granular_size(size);
+        let in_range =
+            pos >= self.start && pos.checked_add(size).is_some_and(|end| end <= self.b_pos);
+        debug_assert!(in_range, "dealloc {:#x} outside of the bytes area", pos);
+        if !in_range {
+            return false; // 忽略不属于字节区的指针
+        }
         self.count -= 1;
-        if self.count == 0 { // 没有分配时释放空间
-            self.b_pos = self.start;
+        let scratch_done = match pool {
+            Pool::Permanent => {
+                if self.scratch > 0 && pos >= self.scratch_base {
+                    self.pinned = self.pinned.saturating_sub(1);
+                }
+                false
+            }
+            Pool::Scratch => {
+                self.scratch = self.scratch.saturating_sub(1);
+                self.scratch == 0 && self.pinned == 0
+            }
+        };
+        if !AUTO_RESET {
+            // 不移动 b_pos，只能显式回收
+            self.poison(pos, size);
+        } else if self.count == 0 { // 没有分配时释放空间
+            self.dealloc_all_bytes();
+        } else if scratch_done && self.scratch_base < self.b_pos {
+            // 临时池之上没有永久分配，整体回收
+            self.poison(self.scratch_base, self.b_pos - self.scratch_base);
+            self.b_pos = self.scratch_base;
+            self.drop_stale_padding();
+        } else if pos + size == self.b_pos {
+            // 释放的是栈顶块，直接回退 b_pos
+            self.poison(pos, size);
+            self.b_pos = pos;
+            self.drop_stale_padding();
+        } else {
+            self.warn_non_lifo(pos, size);
+        }
+        self.verify_invariants();
+        true
+    }
+
+    /// 将 `[b_pos, b_end)` 记为一次字节分配，`b_pos` 之前的部分是对齐空隙
+    fn claim_bytes(&mut self, b_pos: usize, b_end: usize) {
+        self.pin_bytes(b_pos);
+        if b_pos > self.b_pos {
+            // 记住最近的对齐空隙，供之后的小分配使用
+            self.padding = Some((self.b_pos, b_pos));
+        }
+        self.wasted += b_pos - self.b_pos;
+        self.b_pos = b_end;
+        self.count += 1;
+        self.peak_bytes = self.peak_bytes.max(self.b_pos - self.start);
+        self.update_min_gap();
+        self.verify_invariants();
+    }
+
+    /// 单端模式下页从 `b_pos` 向前分配
+    fn bump_pages_forward(
+        &mut self,
+        size: usize,
+        align: usize,
+        non_null: bool,
+    ) -> AllocResult<usize> {
+        let pos = align_up(self.b_pos, align.max(self.page_size)).ok_or(AllocError::NoMemory)?;
+        if non_null && pos == 0 {
+            return Err(AllocError::NoMemory); // 不能作为指针返回
+        }
+        let end = pos.checked_add(size).ok_or(AllocError::NoMemory)?;
+        let gap = self.p_pos.checked_sub(end);
+        if !gap.is_some_and(|gap| gap >= self.byte_reserve) {
+            return Err(AllocError::NoMemory);
+        }
+        self.slack = None;
+        self.claim_bytes(pos, end);
+        Ok(pos)
+    }
+
+    /// 分配页，`non_null` 时拒绝从地址 0 开始的页
+    fn bump_pages(
+        &mut self,
+        num_pages: usize,
+        align_pow2: usize,
+        non_null: bool,
+    ) -> AllocResult<usize> {
+        if self.frozen {
+            return Err(AllocError::NoMemory);
+        }
+        if num_pages == 0 || align_pow2 >= usize::BITS as usize {
+            return Err(AllocError::InvalidParam);
+        }
+        let align = 1 << align_pow2;
+        let size = num_pages
+            .checked_mul(self.page_size)
+            .ok_or(AllocError::InvalidParam)?; // 超出整个地址空间
+        if self.single_ended {
+            return self.bump_pages_forward(size, align, non_null);
+        }
+        let p_pos = self.p_pos.checked_sub(size).ok_or(AllocError::NoMemory)?;
+        let p_pos = align_down(p_pos, align); // 起始位置对齐
+        if non_null && p_pos == 0 {
+            return Err(AllocError::NoMemory); // 不能作为指针返回
+        }
+        let gap = p_pos.checked_sub(self.b_pos);
+        if gap.is_some_and(|gap| gap >= self.byte_reserve) { // 检查空间是否足够，并为字节区保留空间
+            debug_assert!(
+                p_pos >= self.start && p_pos % self.page_size == 0,
+                "pages base {:#x} below the arena start {:#x} or not page aligned",
+                p_pos,
+                self.start
+            );
+            self.p_pos = p_pos;
+            self.page_count += num_pages;
+            self.slack = None;
+            self.peak_pages = self.peak_pages.max(self.used_pages());
+            self.update_min_gap();
+            self.verify_invariants();
+            Ok(p_pos)
+        } else {
+            Err(AllocError::NoMemory)
+        }
+    }
+}
+
+impl<
+        const PAGE_SIZE: usize,
+        const TRACE_LEN: usize,
+        const MIN_ALIGN: usize,
+        const AUTO_RESET: bool,
+    > ByteAllocator for EarlyAllocator<PAGE_SIZE, TRACE_LEN, MIN_ALIGN, AUTO_RESET>
+{
+    #[inline]
+    fn alloc(&mut self, layout: Layout) -> AllocResult<NonNull<u8>> {
+        self.alloc_tagged(layout, 0)
+    }
+
+    #[inline]
+    fn dealloc(&mut self, pos: NonNull<u8>, layout: Layout) {
+        let addr = pos.as_ptr() as usize;
+        if self.free_bytes(addr, layout.size(), Pool::Permanent) {
+            self.emit(AllocEvent::Dealloc {
+                addr,
+                size: layout.size(),
+            });
         }
     }
 
     fn total_bytes(&self) -> usize {
-        self.p_pos - self.start
+        if self.is_uninit() {
+            return 0;
+        }
+        self.end - self.start
     }
 
     fn used_bytes(&self) -> usize {
+        if self.is_uninit() {
+            return 0;
+        }
         self.b_pos - self.start
     }
 
     fn available_bytes(&self) -> usize {
+        if self.is_uninit() {
+            return 0;
+        }
         self.p_pos - self.b_pos
     }
 }
 
-impl<const PAGE_SIZE: usize> PageAllocator for EarlyAllocator<PAGE_SIZE> {
+impl<
+        const PAGE_SIZE: usize,
+        const TRACE_LEN: usize,
+        const MIN_ALIGN: usize,
+        const AUTO_RESET: bool,
+    > PageAllocator for EarlyAllocator<PAGE_SIZE, TRACE_LEN, MIN_ALIGN, AUTO_RESET>
+{
     const PAGE_SIZE: usize = PAGE_SIZE;
 
     fn alloc_pages(&mut self, num_pages: usize, align_pow2: usize) -> AllocResult<usize> {
-        let align = 1 << align_pow2;
-        let p_pos = self.align_down(self.p_pos - num_pages * PAGE_SIZE, align); // 起始位置对齐
-        if p_pos >= self.b_pos { // 检查空间是否足够
-            self.p_pos = p_pos;
-            self.page_count += num_pages;
-            Ok(p_pos)
-        } else {
-            Err(AllocError::NoMemory)
-        }
+        self.alloc_pages_with(num_pages, align_pow2, false)
     }
 
+    /// Frees pages, ignoring ranges that are not page aligned or not inside
+    /// the pages area (with a debug assertion), so that a bogus free cannot
+    /// reset the pages area while live pages remain.
     fn dealloc_pages(&mut self, pos: usize, num_pages: usize) {
-        self.page_count -= num_pages;
+        if self.frozen {
+            return;
+        }
+        let in_range = self.in_pages_area(pos, num_pages);
+        debug_assert!(in_range, "dealloc_pages {:#x} outside of the pages area", pos);
+        if !in_range {
+            return; // 忽略不属于页区的范围
+        }
+        if self.single_ended {
+            // 按字节分配释放，栈顶的页可以直接回收
+            if self.free_bytes(pos, num_pages * self.page_size, Pool::Permanent) {
+                self.emit(AllocEvent::DeallocPages { addr: pos, num_pages });
+            }
+            return;
+        }
+        self.page_count = self.page_count.saturating_sub(num_pages);
         if self.page_count == 0 { // 没有分配时释放空间
+            self.scrub(self.p_pos, self.end - self.p_pos);
             self.p_pos = self.end;
+            self.reserved_pages = 0;
+            self.frag_page = None;
         }
+        self.verify_invariants();
+        self.emit(AllocEvent::DeallocPages { addr: pos, num_pages });
     }
 
+    /// Returns the pages of the whole arena, which does not change with the
+    /// bytes allocations, like [`ByteAllocator::total_bytes`].
     fn total_pages(&self) -> usize {
-        (self.end - self.b_pos) / PAGE_SIZE
+        if self.is_uninit() {
+            return 0;
+        }
+        pages_between(self.start, self.end, self.page_size)
     }
 
     fn used_pages(&self) -> usize {
-        (self.end - self.p_pos) / PAGE_SIZE
+        if self.is_uninit() {
+            return 0;
+        }
+        pages_between(self.p_pos, self.end, self.page_size)
     }
 
+    /// Returns the number of pages that a single page-aligned `alloc_pages`
+    /// could get right now.
+    ///
+    /// Pages can only start at a page boundary at or above the bytes cursor
+    /// plus the [byte reserve](Self::set_byte_reserve), so a partial page
+    /// left by an unaligned `b_pos` is not counted. Larger alignments may get
+    /// fewer pages.
     fn available_pages(&self) -> usize {
-        (self.p_pos - self.b_pos) / PAGE_SIZE
+        if self.is_uninit() {
+            return 0;
+        }
+        // 页块起点必须页对齐且不低于 b_pos 加上保留的空间
+        let low = self.b_pos.checked_add(self.byte_reserve);
+        match low.and_then(|low| align_up(low, self.page_size)) {
+            Some(low) if low <= self.p_pos => pages_between(low, self.p_pos, self.page_size),
+            _ => 0,
+        }
     }
 }
@@ -0,0 +1,78 @@
+use allocator::{AllocResult, BaseAllocator, ByteAllocator, PageAllocator};
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::{self, NonNull};
+use spin::{Mutex, MutexGuard};
+
+use crate::EarlyAllocator;
+
+/// An [`EarlyAllocator`] protected by a spinlock, so that it can be shared
+/// through `&self`, e.g. as the global allocator.
+pub struct SpinEarlyAllocator<const PAGE_SIZE: usize> {
+    inner: Mutex<EarlyAllocator<PAGE_SIZE>>,
+}
+
+/// The [`GlobalAlloc`] adapter of [`EarlyAllocator`], usable as
+/// `#[global_allocator]` during early boot.
+///
+/// Allocation failures are reported as null pointers, as the trait requires.
+pub type GlobalEarlyAllocator<const PAGE_SIZE: usize> = SpinEarlyAllocator<PAGE_SIZE>;
+
+impl<const PAGE_SIZE: usize> SpinEarlyAllocator<PAGE_SIZE> {
+    /// Creates an empty [`SpinEarlyAllocator`].
+    pub const fn new() -> Self {
+        Self {
+            inner: Mutex::new(EarlyAllocator::new()),
+        }
+    }
+
+    /// Locks the inner allocator for operations not covered by the wrapper.
+    pub fn lock(&self) -> MutexGuard<'_, EarlyAllocator<PAGE_SIZE>> {
+        self.inner.lock()
+    }
+
+    /// Initializes the allocator with the given region.
+    pub fn init(&self, start: usize, size: usize) {
+        self.inner.lock().init(start, size);
+    }
+
+    /// Allocates arbitrary number of bytes.
+    pub fn alloc(&self, layout: Layout) -> AllocResult<NonNull<u8>> {
+        self.inner.lock().alloc(layout)
+    }
+
+    /// Gives back the allocated bytes.
+    pub fn dealloc(&self, pos: NonNull<u8>, layout: Layout) {
+        self.inner.lock().dealloc(pos, layout)
+    }
+
+    /// Allocates contiguous pages.
+    pub fn alloc_pages(&self, num_pages: usize, align_pow2: usize) -> AllocResult<usize> {
+        self.inner.lock().alloc_pages(num_pages, align_pow2)
+    }
+
+    /// Gives back the allocated pages starts from `pos`.
+    pub fn dealloc_pages(&self, pos: usize, num_pages: usize) {
+        self.inner.lock().dealloc_pages(pos, num_pages)
+    }
+}
+
+impl<const PAGE_SIZE: usize> Default for SpinEarlyAllocator<PAGE_SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl<const PAGE_SIZE: usize> GlobalAlloc for SpinEarlyAllocator<PAGE_SIZE> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match SpinEarlyAllocator::alloc(self, layout) {
+            Ok(ptr) => ptr.as_ptr(),
+            Err(_) => ptr::null_mut(),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if let Some(ptr) = NonNull::new(ptr) {
+            SpinEarlyAllocator::dealloc(self, ptr, layout)
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! An object-safe view of [`EarlyAllocator`] for any page size.
+//!
+//! The trait is not re-exported at the crate root: its methods share the
+//! names of the ones of [`ByteAllocator`] and [`PageAllocator`], so having it
+//! in scope makes the calls on an [`EarlyAllocator`] ambiguous.
+
+use allocator::{AllocResult, ByteAllocator, PageAllocator};
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+use crate::{EarlyAllocator, MemStats};
+
+/// The operations of an [`EarlyAllocator`] that do not depend on its const
+/// parameters, so that boot code can carry a `&mut dyn EarlyAllocatorDyn`
+/// without knowing the page size at compile time.
+///
+/// The methods forward to the ones of [`ByteAllocator`] and
+/// [`PageAllocator`] with the same names.
+pub trait EarlyAllocatorDyn {
+    /// Allocates memory with the given size (in bytes) and alignment.
+    fn alloc(&mut self, layout: Layout) -> AllocResult<NonNull<u8>>;
+
+    /// Deallocates memory at the given position, size, and alignment.
+    fn dealloc(&mut self, pos: NonNull<u8>, layout: Layout);
+
+    /// Allocates contiguous pages, see [`PageAllocator::alloc_pages`].
+    fn alloc_pages(&mut self, num_pages: usize, align_pow2: usize) -> AllocResult<usize>;
+
+    /// Deallocates contiguous pages, see [`PageAllocator::dealloc_pages`].
+    fn dealloc_pages(&mut self, pos: usize, num_pages: usize);
+
+    /// Returns the page size of the allocator.
+    fn page_size(&self) -> usize;
+
+    /// Returns the usage of the allocator, see [`EarlyAllocator::stats`].
+    fn stats(&self) -> MemStats;
+}
+
+impl<
+        const PAGE_SIZE: usize,
+        const TRACE_LEN: usize,
+        const MIN_ALIGN: usize,
+        const AUTO_RESET: bool,
+    > EarlyAllocatorDyn for EarlyAllocator<PAGE_SIZE, TRACE_LEN, MIN_ALIGN, AUTO_RESET>
+{
+    fn alloc(&mut self, layout: Layout) -> AllocResult<NonNull<u8>> {
+        ByteAllocator::alloc(self, layout)
+    }
+
+    fn dealloc(&mut self, pos: NonNull<u8>, layout: Layout) {
+        ByteAllocator::dealloc(self, pos, layout)
+    }
+
+    fn alloc_pages(&mut self, num_pages: usize, align_pow2: usize) -> AllocResult<usize> {
+        PageAllocator::alloc_pages(self, num_pages, align_pow2)
+    }
+
+    fn dealloc_pages(&mut self, pos: usize, num_pages: usize) {
+        PageAllocator::dealloc_pages(self, pos, num_pages)
+    }
+
+    fn page_size(&self) -> usize {
+        EarlyAllocator::page_size(self)
+    }
+
+    fn stats(&self) -> MemStats {
+        EarlyAllocator::stats(self)
+    }
+}
@@ -0,0 +1,145 @@
+//! A smoke test of the allocator over its own region.
+
+use allocator::{ByteAllocator, PageAllocator};
+use core::alloc::Layout;
+use core::mem::{align_of, size_of};
+use core::ptr::NonNull;
+
+use crate::EarlyAllocator;
+
+/// Each bytes block starts with the address of the previous block and its
+/// own size, so that the blocks can be freed without any other memory.
+const BLOCK_HEADER: usize = 2 * size_of::<usize>();
+
+impl<
+        const PAGE_SIZE: usize,
+        const TRACE_LEN: usize,
+        const MIN_ALIGN: usize,
+        const AUTO_RESET: bool,
+    > EarlyAllocator<PAGE_SIZE, TRACE_LEN, MIN_ALIGN, AUTO_RESET>
+{
+    /// Exercises the allocator over its whole region, e.g. to check the
+    /// memory map when bringing up new hardware.
+    ///
+    /// The bytes area is filled until no allocation succeeds, then freed,
+    /// then the same is done for the pages area. The allocated memory is
+    /// written and read back. The cursors, the counters and
+    /// [`verify_invariants`](Self::verify_invariants) are checked at each
+    /// step.
+    ///
+    /// The allocator must be initialized and empty. The OOM hook, retry
+    /// policy and event sink are not called during the test. On success, the allocator is
+    /// [`reset`](Self::reset), otherwise it is left as the failed check
+    /// found it, and the first failure is returned.
+    pub fn self_test(&mut self) -> Result<(), &'static str> {
+        if self.is_uninit() {
+            return Err("allocator not initialized");
+        }
+        if !self.is_empty() {
+            return Err("live allocations before the test");
+        }
+        if self.frozen {
+            return Err("allocator frozen");
+        }
+        // 测试中的分配失败是预期的，不通知调用方
+        let on_oom = self.on_oom.take();
+        let retry_on_oom = self.retry_on_oom.take();
+        let on_event = self.on_event.take();
+        let res = self.test_bytes().and_then(|_| self.test_pages());
+        self.on_oom = on_oom;
+        self.retry_on_oom = retry_on_oom;
+        self.on_event = on_event;
+        if res.is_ok() {
+            self.reset();
+        }
+        res
+    }
+
+    fn test_bytes(&mut self) -> Result<(), &'static str> {
+        let align = align_of::<usize>();
+        let mut last = 0;
+        let mut blocks = 0;
+        loop {
+            let size = self.max_alloc_bytes(align) & !(align - 1);
+            if size < BLOCK_HEADER {
+                break;
+            }
+            let layout = Layout::from_size_align(size, align).unwrap();
+            let ptr = self.alloc(layout).map_err(|_| "bytes allocation failed")?;
+            let header = ptr.as_ptr() as *mut usize;
+            unsafe {
+                header.write(last);
+                header.add(1).write(size);
+            }
+            last = ptr.as_ptr() as usize;
+            blocks += 1;
+            self.verify_invariants();
+        }
+        let header = Layout::from_size_align(BLOCK_HEADER, align).unwrap();
+        if self.alloc(header).is_ok() {
+            return Err("bytes area not exhausted");
+        }
+        if self.byte_alloc_count() != blocks {
+            return Err("bytes count mismatches the allocations");
+        }
+
+        // 按分配的逆序释放，读回每块的头部
+        while last != 0 {
+            let header = last as *const usize;
+            let (prev, size) = unsafe { (header.read(), header.add(1).read()) };
+            if prev >= last || size < BLOCK_HEADER {
+                return Err("bytes block header corrupted");
+            }
+            let layout = Layout::from_size_align(size, align).unwrap();
+            self.dealloc(NonNull::new(last as *mut u8).unwrap(), layout);
+            last = prev;
+            blocks -= 1;
+            self.verify_invariants();
+        }
+        if !AUTO_RESET {
+            self.dealloc_all_bytes(); // 释放不会回收字节区
+        }
+        if blocks != 0 || !self.is_empty() || self.b_pos != self.start {
+            return Err("bytes area not reclaimed");
+        }
+        Ok(())
+    }
+
+    fn test_pages(&mut self) -> Result<(), &'static str> {
+        let align_pow2 = self.page_size.trailing_zeros() as usize;
+        let expected = self.available_pages();
+        let mut last = 0;
+        let mut pages = 0;
+        while let Ok(pos) = self.alloc_pages(1, align_pow2) {
+            if pos < self.start || pos % self.page_size != 0 {
+                return Err("page out of the arena");
+            }
+            unsafe { (pos as *mut usize).write(last) };
+            last = pos;
+            pages += 1;
+            self.verify_invariants();
+        }
+        if pages != expected || self.available_pages() != 0 {
+            return Err("pages area not exhausted");
+        }
+        if self.page_alloc_count() != pages {
+            return Err("pages count mismatches the allocations");
+        }
+
+        // 页从高地址向低地址分配，链表中的前一页地址更高
+        while pages != 0 {
+            let prev = unsafe { (last as *const usize).read() };
+            if prev != 0 && prev != last + self.page_size {
+                return Err("page content corrupted");
+            }
+            self.dealloc_pages(last, 1);
+            last = prev;
+            pages -= 1;
+            self.verify_invariants();
+        }
+        if last != 0 || !self.is_empty() || self.p_pos != self.end {
+            return Err("pages area not reclaimed");
+        }
+        Ok(())
+    }
+}
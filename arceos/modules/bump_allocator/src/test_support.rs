@@ -0,0 +1,25 @@
+//! Helpers for host-side tests, available with the `test-support` feature.
+
+use alloc::boxed::Box;
+use alloc::vec;
+
+use allocator::BaseAllocator;
+
+use crate::EarlyAllocator;
+
+/// Creates an allocator over `size` bytes of heap memory.
+///
+/// The returned buffer backs the arena and must outlive every use of the
+/// allocator. The arena starts at the buffer start and ends at the last page
+/// boundary inside it.
+///
+/// # Panics
+///
+/// Panics if `size` cannot hold a single aligned page, see
+/// [`EarlyAllocator::try_init`].
+pub fn with_backing<const PAGE_SIZE: usize>(size: usize) -> (EarlyAllocator<PAGE_SIZE>, Box<[u8]>) {
+    let mut backing = vec![0u8; size].into_boxed_slice();
+    let mut early = EarlyAllocator::new();
+    early.init(backing.as_mut_ptr() as usize, size);
+    (early, backing)
+}
@@ -0,0 +1,2882 @@
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+use allocator::{AllocError, BaseAllocator, ByteAllocator, PageAllocator};
+
+use crate::test_support::with_backing;
+use crate::{AllocEvent, DynEarlyAllocator, EarlyAllocator, Pool};
+
+const PAGE_SIZE: usize = 0x1000;
+const BASE: usize = 0x8000_0000;
+
+/// Page-aligned host memory for tests that touch the allocated bytes.
+struct Arena {
+    buf: Vec<u8>,
+    offset: usize,
+}
+
+impl Arena {
+    fn new(num_pages: usize) -> Self {
+        Self::with_page_size(num_pages, PAGE_SIZE)
+    }
+
+    fn with_page_size(num_pages: usize, page_size: usize) -> Self {
+        let mut buf = vec![0; (num_pages + 1) * page_size];
+        let offset = buf.as_mut_ptr().align_offset(page_size);
+        Self { buf, offset }
+    }
+
+    fn start(&mut self) -> usize {
+        self.buf.as_mut_ptr() as usize + self.offset
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &self.buf[self.offset..]
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.buf[self.offset..]
+    }
+}
+
+#[test]
+fn test_add_memory() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 4 * PAGE_SIZE);
+    assert_eq!(early.total_pages(), 4);
+
+    assert!(matches!(
+        early.add_memory(BASE + 8 * PAGE_SIZE, 4 * PAGE_SIZE),
+        Err(AllocError::InvalidParam)
+    ));
+    early
+        .add_memory(BASE + 4 * PAGE_SIZE, 4 * PAGE_SIZE)
+        .unwrap();
+    assert_eq!(early.total_pages(), 8);
+    assert_eq!(early.available_pages(), 8);
+
+    let addr = early.alloc_pages(2, 12).unwrap();
+    assert_eq!(addr, BASE + 6 * PAGE_SIZE);
+    assert_eq!(early.used_pages(), 2);
+
+    // 未初始化时拒绝
+    let mut uninit = EarlyAllocator::<PAGE_SIZE>::new();
+    assert!(matches!(
+        uninit.add_memory(0, 4 * PAGE_SIZE),
+        Err(AllocError::InvalidParam)
+    ));
+    assert_eq!(uninit.total_bytes(), 0);
+}
+
+#[test]
+fn test_add_memory_live_pages() {
+    let (mut early, _backing) = with_backing::<PAGE_SIZE>(8 * PAGE_SIZE);
+    early.init(early.start_addr(), 4 * PAGE_SIZE);
+    let end = early.end_addr();
+    let pages = early.total_pages();
+
+    // 有存活的页时不能扩展
+    let addr = early.alloc_pages(1, 12).unwrap();
+    assert!(matches!(
+        early.add_memory(end, 2 * PAGE_SIZE),
+        Err(AllocError::InvalidParam)
+    ));
+    assert_eq!(early.end_addr(), end);
+    assert_eq!(early.used_pages(), 1);
+
+    early.dealloc_pages(addr, 1);
+    early.add_memory(end, 2 * PAGE_SIZE).unwrap();
+    assert_eq!(early.used_pages(), 0);
+    assert_eq!(early.total_pages(), pages + 2);
+    assert_eq!(early.alloc_pages(1, 12).unwrap(), end + PAGE_SIZE);
+}
+
+#[test]
+fn test_dealloc_underflow() {
+    let mut arena = Arena::new(4);
+    let start = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(start, 4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(16, 8).unwrap();
+    early.dealloc(NonNull::dangling(), layout);
+    assert_eq!(early.used_bytes(), 0);
+
+    let a = early.alloc(layout).unwrap();
+    let b = early.alloc(layout).unwrap();
+    early.dealloc(a, layout);
+    early.dealloc(a, layout); // double free
+    assert_eq!(early.used_bytes(), 0);
+    early.dealloc(b, layout); // stray free after the reset
+    assert_eq!(early.used_bytes(), 0);
+
+    let c = early.alloc(layout).unwrap();
+    assert_eq!(c.as_ptr() as usize, start);
+    assert_eq!(early.used_bytes(), 16);
+}
+
+#[test]
+fn test_total_bytes_fixed() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 8 * PAGE_SIZE);
+    assert_eq!(early.total_bytes(), 8 * PAGE_SIZE);
+    for _ in 0..3 {
+        early.alloc_pages(1, 12).unwrap();
+        assert_eq!(early.total_bytes(), 8 * PAGE_SIZE);
+    }
+    assert_eq!(early.byte_capacity_remaining(), 5 * PAGE_SIZE);
+}
+
+#[test]
+fn test_init_unaligned_size() {
+    let mut arena = Arena::new(5);
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(arena.start(), 4 * PAGE_SIZE + 0x123);
+    assert_eq!(early.used_pages(), 0);
+    assert_eq!(early.available_pages(), 4);
+
+    let addr = early.alloc_pages(1, 12).unwrap();
+    early.dealloc_pages(addr, 1);
+    assert_eq!(early.used_pages(), 0);
+    assert_eq!(early.available_pages(), 4);
+}
+
+#[test]
+fn test_report_before_init() {
+    let early = EarlyAllocator::<PAGE_SIZE>::new();
+    assert_eq!(early.total_bytes(), 0);
+    assert_eq!(early.used_bytes(), 0);
+    assert_eq!(early.available_bytes(), 0);
+    assert_eq!(early.total_pages(), 0);
+    assert_eq!(early.used_pages(), 0);
+    assert_eq!(early.available_pages(), 0);
+    assert_eq!(early.byte_used_permille(), 0);
+    assert_eq!(early.page_used_permille(), 0);
+    assert_eq!(early.peak_used_bytes(), 0);
+    assert_eq!(early.peak_used_pages(), 0);
+    assert_eq!(early.untouched_bytes(), 0);
+    assert_eq!(early.wasted_bytes(), 0);
+    assert_eq!(early.byte_capacity_remaining(), 0);
+    assert_eq!(early.available_for(Layout::new::<u64>()), 0);
+    assert_eq!(early.max_alloc_bytes(8), 0);
+    let stats = early.stats();
+    assert_eq!(
+        stats.total_bytes + stats.available_bytes + stats.total_pages,
+        0
+    );
+    assert_eq!(stats.byte_allocs + stats.page_allocs, 0);
+    assert!(early.is_empty());
+}
+
+#[test]
+fn test_dealloc_all_bytes() {
+    let mut arena = Arena::new(2);
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(arena.start(), 2 * PAGE_SIZE);
+    let layout = Layout::from_size_align(0x100, 8).unwrap();
+    let first = early.alloc(layout).unwrap();
+    // 故意泄漏的分配，计数不会归零
+    for _ in 0..3 {
+        early.alloc(layout).unwrap();
+    }
+    early.dealloc(first, layout);
+    assert_eq!(early.used_bytes(), 0x400);
+
+    early.dealloc_all_bytes();
+    assert_eq!(early.used_bytes(), 0);
+    assert_eq!(early.peak_used_bytes(), 0x400);
+    assert_eq!(early.alloc(layout).unwrap(), first);
+    // 计数也已清零，一次释放即可回收
+    early.dealloc(first, layout);
+    assert_eq!(early.used_bytes(), 0);
+}
+
+#[test]
+fn test_auto_reset() {
+    let mut arena = Arena::new(2);
+    let layout = Layout::from_size_align(0x100, 8).unwrap();
+
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(arena.start(), 2 * PAGE_SIZE);
+    let a = early.alloc(layout).unwrap();
+    let b = early.alloc(layout).unwrap();
+    early.dealloc(a, layout);
+    early.dealloc(b, layout);
+    assert_eq!(early.used_bytes(), 0);
+
+    // 释放从不回收，包括栈顶块与计数归零时
+    let mut early = EarlyAllocator::<PAGE_SIZE, 16, 1, false>::new();
+    early.init(arena.start(), 2 * PAGE_SIZE);
+    let a = early.alloc(layout).unwrap();
+    let b = early.alloc(layout).unwrap();
+    early.dealloc(b, layout);
+    assert_eq!(early.used_bytes(), 0x200);
+    early.dealloc(a, layout);
+    assert_eq!(early.used_bytes(), 0x200);
+    assert_eq!(early.byte_alloc_count(), 0);
+    early.verify_invariants();
+    assert_ne!(early.alloc(layout).unwrap(), a);
+
+    early.dealloc_all_bytes();
+    assert_eq!(early.used_bytes(), 0);
+    assert_eq!(early.alloc(layout).unwrap(), a);
+    early.reset();
+    assert_eq!(early.used_bytes(), 0);
+}
+
+#[test]
+fn test_scratch_pool() {
+    let mut arena = Arena::new(4);
+    let start = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(start, 4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(0x100, 8).unwrap();
+    early.alloc_pool(layout, Pool::Permanent).unwrap();
+
+    // 临时池回收到第一次临时分配之前，永久分配保持不变
+    let a = early.alloc_pool(layout, Pool::Scratch).unwrap();
+    let b = early.alloc_pool(layout, Pool::Scratch).unwrap();
+    assert_eq!(early.byte_cursor(), start + 0x300);
+    early.dealloc_pool(a, layout, Pool::Scratch);
+    assert_eq!(early.byte_cursor(), start + 0x300);
+    early.dealloc_pool(b, layout, Pool::Scratch);
+    assert_eq!(early.byte_cursor(), start + 0x100);
+    assert_eq!(early.byte_alloc_count(), 1);
+
+    // 临时分配之上的永久分配阻止回收
+    let a = early.alloc_pool(layout, Pool::Scratch).unwrap();
+    let data = early.alloc(layout).unwrap();
+    let b = early.alloc_pool(layout, Pool::Scratch).unwrap();
+    early.dealloc_pool(a, layout, Pool::Scratch);
+    early.dealloc_pool(b, layout, Pool::Scratch);
+    assert_eq!(early.byte_cursor(), start + 0x300);
+    early.dealloc(data, layout);
+    assert_eq!(early.byte_cursor(), start + 0x200);
+    early.reset_bytes();
+
+    // 先释放其上的永久分配则不再阻止
+    let boot = early.alloc(layout).unwrap();
+    let a = early.alloc_pool(layout, Pool::Scratch).unwrap();
+    let data = early.alloc(layout).unwrap();
+    let b = early.alloc_pool(layout, Pool::Scratch).unwrap();
+    early.dealloc(data, layout);
+    early.dealloc_pool(a, layout, Pool::Scratch);
+    early.dealloc_pool(b, layout, Pool::Scratch);
+    assert_eq!(early.byte_cursor(), start + 0x100);
+    early.verify_invariants();
+
+    // 永久分配全部释放时照常回收整个字节区
+    early.alloc_pool(layout, Pool::Scratch).unwrap();
+    early.dealloc(boot, layout);
+    assert_eq!(early.byte_cursor(), start + 0x200);
+    assert_eq!(early.byte_alloc_count(), 1);
+}
+
+#[test]
+fn test_reset() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 8 * PAGE_SIZE);
+    early
+        .alloc(Layout::from_size_align(100, 8).unwrap())
+        .unwrap();
+    early.alloc_pages(2, 12).unwrap();
+    early.reset();
+    assert_eq!(early.used_bytes(), 0);
+    assert_eq!(early.used_pages(), 0);
+    assert_eq!(early.available_pages(), 8);
+}
+
+#[test]
+fn test_peak_usage() {
+    let mut arena = Arena::new(8);
+    let start = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(start, 8 * PAGE_SIZE);
+    let layout = Layout::from_size_align(0x100, 8).unwrap();
+    let a = early.alloc(layout).unwrap();
+    let b = early.alloc(layout).unwrap();
+    let p = early.alloc_pages(3, 12).unwrap();
+    early.dealloc(a, layout);
+    early.dealloc(b, layout);
+    early.dealloc_pages(p, 3);
+    assert_eq!(early.used_bytes(), 0);
+    assert_eq!(early.used_pages(), 0);
+    assert_eq!(early.peak_used_bytes(), 0x200);
+    assert_eq!(early.peak_used_pages(), 3);
+
+    early.alloc(layout).unwrap();
+    assert_eq!(early.peak_used_bytes(), 0x200);
+    early.reset();
+    assert_eq!(early.peak_used_bytes(), 0);
+    assert_eq!(early.peak_used_pages(), 0);
+}
+
+#[test]
+fn test_realloc() {
+    let mut arena = Arena::new(8);
+    let start = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(start, 8 * PAGE_SIZE);
+
+    // in-place grow and shrink of the top block
+    let old = Layout::from_size_align(16, 8).unwrap();
+    let new = Layout::from_size_align(64, 8).unwrap();
+    let a = early.alloc(old).unwrap();
+    let a2 = early.realloc(a, old, new).unwrap();
+    assert_eq!(a, a2);
+    assert_eq!(early.used_bytes(), 64);
+    let a3 = early.realloc(a2, new, old).unwrap();
+    assert_eq!(a, a3);
+    assert_eq!(early.used_bytes(), 16);
+
+    // fallback: the block is not on top any more
+    unsafe { a.as_ptr().write_bytes(0x5a, 16) };
+    let b = early.alloc(old).unwrap();
+    let a4 = early.realloc(a, old, new).unwrap();
+    assert_ne!(a, a4);
+    assert!(a4.as_ptr() as usize >= b.as_ptr() as usize + 16);
+    let data = unsafe { core::slice::from_raw_parts(a4.as_ptr(), 16) };
+    assert!(data.iter().all(|&x| x == 0x5a));
+
+    // neither path fits
+    let huge = Layout::from_size_align(16 * PAGE_SIZE, 8).unwrap();
+    assert!(matches!(
+        early.realloc(a4, new, huge),
+        Err(AllocError::NoMemory)
+    ));
+}
+
+#[test]
+fn test_realloc_accounting() {
+    use std::sync::Mutex;
+
+    static EVENTS: Mutex<Vec<AllocEvent>> = Mutex::new(Vec::new());
+    fn sink(event: AllocEvent) {
+        EVENTS.lock().unwrap().push(event);
+    }
+
+    let mut arena = Arena::new(8);
+    let start = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(start, 8 * PAGE_SIZE);
+    early.set_size_granularity(16).unwrap();
+    early.set_event_sink(Some(sink));
+
+    // 原地调整与分配一样计入取整多出的部分
+    let old = Layout::from_size_align(10, 8).unwrap();
+    let new = Layout::from_size_align(20, 8).unwrap();
+    let a = early.alloc(old).unwrap();
+    assert_eq!(early.wasted_bytes(), 6);
+    assert_eq!(early.realloc(a, old, new).unwrap(), a);
+    assert_eq!(early.wasted_bytes(), 6 + 12);
+    let exact = Layout::from_size_align(16, 8).unwrap();
+    assert_eq!(early.realloc(a, new, exact).unwrap(), a);
+    assert_eq!(early.wasted_bytes(), 6 + 12);
+    assert_eq!(early.used_bytes(), 16);
+
+    // 原地调整也受大小限制
+    early.set_max_alloc(0x100);
+    let big = Layout::from_size_align(0x200, 8).unwrap();
+    assert!(matches!(
+        early.realloc(a, exact, big),
+        Err(AllocError::InvalidParam)
+    ));
+    early.set_event_sink(None);
+
+    let addr = a.as_ptr() as usize;
+    assert_eq!(
+        *EVENTS.lock().unwrap(),
+        [
+            AllocEvent::Alloc { addr, size: 10 },
+            AllocEvent::Dealloc { addr, size: 10 },
+            AllocEvent::Alloc { addr, size: 20 },
+            AllocEvent::Dealloc { addr, size: 20 },
+            AllocEvent::Alloc { addr, size: 16 },
+        ]
+    );
+
+    #[cfg(feature = "alloc-trace")]
+    assert_eq!(
+        early.trace().iter().map(|e| e.size).collect::<Vec<_>>(),
+        [10, 20, 16]
+    );
+
+    // 注入的失败对原地调整同样生效
+    #[cfg(feature = "test-inject")]
+    {
+        early.fail_after(1);
+        assert!(matches!(
+            early.realloc(a, exact, new),
+            Err(AllocError::NoMemory)
+        ));
+        assert_eq!(early.used_bytes(), 16);
+        assert_eq!(early.realloc(a, exact, new).unwrap(), a);
+    }
+}
+
+#[test]
+fn test_alloc_zeroed() {
+    let mut arena = Arena::new(8);
+    arena.bytes_mut().fill(0xff);
+    let start = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(start, 8 * PAGE_SIZE);
+
+    let layout = Layout::from_size_align(32, 8).unwrap();
+    let ptr = early.alloc_zeroed(layout).unwrap();
+    let bytes = unsafe { core::slice::from_raw_parts(ptr.as_ptr(), 32) };
+    assert!(bytes.iter().all(|&b| b == 0));
+
+    let pos = early.alloc_pages_zeroed(2, 12).unwrap();
+    let bytes = unsafe { core::slice::from_raw_parts(pos as *const u8, 2 * PAGE_SIZE) };
+    assert!(bytes.iter().all(|&b| b == 0));
+    assert_eq!(arena.bytes()[0x100], 0xff);
+}
+
+#[test]
+fn test_zero_size_alloc() {
+    let mut arena = Arena::new(2);
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(arena.start(), 2 * PAGE_SIZE);
+    let live = early.alloc(Layout::new::<u32>()).unwrap();
+
+    let empty = Layout::from_size_align(0, 8).unwrap();
+    let a = early.alloc(empty).unwrap();
+    let b = early.alloc(empty).unwrap();
+    // 悬空指针，不移动游标也不计数
+    assert_eq!(a, NonNull::<u64>::dangling().cast());
+    assert_eq!(a, b);
+    assert_eq!(early.used_bytes(), 4);
+    assert_eq!(early.stats().byte_allocs, 1);
+    let page = Layout::from_size_align(0, PAGE_SIZE).unwrap();
+    assert_eq!(early.alloc(page).unwrap().as_ptr() as usize, PAGE_SIZE);
+
+    early.dealloc(a, empty);
+    early.dealloc(b, empty);
+    assert_eq!(early.stats().byte_allocs, 1);
+    assert_eq!(early.used_bytes(), 4);
+    early.dealloc(live, Layout::new::<u32>());
+    assert_eq!(early.used_bytes(), 0);
+}
+
+#[test]
+fn test_alloc_overflow() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    let start = usize::MAX & !0xffff;
+    early.init(start, 8 * PAGE_SIZE);
+
+    let huge = Layout::from_size_align(isize::MAX as usize, 1).unwrap();
+    assert!(matches!(early.alloc(huge), Err(AllocError::InvalidParam)));
+    let over_aligned = Layout::from_size_align(0, 1 << (usize::BITS - 1)).unwrap();
+    let ptr = early.alloc(over_aligned).unwrap();
+    assert_eq!(ptr.as_ptr() as usize, over_aligned.align());
+    assert_eq!(early.used_bytes(), 0);
+}
+
+#[test]
+fn test_alloc_larger_than_arena() {
+    let mut arena = Arena::new(4);
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(arena.start(), 4 * PAGE_SIZE);
+    let whole = Layout::from_size_align(4 * PAGE_SIZE, 8).unwrap();
+    let larger = Layout::from_size_align(4 * PAGE_SIZE + 1, 8).unwrap();
+
+    // 空的区域放不下，即使没有任何分配
+    assert!(matches!(early.alloc(larger), Err(AllocError::InvalidParam)));
+    assert_eq!(early.byte_cursor(), arena.start());
+    assert_eq!(early.byte_alloc_count(), 0);
+
+    // 被存活的分配占满，释放后即可满足
+    let live = early.alloc(Layout::new::<u64>()).unwrap();
+    assert!(matches!(early.alloc(whole), Err(AllocError::NoMemory)));
+    assert!(matches!(early.alloc(larger), Err(AllocError::InvalidParam)));
+    early.dealloc(live, Layout::new::<u64>());
+    let ptr = early.alloc(whole).unwrap();
+    assert_eq!(ptr.as_ptr() as usize, arena.start());
+}
+
+#[test]
+fn test_alloc_align_beyond_window() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE + 0x10, 8 * PAGE_SIZE);
+    let ptr = early.alloc(Layout::new::<u64>()).unwrap();
+    let before = early.snapshot();
+
+    // 对齐后的起点已超出整个区域，永远无法满足
+    for align in [16 * PAGE_SIZE, 1 << (usize::BITS - 2)] {
+        let layout = Layout::from_size_align(8, align).unwrap();
+        assert!(matches!(early.alloc(layout), Err(AllocError::InvalidParam)));
+    }
+    assert_eq!(early.snapshot(), before);
+    assert_eq!(early.used_bytes(), 8);
+    let next = early.alloc(Layout::new::<u64>()).unwrap();
+    assert_eq!(next.as_ptr() as usize, ptr.as_ptr() as usize + 8);
+}
+
+#[test]
+fn test_counts_at_top() {
+    // 区域紧贴地址空间顶端，32 位目标上同样成立
+    let top = usize::MAX & !(PAGE_SIZE - 1);
+    let start = top - 8 * PAGE_SIZE;
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(start, usize::MAX - start);
+    assert_eq!(early.total_pages(), 8);
+    assert_eq!(early.used_pages(), 0);
+    assert_eq!(early.available_pages(), 8);
+    assert_eq!(early.currently_allocatable_pages(), 8);
+
+    let pos = early.alloc_pages_below(2, 12, usize::MAX).unwrap();
+    assert_eq!(pos, top - 2 * PAGE_SIZE);
+    let (pos, slack) = early.alloc_aligned_pages(1, 15).unwrap();
+    assert_eq!(pos % (1 << 15), 0);
+    assert!(slack > 0);
+    assert_eq!(pos + (slack + 1) * PAGE_SIZE, top - 2 * PAGE_SIZE);
+    early
+        .alloc(Layout::from_size_align(0x100, 8).unwrap())
+        .unwrap();
+    assert_eq!(early.total_pages(), 8);
+    assert_eq!(early.used_pages(), (top - pos) / PAGE_SIZE);
+    assert_eq!(early.currently_allocatable_pages(), 7);
+    assert_eq!(
+        early.available_pages(),
+        (pos - start - PAGE_SIZE) / PAGE_SIZE
+    );
+    assert_eq!(early.peak_used_pages(), early.used_pages());
+    assert_eq!(
+        early.page_used_permille(),
+        (early.used_pages() * 1000 / 7) as u32
+    );
+
+    let mut dynamic = DynEarlyAllocator::new();
+    dynamic.init(start, usize::MAX - start, PAGE_SIZE);
+    assert_eq!(dynamic.total_pages(), 8);
+    dynamic.alloc_pages(8, 12).unwrap();
+    assert_eq!(dynamic.used_pages(), 8);
+    assert_eq!(dynamic.available_pages(), 0);
+}
+
+#[test]
+fn test_dealloc_pages_over_free() {
+    let mut arena = Arena::new(8);
+    let start = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(start, 8 * PAGE_SIZE);
+    let a = early.alloc_pages(2, 12).unwrap();
+
+    assert!(matches!(
+        early.try_dealloc_pages(a, 3),
+        Err(AllocError::InvalidParam)
+    ));
+    assert!(matches!(
+        early.try_dealloc_pages(start, 1),
+        Err(AllocError::InvalidParam)
+    ));
+    assert_eq!(early.used_pages(), 2);
+
+    early.dealloc_pages(a, 2);
+    assert_eq!(early.used_pages(), 0);
+    assert_eq!(early.available_pages(), 8);
+    let b = early.alloc_pages(1, 12).unwrap();
+    assert_eq!(early.used_pages(), 1);
+    early.try_dealloc_pages(b, 1).unwrap();
+    assert_eq!(early.used_pages(), 0);
+}
+
+#[cfg(feature = "spin")]
+#[test]
+fn test_spin_concurrent() {
+    use crate::SpinEarlyAllocator;
+
+    const NUM_THREADS: usize = 8;
+    const NUM_ITERS: usize = 1000;
+
+    // large enough even if the cursors never get reset in between
+    let num_pages = 2 * NUM_THREADS * NUM_ITERS;
+    let mut arena = Arena::new(num_pages);
+    let early = SpinEarlyAllocator::<PAGE_SIZE>::new();
+    early.init(arena.start(), num_pages * PAGE_SIZE);
+    std::thread::scope(|s| {
+        for i in 0..NUM_THREADS {
+            let early = &early;
+            s.spawn(move || {
+                let layout = Layout::from_size_align(8 + i, 8).unwrap();
+                for _ in 0..NUM_ITERS {
+                    let ptr = early.alloc(layout).unwrap();
+                    let pos = early.alloc_pages(1, 12).unwrap();
+                    early.dealloc_pages(pos, 1);
+                    early.dealloc(ptr, layout);
+                }
+            });
+        }
+    });
+    assert_eq!(early.lock().used_bytes(), 0);
+    assert_eq!(early.lock().used_pages(), 0);
+}
+
+#[test]
+fn test_debug_format() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 4 * PAGE_SIZE);
+    early
+        .alloc(Layout::from_size_align(0x10, 8).unwrap())
+        .unwrap();
+    early.alloc_pages(1, 12).unwrap();
+    assert_eq!(
+        format!("{:?}", early),
+        "EarlyAllocator { start: 0x80000000, end: 0x80004000, b_pos: 0x80000010, \
+         p_pos: 0x80003000, count: 0x1, page_count: 0x1, available_bytes: 0x2ff0, \
+         available_pages: 0x2 }"
+    );
+}
+
+#[test]
+fn test_cursors() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 4 * PAGE_SIZE);
+    early
+        .alloc(Layout::from_size_align(0x18, 8).unwrap())
+        .unwrap();
+    early
+        .alloc(Layout::from_size_align(0x20, 0x20).unwrap())
+        .unwrap();
+    early.alloc_pages(1, 12).unwrap();
+    assert_eq!(early.start_addr(), BASE);
+    assert_eq!(early.end_addr(), BASE + 4 * PAGE_SIZE);
+    assert_eq!(early.byte_cursor(), BASE + 0x40);
+    assert_eq!(early.page_cursor(), BASE + 3 * PAGE_SIZE);
+}
+
+#[test]
+fn test_alloc_pages_align() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE + PAGE_SIZE, 8 * PAGE_SIZE);
+    let a = early.alloc_pages(1, 0).unwrap();
+    assert_eq!(a, BASE + 8 * PAGE_SIZE);
+    let b = early.alloc_pages(1, 12).unwrap();
+    assert_eq!(b, BASE + 7 * PAGE_SIZE);
+    // no 2 MiB boundary inside the arena
+    assert!(matches!(
+        early.alloc_pages(1, 21),
+        Err(AllocError::NoMemory)
+    ));
+    assert_eq!(early.used_pages(), 2);
+
+    // `num_pages * PAGE_SIZE` is larger than `p_pos`
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(PAGE_SIZE, 4 * PAGE_SIZE);
+    assert!(matches!(
+        early.alloc_pages(8, 12),
+        Err(AllocError::NoMemory)
+    ));
+    assert!(matches!(
+        early.alloc_pages(usize::MAX, 12),
+        Err(AllocError::InvalidParam)
+    ));
+}
+
+#[test]
+fn test_alloc_pages_ptr_at_zero() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(0, 2 * PAGE_SIZE);
+    let ptr = early.alloc_pages_ptr(1, 12).unwrap();
+    assert_eq!(ptr.as_ptr() as usize, PAGE_SIZE);
+    assert!(matches!(
+        early.alloc_pages_ptr(1, 12),
+        Err(AllocError::NoMemory)
+    ));
+    assert_eq!(early.used_pages(), 1);
+}
+
+#[test]
+fn test_alloc_pages_ptr_at_zero_untouched() {
+    use std::sync::Mutex;
+
+    static EVENTS: Mutex<Vec<AllocEvent>> = Mutex::new(Vec::new());
+    fn sink(event: AllocEvent) {
+        EVENTS.lock().unwrap().push(event);
+    }
+
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(0, 2 * PAGE_SIZE);
+    early.set_event_sink(Some(sink));
+    early.alloc_pages_ptr(1, 12).unwrap();
+    let state = early.snapshot();
+    assert!(early.alloc_pages_ptr(1, 12).is_err());
+    assert_eq!(early.snapshot(), state);
+    // 被拒绝的分配不通知
+    assert_eq!(
+        *EVENTS.lock().unwrap(),
+        [AllocEvent::AllocPages {
+            addr: PAGE_SIZE,
+            num_pages: 1
+        }]
+    );
+
+    // 单端模式下页从字节区分配，拒绝时字节区也不变
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(0, 2 * PAGE_SIZE);
+    early.set_single_ended(true).unwrap();
+    let state = early.snapshot();
+    assert!(matches!(
+        early.alloc_pages_ptr(1, 12),
+        Err(AllocError::NoMemory)
+    ));
+    assert_eq!(early.snapshot(), state);
+    assert_eq!(early.used_bytes(), 0);
+    assert_eq!(early.byte_alloc_count(), 0);
+    assert_eq!(early.wasted_bytes(), 0);
+}
+
+#[cfg(feature = "debug-poison")]
+#[test]
+fn test_debug_poison() {
+    use crate::POISON_BYTE;
+
+    let mut arena = Arena::new(8);
+    let start = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(start, 8 * PAGE_SIZE);
+
+    let layout = Layout::from_size_align(64, 8).unwrap();
+    let a = early.alloc_zeroed(layout).unwrap();
+    let b = early.alloc_zeroed(layout).unwrap();
+    early.dealloc(b, layout);
+    assert!(arena.bytes()[64..128].iter().all(|&x| x == POISON_BYTE));
+    assert!(arena.bytes()[..64].iter().all(|&x| x == 0));
+    early.dealloc(a, layout);
+    assert!(arena.bytes()[..128].iter().all(|&x| x == POISON_BYTE));
+}
+
+#[cfg(feature = "debug-poison")]
+#[test]
+fn test_debug_poison_uninit() {
+    // 未初始化时没有可写的字节区
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.dealloc_all_bytes();
+    assert_eq!(early.byte_cursor(), 0);
+}
+
+#[test]
+fn test_try_alloc_exhausted() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 2 * PAGE_SIZE);
+    let layout = Layout::from_size_align(PAGE_SIZE, 8).unwrap();
+    assert!(early.try_alloc_pages(1, 12).is_some());
+    assert!(early.try_alloc(layout).is_some());
+    assert!(early.try_alloc(layout).is_none());
+    assert!(early.try_alloc_pages(1, 12).is_none());
+}
+
+#[test]
+fn test_alloc_pages_invalid_align() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 4 * PAGE_SIZE);
+    assert!(matches!(
+        early.alloc_pages(1, usize::BITS as usize),
+        Err(AllocError::InvalidParam)
+    ));
+    assert!(matches!(
+        early.alloc_pages(1, usize::MAX),
+        Err(AllocError::InvalidParam)
+    ));
+    assert_eq!(early.used_pages(), 0);
+}
+
+#[test]
+fn test_snapshot_restore() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 8 * PAGE_SIZE);
+    let layout = Layout::from_size_align(0x30, 8).unwrap();
+    early.alloc(layout).unwrap();
+    early.alloc_pages(1, 12).unwrap();
+
+    let state = early.snapshot();
+    let (b_pos, p_pos) = (early.byte_cursor(), early.page_cursor());
+    early.alloc(layout).unwrap();
+    early.alloc_pages(2, 12).unwrap();
+    early.restore(state);
+    assert_eq!(early.snapshot(), state);
+    assert_eq!(early.byte_cursor(), b_pos);
+    assert_eq!(early.page_cursor(), p_pos);
+    assert_eq!(early.used_bytes(), 0x30);
+    assert_eq!(early.used_pages(), 1);
+}
+
+#[test]
+fn test_transaction() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(0x30, 8).unwrap();
+    early.alloc(layout).unwrap();
+    let state = early.snapshot();
+
+    // 第三个分配失败，前两个一起回滚
+    let res = early.transaction(|early| {
+        early.alloc(layout)?;
+        early.alloc_pages(2, 12)?;
+        early.alloc_pages(4, 12)
+    });
+    assert!(matches!(res, Err(AllocError::NoMemory)));
+    assert_eq!(early.snapshot(), state);
+    assert_eq!(early.byte_alloc_count(), 1);
+    assert_eq!(early.used_pages(), 0);
+
+    let pos = early
+        .transaction(|early| {
+            early.alloc(layout)?;
+            early.alloc_pages(2, 12)
+        })
+        .unwrap();
+    assert_eq!(pos, BASE + 2 * PAGE_SIZE);
+    assert_eq!(early.byte_alloc_count(), 2);
+    assert_eq!(early.used_pages(), 2);
+}
+
+#[test]
+fn test_alloc_at() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(0x100, 0x100).unwrap();
+
+    let ptr = early.alloc_at(BASE + 0x800, layout).unwrap();
+    assert_eq!(ptr.as_ptr() as usize, BASE + 0x800);
+    assert_eq!(early.byte_cursor(), BASE + 0x900);
+
+    // misaligned
+    assert!(matches!(
+        early.alloc_at(BASE + 0xa10, layout),
+        Err(AllocError::InvalidParam)
+    ));
+    // below the cursor
+    assert!(matches!(
+        early.alloc_at(BASE + 0x100, layout),
+        Err(AllocError::InvalidParam)
+    ));
+    // runs into the pages area
+    assert!(matches!(
+        early.alloc_at(BASE + 4 * PAGE_SIZE, layout),
+        Err(AllocError::NoMemory)
+    ));
+    assert_eq!(early.byte_cursor(), BASE + 0x900);
+}
+
+#[test]
+fn test_alloc_at_zero_size() {
+    let (mut early, _backing) = with_backing::<PAGE_SIZE>(4 * PAGE_SIZE);
+    let base = early.start_addr();
+    let zero = Layout::from_size_align(0, 1).unwrap();
+
+    // 在起点分配零大小，不计数也不移动游标
+    let ptr = early.alloc_at(base, zero).unwrap();
+    assert_eq!(ptr.as_ptr() as usize, 1);
+    assert_eq!(early.byte_alloc_count(), 0);
+    assert_eq!(early.byte_cursor(), base);
+    early.verify_invariants();
+    early.dealloc(ptr, zero);
+    assert_eq!(early.byte_alloc_count(), 0);
+
+    let layout = Layout::from_size_align(0x100, 1).unwrap();
+    let block = early.alloc_at(base + 0x100, layout).unwrap();
+    early.alloc_at(base + 0x200, zero).unwrap();
+    assert_eq!(early.byte_alloc_count(), 1);
+    early.dealloc(block, layout);
+    assert_eq!(early.byte_cursor(), base);
+    assert!(early.is_empty());
+}
+
+#[test]
+fn test_wasted_bytes() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 4 * PAGE_SIZE);
+    early.alloc(Layout::from_size_align(1, 1).unwrap()).unwrap();
+    assert_eq!(early.wasted_bytes(), 0);
+    early
+        .alloc(Layout::from_size_align(64, 64).unwrap())
+        .unwrap();
+    assert_eq!(early.wasted_bytes(), 63);
+    assert_eq!(early.used_bytes(), 128);
+    early.reset();
+    assert_eq!(early.wasted_bytes(), 0);
+}
+
+#[test]
+fn test_payload_bytes() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 4 * PAGE_SIZE);
+    assert_eq!(early.payload_bytes(), 0);
+    // 对齐空隙，复用空隙，以及页对齐
+    for (size, align) in [(1, 1), (64, 64), (3, 1), (16, 16), (8, PAGE_SIZE), (5, 8)] {
+        early
+            .alloc(Layout::from_size_align(size, align).unwrap())
+            .unwrap();
+        assert_eq!(
+            early.payload_bytes() + early.wasted_bytes(),
+            early.used_bytes()
+        );
+    }
+    assert_eq!(early.payload_bytes(), 1 + 64 + 3 + 16 + 8 + 5);
+    assert!(early.wasted_bytes() > 0);
+}
+
+#[test]
+fn test_size_granularity() {
+    let mut arena = Arena::new(4);
+    let start = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(start, 4 * PAGE_SIZE);
+    assert_eq!(early.size_granularity(), 1);
+    assert!(matches!(
+        early.set_size_granularity(0),
+        Err(AllocError::InvalidParam)
+    ));
+    assert!(matches!(
+        early.set_size_granularity(48),
+        Err(AllocError::InvalidParam)
+    ));
+    early.set_size_granularity(64).unwrap();
+
+    // 大小向上取整，下一次分配从 64 字节边界开始
+    let small = Layout::from_size_align(10, 1).unwrap();
+    let first = early.alloc(small).unwrap();
+    assert_eq!(first.as_ptr() as usize, start);
+    assert_eq!(early.byte_cursor(), start + 64);
+    assert_eq!(early.wasted_bytes(), 54);
+    let one = Layout::from_size_align(1, 1).unwrap();
+    let second = early.alloc(one).unwrap();
+    assert_eq!(second.as_ptr() as usize, start + 64);
+    assert_eq!(early.byte_cursor(), start + 128);
+    assert_eq!(early.wasted_bytes(), 54 + 63);
+    assert_eq!(early.max_alloc_bytes(1) % 64, 0);
+    // 有存活的分配时不能修改
+    assert!(matches!(
+        early.set_size_granularity(1),
+        Err(AllocError::InvalidParam)
+    ));
+
+    // 释放和原地调整都按取整后的大小
+    early.dealloc(second, one);
+    assert_eq!(early.byte_cursor(), start + 64);
+    let grown = Layout::from_size_align(100, 1).unwrap();
+    let ptr = early.realloc(first, small, grown).unwrap();
+    assert_eq!(ptr, first);
+    assert_eq!(early.byte_cursor(), start + 128);
+    assert_eq!(early.wasted_bytes(), 54 + 63 + 28);
+    early.shrink(ptr, grown, small);
+    assert_eq!(early.byte_cursor(), start + 64);
+    early.dealloc(ptr, small);
+    assert!(early.is_empty());
+
+    early.set_size_granularity(1).unwrap();
+    early.alloc(small).unwrap();
+    assert_eq!(early.byte_cursor(), start + 10);
+    assert_eq!(early.wasted_bytes(), 54 + 63 + 28);
+}
+
+#[cfg(feature = "spin")]
+#[test]
+fn test_global_alloc() {
+    use crate::GlobalEarlyAllocator;
+    use core::alloc::GlobalAlloc;
+
+    let mut arena = Arena::new(4);
+    let early = GlobalEarlyAllocator::<PAGE_SIZE>::new();
+    early.init(arena.start(), 4 * PAGE_SIZE);
+
+    let layout = Layout::new::<[u64; 4]>();
+    unsafe {
+        let ptr = GlobalAlloc::alloc(&early, layout) as *mut [u64; 4];
+        assert!(!ptr.is_null());
+        ptr.write([1, 2, 3, 4]);
+        assert_eq!(*ptr, [1, 2, 3, 4]);
+        GlobalAlloc::dealloc(&early, ptr as *mut u8, layout);
+    }
+    assert_eq!(early.lock().used_bytes(), 0);
+
+    let huge = Layout::from_size_align(8 * PAGE_SIZE, 8).unwrap();
+    assert!(unsafe { GlobalAlloc::alloc(&early, huge) }.is_null());
+}
+
+/// Checks the page size dependent behavior on an allocator initialized with
+/// `[start, start + 8 * page_size + 0x123)`, the same for [`EarlyAllocator`]
+/// and [`DynEarlyAllocator`].
+fn check_page_size<A>(early: &mut A, start: usize, page_size: usize)
+where
+    A: BaseAllocator + ByteAllocator + PageAllocator,
+{
+    assert_eq!(early.total_bytes(), 8 * page_size);
+    assert_eq!(early.available_pages(), 8);
+
+    let layout = Layout::from_size_align(0x10, 8).unwrap();
+    let ptr = early.alloc(layout).unwrap();
+    assert_eq!(early.used_bytes(), 0x10);
+    assert_eq!(early.total_pages(), 8);
+
+    let shift = page_size.trailing_zeros() as usize;
+    assert!(matches!(
+        early.alloc_pages(0, shift),
+        Err(AllocError::InvalidParam)
+    ));
+    let addr = early.alloc_pages(2, shift).unwrap();
+    assert_eq!(addr, start + 6 * page_size);
+    assert_eq!(early.used_pages(), 2);
+    assert_eq!(early.available_pages(), 5);
+    // 有存活的页时不能扩展
+    assert!(matches!(
+        early.add_memory(start + 8 * page_size, page_size),
+        Err(AllocError::InvalidParam)
+    ));
+    early.dealloc_pages(addr, 2);
+    assert_eq!(early.used_pages(), 0);
+
+    // 不足一页的扩展被拒绝
+    assert!(matches!(
+        early.add_memory(start + 8 * page_size, page_size - 1),
+        Err(AllocError::InvalidParam)
+    ));
+    early.add_memory(start + 8 * page_size, page_size).unwrap();
+    assert_eq!(early.available_pages(), 8);
+    early.dealloc(ptr, layout);
+    assert_eq!(early.used_bytes(), 0);
+    assert_eq!(early.available_pages(), 9);
+}
+
+#[test]
+fn test_const_page_size() {
+    let mut arena = Arena::with_page_size(10, 0x1000);
+    let start = arena.start();
+    let mut early = EarlyAllocator::<0x1000>::new();
+    early.init(start, 8 * 0x1000 + 0x123);
+    check_page_size(&mut early, start, 0x1000);
+
+    let mut arena = Arena::with_page_size(10, 0x10000);
+    let start = arena.start();
+    let mut early = EarlyAllocator::<0x10000>::new();
+    early.init(start, 8 * 0x10000 + 0x123);
+    check_page_size(&mut early, start, 0x10000);
+}
+
+#[test]
+fn test_dyn_page_size() {
+    for page_size in [0x1000, 0x10000] {
+        let mut arena = Arena::with_page_size(10, page_size);
+        let start = arena.start();
+        let mut early = DynEarlyAllocator::new();
+        early.init(start, 8 * page_size + 0x123, page_size);
+        assert_eq!(early.page_size(), page_size);
+        check_page_size(&mut early, start, page_size);
+    }
+}
+
+#[test]
+fn test_oom_hook() {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static OOM_SIZE: AtomicUsize = AtomicUsize::new(0);
+    fn hook(layout: Layout) {
+        OOM_SIZE.store(layout.size(), Ordering::SeqCst);
+    }
+
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 2 * PAGE_SIZE);
+    early.set_oom_hook(Some(hook));
+    early
+        .alloc(Layout::from_size_align(0x100, 8).unwrap())
+        .unwrap();
+    assert_eq!(OOM_SIZE.load(Ordering::SeqCst), 0);
+
+    assert!(early
+        .alloc(Layout::from_size_align(2 * PAGE_SIZE, 8).unwrap())
+        .is_err());
+    assert_eq!(OOM_SIZE.load(Ordering::SeqCst), 2 * PAGE_SIZE);
+    assert!(early.alloc_pages(2, 12).is_err());
+    assert_eq!(OOM_SIZE.load(Ordering::SeqCst), 2 * PAGE_SIZE);
+    assert!(early.alloc_pages(3, 12).is_err());
+    assert_eq!(OOM_SIZE.load(Ordering::SeqCst), 3 * PAGE_SIZE);
+}
+
+#[test]
+fn test_event_sink() {
+    use std::sync::Mutex;
+
+    static EVENTS: Mutex<Vec<AllocEvent>> = Mutex::new(Vec::new());
+    fn sink(event: AllocEvent) {
+        EVENTS.lock().unwrap().push(event);
+    }
+
+    let mut arena = Arena::new(4);
+    let base = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(base, 4 * PAGE_SIZE);
+    let small = Layout::from_size_align(0x20, 8).unwrap();
+    // 未设置时不通知
+    let ptr = early.alloc(small).unwrap();
+    early.dealloc(ptr, small);
+
+    early.set_event_sink(Some(sink));
+    let layout = Layout::from_size_align(0x40, 8).unwrap();
+    let a = early.alloc(layout).unwrap();
+    let b = early.alloc(small).unwrap();
+    let page = early.alloc_pages(1, 12).unwrap();
+    // 失败的分配不通知
+    assert!(early.alloc_pages(8, 12).is_err());
+    early.dealloc(b, small);
+    early.dealloc(a, layout);
+    // 被忽略的多余释放不通知
+    early.dealloc(a, layout);
+    early.dealloc_pages(page, 1);
+
+    early.set_event_sink(None);
+    early.alloc(small).unwrap();
+    assert_eq!(
+        *EVENTS.lock().unwrap(),
+        [
+            AllocEvent::Alloc {
+                addr: base,
+                size: 0x40
+            },
+            AllocEvent::Alloc {
+                addr: base + 0x40,
+                size: 0x20
+            },
+            AllocEvent::AllocPages {
+                addr: base + 3 * PAGE_SIZE,
+                num_pages: 1
+            },
+            AllocEvent::Dealloc {
+                addr: base + 0x40,
+                size: 0x20
+            },
+            AllocEvent::Dealloc {
+                addr: base,
+                size: 0x40
+            },
+            AllocEvent::DeallocPages {
+                addr: base + 3 * PAGE_SIZE,
+                num_pages: 1
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_retry_on_oom() {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static RETRIES: AtomicUsize = AtomicUsize::new(0);
+    fn free_all() -> bool {
+        // 阶段结束，之前的分配都已丢弃
+        RETRIES.fetch_add(1, Ordering::SeqCst);
+        true
+    }
+    fn give_up() -> bool {
+        false
+    }
+
+    let mut arena = Arena::new(2);
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(arena.start(), 2 * PAGE_SIZE);
+    let layout = Layout::from_size_align(PAGE_SIZE, 8).unwrap();
+    let first = early.alloc(layout).unwrap();
+    early.alloc(Layout::new::<u64>()).unwrap();
+
+    // 默认不重试
+    assert!(matches!(early.alloc(layout), Err(AllocError::NoMemory)));
+    early.set_retry_on_oom(Some(give_up));
+    assert!(matches!(early.alloc(layout), Err(AllocError::NoMemory)));
+    assert_eq!(early.used_bytes(), PAGE_SIZE + 8);
+
+    early.set_retry_on_oom(Some(free_all));
+    assert_eq!(early.alloc(layout).unwrap(), first);
+    assert_eq!(RETRIES.load(Ordering::SeqCst), 1);
+    assert_eq!(early.used_bytes(), PAGE_SIZE);
+    // 大于整个区域的请求不重试
+    let huge = Layout::from_size_align(4 * PAGE_SIZE, 8).unwrap();
+    assert!(matches!(early.alloc(huge), Err(AllocError::InvalidParam)));
+    assert_eq!(RETRIES.load(Ordering::SeqCst), 1);
+    // 只重试一次，页区域不会被回收
+    early.dealloc(first, layout);
+    early.alloc_pages(1, 12).unwrap();
+    let big = Layout::from_size_align(PAGE_SIZE + 8, 8).unwrap();
+    assert!(matches!(early.alloc(big), Err(AllocError::NoMemory)));
+    assert_eq!(RETRIES.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_page_size() {
+    let early = EarlyAllocator::<PAGE_SIZE>::new();
+    assert_eq!(early.page_size(), PAGE_SIZE);
+    assert_eq!(EarlyAllocator::<0x10000>::new().page_size(), 0x10000);
+}
+
+#[test]
+fn test_max_aligned_pages() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    assert_eq!(early.max_aligned_pages(12), 0);
+    early.init(BASE + 0x10, 32 * PAGE_SIZE);
+    early
+        .alloc(Layout::from_size_align(PAGE_SIZE, 8).unwrap())
+        .unwrap();
+    // 页区非空，对齐从页游标开始计算
+    early.alloc_pages(3, 12).unwrap();
+
+    for single_ended in [false, true] {
+        if single_ended {
+            early.reset();
+            early.set_single_ended(true).unwrap();
+            early
+                .alloc(Layout::from_size_align(0x30, 8).unwrap())
+                .unwrap();
+        }
+        for reserve in [0, 3 * PAGE_SIZE + 0x10] {
+            early.set_byte_reserve(reserve);
+            for align_pow2 in [0, 12, 13, 14, 16, 30] {
+                let max = early.max_aligned_pages(align_pow2);
+                let state = early.snapshot();
+                if max > 0 {
+                    let pos = early.alloc_pages(max, align_pow2).unwrap();
+                    assert_eq!(pos % (1 << align_pow2), 0);
+                    early.restore(state);
+                }
+                assert!(matches!(
+                    early.alloc_pages(max + 1, align_pow2),
+                    Err(AllocError::NoMemory)
+                ));
+                assert_eq!(early.snapshot(), state);
+            }
+            assert_eq!(early.max_aligned_pages(30), 0);
+            assert!(early.max_aligned_pages(14) < early.max_aligned_pages(12));
+        }
+        early.set_byte_reserve(0);
+    }
+    early.freeze();
+    assert_eq!(early.max_aligned_pages(12), 0);
+}
+
+#[test]
+fn test_pages_for() {
+    type Early = EarlyAllocator<PAGE_SIZE>;
+    assert_eq!(Early::pages_for(0), 0);
+    assert_eq!(Early::pages_for(1), 1);
+    assert_eq!(Early::pages_for(PAGE_SIZE - 1), 1);
+    assert_eq!(Early::pages_for(PAGE_SIZE), 1);
+    assert_eq!(Early::pages_for(PAGE_SIZE + 1), 2);
+    assert_eq!(Early::pages_for(3 * PAGE_SIZE), 3);
+    // 不会溢出
+    assert_eq!(Early::pages_for(usize::MAX), usize::MAX / PAGE_SIZE + 1);
+
+    assert_eq!(
+        Early::layout_for_pages(0),
+        Layout::from_size_align(0, PAGE_SIZE).unwrap()
+    );
+    let layout = Early::layout_for_pages(3);
+    assert_eq!((layout.size(), layout.align()), (3 * PAGE_SIZE, PAGE_SIZE));
+    assert_eq!(Early::pages_for(layout.size()), 3);
+}
+
+#[test]
+#[should_panic(expected = "too many pages")]
+fn test_layout_for_too_many_pages() {
+    EarlyAllocator::<PAGE_SIZE>::layout_for_pages(usize::MAX / PAGE_SIZE + 1);
+}
+
+#[test]
+fn test_min_align() {
+    let mut early = EarlyAllocator::<PAGE_SIZE, 16, 64>::new();
+    early.init(BASE + 8, 2 * PAGE_SIZE);
+    let byte = Layout::from_size_align(3, 1).unwrap();
+    let mut last = 0;
+    for _ in 0..4 {
+        let ptr = early.alloc(byte).unwrap().as_ptr() as usize;
+        assert_eq!(ptr % 64, 0);
+        assert!(ptr > last);
+        last = ptr;
+    }
+    // 更大的对齐要求不受影响
+    let page = Layout::from_size_align(8, PAGE_SIZE).unwrap();
+    assert_eq!(early.alloc(page).unwrap().as_ptr() as usize % PAGE_SIZE, 0);
+
+    assert!(matches!(
+        early.alloc_at(early.byte_cursor() + 8, byte),
+        Err(AllocError::InvalidParam)
+    ));
+    let b_pos = crate::align_up(early.byte_cursor(), 64).unwrap();
+    assert_eq!(early.available_for(byte), early.page_cursor() - b_pos);
+    assert_eq!(early.max_alloc_bytes(1), early.page_cursor() - b_pos);
+
+    // 默认不额外对齐
+    let mut plain = EarlyAllocator::<PAGE_SIZE>::new();
+    plain.init(BASE, 2 * PAGE_SIZE);
+    plain.alloc(byte).unwrap();
+    assert_eq!(plain.alloc(byte).unwrap().as_ptr() as usize, BASE + 3);
+}
+
+#[test]
+fn test_available_for() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 2 * PAGE_SIZE);
+    let layout = Layout::from_size_align(8, 0x100).unwrap();
+    assert_eq!(early.available_for(layout), 2 * PAGE_SIZE);
+
+    early.alloc(Layout::from_size_align(1, 1).unwrap()).unwrap();
+    assert_eq!(early.available_bytes(), 2 * PAGE_SIZE - 1);
+    assert_eq!(early.available_for(layout), 2 * PAGE_SIZE - 0x100);
+    let fit = Layout::from_size_align(early.available_for(layout), 0x100).unwrap();
+    early.alloc(fit).unwrap();
+    assert_eq!(early.available_bytes(), 0);
+
+    let big = Layout::from_size_align(8, 4 * PAGE_SIZE).unwrap();
+    assert_eq!(early.available_for(big), 0);
+}
+
+#[test]
+fn test_cursors_meet() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 4 * PAGE_SIZE);
+    early.alloc_pages(2, 12).unwrap();
+    early
+        .alloc(Layout::from_size_align(PAGE_SIZE, 8).unwrap())
+        .unwrap();
+    early.alloc_pages(1, 12).unwrap();
+    assert_eq!(early.byte_cursor(), early.page_cursor());
+    assert_eq!(early.available_bytes(), 0);
+    assert_eq!(early.available_pages(), 0);
+
+    let one = Layout::from_size_align(1, 1).unwrap();
+    assert!(matches!(early.alloc(one), Err(AllocError::NoMemory)));
+    assert!(matches!(early.alloc_pages(1, 0), Err(AllocError::NoMemory)));
+    assert_eq!(early.byte_cursor(), BASE + PAGE_SIZE);
+    assert_eq!(early.page_cursor(), BASE + PAGE_SIZE);
+
+    // the same from the other side: the bytes area reaches the pages area
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 4 * PAGE_SIZE);
+    early.alloc_pages(1, 12).unwrap();
+    early
+        .alloc(Layout::from_size_align(3 * PAGE_SIZE, 8).unwrap())
+        .unwrap();
+    assert!(matches!(early.alloc(one), Err(AllocError::NoMemory)));
+    assert!(matches!(early.alloc_pages(1, 0), Err(AllocError::NoMemory)));
+}
+
+#[test]
+fn test_lifo_dealloc() {
+    let mut arena = Arena::new(4);
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(arena.start(), 4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(0x40, 8).unwrap();
+    let a = early.alloc(layout).unwrap();
+    let b = early.alloc(layout).unwrap();
+    let c = early.alloc(layout).unwrap();
+
+    // not the top block: nothing is reclaimed
+    early.dealloc(b, layout);
+    assert_eq!(early.used_bytes(), 0xc0);
+    // the top block is reclaimed at once
+    early.dealloc(c, layout);
+    assert_eq!(early.used_bytes(), 0x80);
+    let d = early.alloc(layout).unwrap();
+    assert_eq!(d, c);
+    early.dealloc(d, layout);
+    early.dealloc(a, layout);
+    assert_eq!(early.used_bytes(), 0);
+}
+
+#[test]
+fn test_stats() {
+    let mut arena = Arena::new(8);
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(arena.start(), 8 * PAGE_SIZE);
+    let small = Layout::from_size_align(0x30, 8).unwrap();
+    let a = early.alloc(small).unwrap();
+    early.alloc_pages(2, 12).unwrap();
+    early
+        .alloc(Layout::from_size_align(0x100, 0x40).unwrap())
+        .unwrap();
+    early.dealloc(a, small);
+    early.alloc_pages(1, 13).unwrap();
+
+    let stats = early.stats();
+    assert_eq!(stats.byte_allocs, 1);
+    assert_eq!(stats.page_allocs, 3);
+    assert_eq!(
+        stats.used_bytes + stats.available_bytes + stats.used_pages * PAGE_SIZE,
+        stats.total_bytes
+    );
+    assert_eq!(
+        stats.used_pages + stats.available_pages,
+        early.currently_allocatable_pages()
+    );
+    assert_eq!(stats.total_pages, 8);
+    assert_eq!(stats.used_bytes, early.used_bytes());
+    assert_eq!(stats.used_pages, early.used_pages());
+}
+
+#[test]
+fn test_max_alloc() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 4 * PAGE_SIZE);
+    early.set_max_alloc(0x100);
+    assert!(early
+        .alloc(Layout::from_size_align(0x100, 8).unwrap())
+        .is_ok());
+    assert!(matches!(
+        early.alloc(Layout::from_size_align(0x101, 8).unwrap()),
+        Err(AllocError::InvalidParam)
+    ));
+    assert_eq!(early.used_bytes(), 0x100);
+    early.set_max_alloc(usize::MAX);
+    assert!(early
+        .alloc(Layout::from_size_align(0x101, 8).unwrap())
+        .is_ok());
+}
+
+#[cfg(feature = "test-inject")]
+#[test]
+fn test_fail_after() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 8 * PAGE_SIZE);
+    let layout = Layout::from_size_align(0x30, 8).unwrap();
+    early.fail_after(3);
+    early.alloc(layout).unwrap();
+    early.alloc_pages(1, 12).unwrap();
+    let state = early.snapshot();
+    assert!(matches!(early.alloc(layout), Err(AllocError::NoMemory)));
+    assert_eq!(early.snapshot(), state);
+    assert!(early.available_bytes() > layout.size());
+    // 只注入一次
+    early.alloc(layout).unwrap();
+
+    early.fail_after(1);
+    assert!(matches!(
+        early.alloc_pages(1, 12),
+        Err(AllocError::NoMemory)
+    ));
+    early.alloc_pages(1, 12).unwrap();
+    early.fail_after(2);
+    early.fail_after(0);
+    early.alloc(layout).unwrap();
+    early.alloc(layout).unwrap();
+    assert_eq!((early.byte_alloc_count(), early.page_alloc_count()), (4, 2));
+}
+
+#[cfg(feature = "secure-pages")]
+#[test]
+fn test_secure_pages() {
+    let mut arena = Arena::new(8);
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(arena.start(), 8 * PAGE_SIZE);
+
+    let a = early.alloc_pages(1, 12).unwrap();
+    let b = early.alloc_pages(2, 12).unwrap();
+    unsafe {
+        core::ptr::write_bytes(a as *mut u8, 0xaa, PAGE_SIZE);
+        core::ptr::write_bytes(b as *mut u8, 0xbb, 2 * PAGE_SIZE);
+    }
+    early.dealloc_pages(a, 1);
+    early.dealloc_pages(b, 2);
+
+    let c = early.alloc_pages(3, 12).unwrap();
+    assert_eq!(c, b);
+    let data = unsafe { core::slice::from_raw_parts(c as *const u8, 3 * PAGE_SIZE) };
+    assert!(data.iter().all(|&x| x == 0));
+}
+
+#[test]
+#[cfg_attr(debug_assertions, should_panic)]
+fn test_dealloc_out_of_range() {
+    let mut arena = Arena::new(4);
+    let start = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(start, 4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(0x10, 8).unwrap();
+    early.alloc(layout).unwrap();
+
+    let wild = NonNull::new((start + 0x800) as *mut u8).unwrap();
+    early.dealloc(wild, layout);
+    assert_eq!(early.stats().byte_allocs, 1);
+    assert_eq!(early.byte_cursor(), start + 0x10);
+}
+
+#[test]
+fn test_used_permille() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    assert_eq!(early.byte_used_permille(), 0);
+    assert_eq!(early.page_used_permille(), 0);
+
+    early.init(BASE, 4 * PAGE_SIZE);
+    assert_eq!(early.byte_used_permille(), 0);
+    assert_eq!(early.page_used_permille(), 0);
+    early.alloc_pages(2, 12).unwrap();
+    assert_eq!(early.page_used_permille(), 500);
+    early
+        .alloc(Layout::from_size_align(PAGE_SIZE, 8).unwrap())
+        .unwrap();
+    assert_eq!(early.byte_used_permille(), 250);
+    early.alloc_pages(1, 12).unwrap();
+    assert_eq!(early.page_used_permille(), 1000);
+
+    // `used * 1000` would overflow `usize`
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(PAGE_SIZE, 1 << 60);
+    early
+        .alloc(Layout::from_size_align(1 << 59, 8).unwrap())
+        .unwrap();
+    assert_eq!(early.byte_used_permille(), 500);
+}
+
+#[test]
+fn test_reset_sides() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 8 * PAGE_SIZE);
+    early
+        .alloc(Layout::from_size_align(0x100, 8).unwrap())
+        .unwrap();
+    let page = early.alloc_pages(2, 12).unwrap();
+
+    // 页仍在使用时重置字节区
+    early.reset_bytes();
+    assert_eq!(early.used_bytes(), 0);
+    assert_eq!(early.used_pages(), 2);
+    assert_eq!(early.page_cursor(), page);
+    let ptr = early
+        .alloc(Layout::from_size_align(0x100, 8).unwrap())
+        .unwrap();
+    assert_eq!(ptr.as_ptr() as usize, BASE);
+
+    // 字节仍在使用时重置页区
+    early.reset_pages();
+    assert_eq!(early.used_pages(), 0);
+    assert_eq!(early.byte_cursor(), BASE + 0x100);
+    assert_eq!(early.alloc_pages(1, 12).unwrap(), BASE + 7 * PAGE_SIZE);
+
+    // 重新 init 会清空两边的计数
+    early.init(BASE, 8 * PAGE_SIZE);
+    let stats = early.stats();
+    assert_eq!((stats.byte_allocs, stats.page_allocs), (0, 0));
+}
+
+#[test]
+fn test_alignment_slack() {
+    const MB2: usize = 0x20_0000;
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 2 * MB2);
+
+    let (pos, slack) = early.alloc_aligned_pages(1, 21).unwrap();
+    assert_eq!(pos, BASE + MB2);
+    assert_eq!(slack, MB2 / PAGE_SIZE - 1);
+    assert_eq!(
+        early.release_alignment_slack(),
+        Some((pos + PAGE_SIZE, slack))
+    );
+    assert_eq!(early.stats().page_allocs, 1 + slack);
+    assert_eq!(early.release_alignment_slack(), None);
+
+    // 中间有其他页分配时不能再取回
+    early.reset_pages();
+    let (_, slack) = early.alloc_aligned_pages(1, 21).unwrap();
+    assert!(slack > 0);
+    early.alloc_pages(1, 12).unwrap();
+    assert_eq!(early.release_alignment_slack(), None);
+
+    // 无需对齐时没有浪费
+    let (_, slack) = early.alloc_aligned_pages(1, 12).unwrap();
+    assert_eq!(slack, 0);
+    assert_eq!(early.release_alignment_slack(), None);
+}
+
+#[test]
+fn test_is_empty() {
+    let mut arena = Arena::new(4);
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(arena.start(), 4 * PAGE_SIZE);
+    assert!(early.is_empty());
+
+    let layout = Layout::from_size_align(0x10, 8).unwrap();
+    let ptr = early.alloc(layout).unwrap();
+    assert!(!early.is_empty());
+    let page = early.alloc_pages(1, 12).unwrap();
+    early.dealloc(ptr, layout);
+    assert!(!early.is_empty());
+    early.dealloc_pages(page, 1);
+    assert!(early.is_empty());
+    assert_eq!(early.byte_cursor(), early.start_addr());
+    assert_eq!(early.page_cursor(), early.end_addr());
+}
+
+#[test]
+fn test_try_init() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    let rejected = [
+        (BASE, 0),                               // 空区域
+        (usize::MAX - PAGE_SIZE, 2 * PAGE_SIZE), // 溢出
+        (BASE + 1, PAGE_SIZE),                   // 放不下一个对齐的页
+        (BASE, PAGE_SIZE - 1),
+    ];
+    for (start, size) in rejected {
+        assert!(matches!(
+            early.try_init(start, size),
+            Err(AllocError::InvalidParam)
+        ));
+        assert_eq!(early.total_bytes(), 0);
+    }
+    early.try_init(BASE + 1, 2 * PAGE_SIZE).unwrap();
+    assert_eq!(early.total_pages(), 1);
+}
+
+#[test]
+fn test_with_region() {
+    let mut arena = Arena::new(4);
+    let base = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::with_region(base, 4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(0x40, 8).unwrap();
+    assert_eq!(early.alloc(layout).unwrap().as_ptr() as usize, base);
+    assert_eq!(early.alloc_pages(1, 12).unwrap(), base + 3 * PAGE_SIZE);
+
+    let early = EarlyAllocator::<PAGE_SIZE>::try_with_region(BASE + 1, 2 * PAGE_SIZE).unwrap();
+    assert_eq!(early.total_pages(), 1);
+    assert!(matches!(
+        EarlyAllocator::<PAGE_SIZE>::try_with_region(BASE, PAGE_SIZE - 1),
+        Err(AllocError::InvalidParam)
+    ));
+}
+
+#[test]
+#[should_panic(expected = "empty region")]
+fn test_init_panics() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 0);
+}
+
+#[test]
+fn test_ranges() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 8 * PAGE_SIZE);
+    early
+        .alloc(Layout::from_size_align(0x123, 8).unwrap())
+        .unwrap();
+    early.alloc_pages(2, 12).unwrap();
+
+    let (bytes, free, pages) = (
+        early.used_bytes_range(),
+        early.free_range(),
+        early.used_pages_range(),
+    );
+    assert_eq!(bytes, BASE..BASE + 0x123);
+    assert_eq!(pages, BASE + 6 * PAGE_SIZE..BASE + 8 * PAGE_SIZE);
+    // 三段首尾相接，覆盖整个区域
+    assert_eq!(bytes.start, early.start_addr());
+    assert_eq!(bytes.end, free.start);
+    assert_eq!(free.end, pages.start);
+    assert_eq!(pages.end, early.end_addr());
+    assert_eq!(bytes.len() + free.len() + pages.len(), early.total_bytes());
+}
+
+#[test]
+fn test_available_pages_unaligned() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 4 * PAGE_SIZE);
+    early
+        .alloc(Layout::from_size_align(PAGE_SIZE + 1, 1).unwrap())
+        .unwrap();
+    assert_eq!(early.available_pages(), 2);
+    assert!(early.alloc_pages(3, 12).is_err());
+    early.alloc_pages(2, 12).unwrap();
+    assert_eq!(early.available_pages(), 0);
+
+    // 字节区末尾紧挨页区时也不会下溢
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 2 * PAGE_SIZE);
+    early.alloc_pages(1, 12).unwrap();
+    early
+        .alloc(Layout::from_size_align(PAGE_SIZE - 1, 1).unwrap())
+        .unwrap();
+    assert_eq!(early.available_pages(), 0);
+}
+
+#[test]
+fn test_with_backing() {
+    let (mut early, backing) = with_backing::<PAGE_SIZE>(4 * PAGE_SIZE);
+    let start = backing.as_ptr() as usize;
+    assert_eq!(early.start_addr(), start);
+    assert!(early.end_addr() <= start + backing.len());
+    assert!(early.total_pages() >= 3);
+
+    let layout = Layout::from_size_align(0x40, 8).unwrap();
+    let ptr = early.alloc(layout).unwrap();
+    unsafe { ptr.as_ptr().write_bytes(0xaa, 0x40) };
+    let page = early.alloc_pages(1, 12).unwrap();
+    assert_eq!(page % PAGE_SIZE, 0);
+    assert!(page >= start + 0x40 && page + PAGE_SIZE <= start + backing.len());
+    unsafe { (page as *mut u8).write_bytes(0x55, PAGE_SIZE) };
+
+    early.dealloc(ptr, layout);
+    early.dealloc_pages(page, 1);
+    assert!(early.is_empty());
+}
+
+#[test]
+fn test_page_fragment() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 4 * PAGE_SIZE);
+    let page = BASE + 3 * PAGE_SIZE;
+    for i in 0..PAGE_SIZE / 512 {
+        assert_eq!(early.alloc_page_fragment(512, 512).unwrap(), page + i * 512);
+    }
+    assert_eq!(early.used_pages(), 1);
+
+    // 当前页放不下时才分配新页
+    assert_eq!(
+        early.alloc_page_fragment(512, 512).unwrap(),
+        page - PAGE_SIZE
+    );
+    assert_eq!(
+        early.alloc_page_fragment(8, 64).unwrap(),
+        page - PAGE_SIZE + 512
+    );
+    assert_eq!(early.used_pages(), 2);
+
+    assert!(matches!(
+        early.alloc_page_fragment(0, 8),
+        Err(AllocError::InvalidParam)
+    ));
+    assert!(matches!(
+        early.alloc_page_fragment(PAGE_SIZE + 1, 8),
+        Err(AllocError::InvalidParam)
+    ));
+}
+
+#[test]
+fn test_contains() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    assert!(!early.contains(0));
+    early.init(BASE, 4 * PAGE_SIZE);
+    let end = early.end_addr();
+    assert!(!early.contains(BASE - 1));
+    assert!(early.contains(BASE));
+    assert!(early.contains(end - 1));
+    assert!(!early.contains(end));
+    assert!(!early.contains(usize::MAX));
+}
+
+#[test]
+fn test_reserve() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 4 * PAGE_SIZE);
+    let front = early.reserve_front(0x123).unwrap();
+    let back = early.reserve_back(0x1001).unwrap();
+    assert_eq!(front, BASE);
+    assert_eq!(back, BASE + 2 * PAGE_SIZE);
+    assert_eq!(early.used_pages(), 2);
+    assert_eq!(early.stats().byte_allocs, 1);
+
+    // 与之后的分配互不重叠
+    let ptr = early.alloc(Layout::from_size_align(8, 8).unwrap()).unwrap();
+    assert!(ptr.as_ptr() as usize >= front + 0x123);
+    let front2 = early.reserve_front(0x10).unwrap();
+    assert!(front2 >= ptr.as_ptr() as usize + 8);
+    let back2 = early.reserve_back(PAGE_SIZE).unwrap();
+    assert_eq!(back2 + PAGE_SIZE, back);
+    assert!(back2 >= front2 + 0x10);
+
+    assert!(matches!(
+        early.reserve_front(0),
+        Err(AllocError::InvalidParam)
+    ));
+    assert!(matches!(
+        early.reserve_back(PAGE_SIZE),
+        Err(AllocError::NoMemory)
+    ));
+    assert!(matches!(
+        early.reserve_front(PAGE_SIZE),
+        Err(AllocError::NoMemory)
+    ));
+}
+
+#[test]
+fn test_reserve_pages_at() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 8 * PAGE_SIZE);
+    let top = BASE + 8 * PAGE_SIZE;
+    early.reserve_pages_at(top - 2 * PAGE_SIZE, 2).unwrap();
+    assert_eq!(early.page_cursor(), top - 2 * PAGE_SIZE);
+    assert_eq!((early.page_alloc_count(), early.reserved_pages()), (2, 2));
+    // 之后分配的页在预留的页之下
+    let pos = early.alloc_pages(1, 12).unwrap();
+    assert_eq!(pos, top - 3 * PAGE_SIZE);
+    early.reserve_pages_at(pos - PAGE_SIZE, 1).unwrap();
+    assert_eq!(early.reserved_pages(), 3);
+
+    // 释放其他页后预留的页仍被占用
+    early.dealloc_pages(pos, 1);
+    assert_eq!(early.page_cursor(), pos - PAGE_SIZE);
+    assert_eq!(early.available_pages(), 4);
+
+    let interior = [
+        (BASE + PAGE_SIZE, 1),    // 空闲区内部
+        (pos - 3 * PAGE_SIZE, 1), // 与页游标之间有空隙
+        (top - PAGE_SIZE, 1),     // 已在页区内
+        (pos - PAGE_SIZE - 1, 1), // 未对齐
+        (pos - PAGE_SIZE, 0),
+    ];
+    for (base, num_pages) in interior {
+        assert!(matches!(
+            early.reserve_pages_at(base, num_pages),
+            Err(AllocError::InvalidParam)
+        ));
+    }
+    // 不能越过字节区
+    early
+        .alloc(Layout::from_size_align(PAGE_SIZE + 1, 8).unwrap())
+        .unwrap();
+    let cursor = early.page_cursor();
+    assert!(matches!(
+        early.reserve_pages_at(cursor - 3 * PAGE_SIZE, 3),
+        Err(AllocError::NoMemory)
+    ));
+    assert_eq!(early.reserved_pages(), 3);
+    early.reset_pages();
+    assert_eq!(early.reserved_pages(), 0);
+}
+
+#[test]
+fn test_invalid_param() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    assert!(matches!(
+        early.try_init(BASE, 0),
+        Err(AllocError::InvalidParam)
+    ));
+    early.init(BASE, 4 * PAGE_SIZE);
+
+    // 错误的请求
+    assert!(matches!(
+        early.alloc_pages(0, 12),
+        Err(AllocError::InvalidParam)
+    ));
+    assert!(matches!(
+        early.alloc_pages(usize::MAX / PAGE_SIZE + 1, 12),
+        Err(AllocError::InvalidParam)
+    ));
+    assert!(matches!(
+        early.alloc_pages(1, usize::BITS as usize),
+        Err(AllocError::InvalidParam)
+    ));
+    assert!(matches!(
+        early.add_memory(BASE + 8 * PAGE_SIZE, PAGE_SIZE),
+        Err(AllocError::InvalidParam)
+    ));
+    assert!(matches!(
+        early.add_memory(BASE + 4 * PAGE_SIZE, 0),
+        Err(AllocError::InvalidParam)
+    ));
+    assert!(matches!(
+        early.add_memory(BASE + 4 * PAGE_SIZE, PAGE_SIZE - 1),
+        Err(AllocError::InvalidParam)
+    ));
+    assert!(matches!(
+        early.add_memory(BASE + 4 * PAGE_SIZE, usize::MAX),
+        Err(AllocError::InvalidParam)
+    ));
+    assert!(early.is_empty());
+    assert_eq!(early.end_addr(), BASE + 4 * PAGE_SIZE);
+
+    // 空间不足，字节分配大于整个区域则是错误的请求
+    let big = Layout::from_size_align(5 * PAGE_SIZE, 8).unwrap();
+    assert!(matches!(early.alloc(big), Err(AllocError::InvalidParam)));
+    assert!(matches!(
+        early.alloc_pages(5, 12),
+        Err(AllocError::NoMemory)
+    ));
+}
+
+#[test]
+fn test_max_alloc_bytes() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 2 * PAGE_SIZE);
+    assert_eq!(early.max_alloc_bytes(1), 2 * PAGE_SIZE);
+    assert_eq!(early.max_alloc_bytes(64), 2 * PAGE_SIZE);
+    assert_eq!(early.max_alloc_bytes(3), 0);
+
+    // 游标未对齐时扣除对齐填充
+    early.alloc(Layout::from_size_align(1, 1).unwrap()).unwrap();
+    assert_eq!(early.max_alloc_bytes(1), 2 * PAGE_SIZE - 1);
+    assert_eq!(early.max_alloc_bytes(64), 2 * PAGE_SIZE - 64);
+    assert_eq!(early.max_alloc_bytes(PAGE_SIZE), PAGE_SIZE);
+    assert_eq!(early.max_alloc_bytes(4 * PAGE_SIZE), 0);
+
+    early.set_max_alloc(0x100);
+    assert_eq!(early.max_alloc_bytes(64), 0x100);
+    early.set_max_alloc(usize::MAX);
+
+    let size = early.max_alloc_bytes(64);
+    let layout = Layout::from_size_align(size, 64).unwrap();
+    early.alloc(layout).unwrap();
+    assert_eq!(early.available_bytes(), 0);
+    assert_eq!(early.max_alloc_bytes(64), 0);
+}
+
+#[test]
+#[cfg(feature = "alloc-trace")]
+fn test_alloc_trace() {
+    let mut early = EarlyAllocator::<PAGE_SIZE, 4>::new();
+    early.init(BASE, 4 * PAGE_SIZE);
+    assert!(early.trace().is_empty());
+
+    early
+        .alloc(Layout::from_size_align(0x10, 8).unwrap())
+        .unwrap();
+    early
+        .alloc_tagged(Layout::from_size_align(0x20, 0x20).unwrap(), 7)
+        .unwrap();
+    // 失败的分配不记录
+    let big = Layout::from_size_align(8 * PAGE_SIZE, 8).unwrap();
+    assert!(early.alloc_tagged(big, 9).is_err());
+    let sites: Vec<_> = early.trace().iter().map(|e| e.site).collect();
+    assert_eq!(sites, [0, 7]);
+
+    let mut out = String::new();
+    early.dump_trace(&mut out).unwrap();
+    assert_eq!(
+        out,
+        "[0] site 0: size 0x10, align 0x8\n[1] site 7: size 0x20, align 0x20\n"
+    );
+
+    // 环满后覆盖最旧的记录
+    for site in 1..=5 {
+        let layout = Layout::from_size_align(site as usize, 1).unwrap();
+        early.alloc_tagged(layout, site).unwrap();
+    }
+    assert_eq!(early.trace().len(), 4);
+    let sites: Vec<_> = early.trace().iter().map(|e| e.site).collect();
+    assert_eq!(sites, [2, 3, 4, 5]);
+    assert_eq!(early.trace().iter().last().unwrap().size, 5);
+}
+
+#[test]
+#[cfg_attr(debug_assertions, should_panic)]
+fn test_dealloc_pages_out_of_range() {
+    let mut arena = Arena::new(8);
+    let start = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(start, 8 * PAGE_SIZE);
+    let a = early.alloc_pages(2, 12).unwrap();
+
+    // 越界、未对齐以及落在页区之外的释放都被忽略
+    early.dealloc_pages(a, 5);
+    early.dealloc_pages(a + 1, 1);
+    early.dealloc_pages(start, 2);
+    assert_eq!(early.used_pages(), 2);
+    assert_eq!(early.page_cursor(), a);
+    assert!(matches!(
+        early.try_dealloc_pages(a + 1, 1),
+        Err(AllocError::InvalidParam)
+    ));
+}
+
+#[test]
+fn test_alloc_array() {
+    let mut arena = Arena::new(4);
+    let start = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(start, 4 * PAGE_SIZE);
+    early.alloc(Layout::from_size_align(1, 1).unwrap()).unwrap();
+
+    // 元素按对齐填充到 0x10 字节
+    let elem = Layout::from_size_align(0xc, 0x10).unwrap();
+    let base = early.alloc_array(elem, 8).unwrap();
+    assert_eq!(base.as_ptr() as usize, start + 0x10);
+    assert_eq!(early.byte_cursor(), start + 0x90);
+    assert_eq!(early.stats().byte_allocs, 2);
+
+    let array = Layout::from_size_align(0x80, 0x10).unwrap();
+    early.dealloc(base, array);
+    assert_eq!(early.byte_cursor(), start + 0x10);
+    assert_eq!(early.stats().byte_allocs, 1);
+
+    let empty = early.alloc_array(elem, 0).unwrap();
+    assert_eq!(empty.as_ptr() as usize, 0x10);
+    assert_eq!(early.stats().byte_allocs, 1);
+    assert!(matches!(
+        early.alloc_array(elem, usize::MAX / 8),
+        Err(AllocError::InvalidParam)
+    ));
+    assert!(matches!(
+        early.alloc_array(elem, 4 * PAGE_SIZE / 0x10),
+        Err(AllocError::NoMemory)
+    ));
+    assert_eq!(early.byte_cursor(), start + 0x10);
+}
+
+#[test]
+fn test_untouched_bytes() {
+    let mut arena = Arena::new(8);
+    let start = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(start, 8 * PAGE_SIZE);
+    assert_eq!(early.untouched_bytes(), 8 * PAGE_SIZE);
+
+    let layout = Layout::from_size_align(PAGE_SIZE, 8).unwrap();
+    let a = early.alloc(layout).unwrap();
+    let p = early.alloc_pages(4, 12).unwrap();
+    assert_eq!(early.untouched_bytes(), 3 * PAGE_SIZE);
+
+    // 释放后中间空隙变大，但记录的是最小值
+    early.dealloc_pages(p, 4);
+    early.dealloc(a, layout);
+    assert_eq!(early.available_bytes(), 8 * PAGE_SIZE);
+    assert_eq!(early.untouched_bytes(), 3 * PAGE_SIZE);
+    let big = Layout::from_size_align(6 * PAGE_SIZE, 8).unwrap();
+    let b = early.alloc(big).unwrap();
+    assert_eq!(early.untouched_bytes(), 2 * PAGE_SIZE);
+    early.dealloc(b, big);
+
+    early.reset();
+    assert_eq!(early.untouched_bytes(), 8 * PAGE_SIZE);
+}
+
+#[test]
+fn test_alloc_pages_slice() {
+    let mut arena = Arena::new(4);
+    let start = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(start, 4 * PAGE_SIZE);
+
+    let slice = unsafe { early.alloc_pages_slice(2, 12) }.unwrap();
+    assert_eq!(slice.len(), 2 * PAGE_SIZE);
+    assert_eq!(slice.as_ptr() as usize, early.page_cursor());
+    slice.fill(0x5a);
+    slice[PAGE_SIZE] = 0xa5;
+    assert_eq!(arena.bytes()[2 * PAGE_SIZE], 0x5a);
+    assert_eq!(arena.bytes()[3 * PAGE_SIZE], 0xa5);
+    assert!(matches!(
+        unsafe { early.alloc_pages_slice(3, 12) },
+        Err(AllocError::NoMemory)
+    ));
+}
+
+#[test]
+fn test_small_align_fast_path() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 4 * PAGE_SIZE);
+    // 已对齐与未对齐的游标都与通用的向上对齐结果一致
+    for (size, align) in [
+        (8, 8),
+        (3, 1),
+        (2, 2),
+        (8, 8),
+        (1, 4),
+        (4, 4),
+        (5, 8),
+        (16, 16),
+    ] {
+        let b_pos = early.byte_cursor();
+        let expected = (b_pos + align - 1) & !(align - 1);
+        let layout = Layout::from_size_align(size, align).unwrap();
+        let ptr = early.alloc(layout).unwrap();
+        assert_eq!(ptr.as_ptr() as usize, expected);
+        assert_eq!(early.byte_cursor(), expected + size);
+    }
+    assert_eq!(early.wasted_bytes(), 17);
+}
+
+#[test]
+#[ignore = "benchmark, run with `--ignored --nocapture`"]
+fn bench_small_alloc() {
+    const ROUNDS: usize = 1000;
+    let num = 4 * PAGE_SIZE / 8;
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(8, 8).unwrap();
+    let begin = std::time::Instant::now();
+    for _ in 0..ROUNDS {
+        for _ in 0..num {
+            std::hint::black_box(early.alloc(layout).unwrap());
+        }
+        early.reset_bytes();
+    }
+    let elapsed = begin.elapsed();
+    println!(
+        "{} word-sized allocations: {:?} ({:.2} ns each)",
+        ROUNDS * num,
+        elapsed,
+        elapsed.as_nanos() as f64 / (ROUNDS * num) as f64
+    );
+}
+
+#[test]
+fn test_clone() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(0x100, 8).unwrap();
+    early.alloc(layout).unwrap();
+    early.alloc_pages(1, 12).unwrap();
+    let before = early.stats();
+
+    // 在副本上试探，直到空间耗尽
+    let mut trial = early.clone();
+    while trial.alloc_pages(1, 12).is_ok() {}
+    assert_eq!(trial.available_pages(), 0);
+    assert!(trial.alloc(layout).is_ok());
+    assert_eq!(early.stats(), before);
+    assert_eq!(early.page_cursor(), BASE + 3 * PAGE_SIZE);
+    assert_eq!(early.available_pages(), 2);
+}
+
+#[test]
+fn test_shard() {
+    let shards = EarlyAllocator::<PAGE_SIZE>::shard::<4>(BASE + 0x100, 16 * PAGE_SIZE).unwrap();
+    // 各分片相邻且覆盖整个区域
+    assert_eq!(shards[0].start_addr(), BASE + 0x100);
+    for pair in shards.windows(2) {
+        assert_eq!(pair[0].end_addr(), pair[1].start_addr());
+        assert_eq!(pair[1].start_addr() % PAGE_SIZE, 0);
+    }
+    assert_eq!(shards[3].end_addr(), BASE + 16 * PAGE_SIZE);
+    let pages: Vec<_> = shards.iter().map(|s| s.available_pages()).collect();
+    assert_eq!(pages, [3, 4, 4, 4]);
+
+    let [mut a, mut b, ..] = shards;
+    let pa = a.alloc_pages(1, 12).unwrap();
+    let pb = b.alloc_pages(1, 12).unwrap();
+    assert!(a.contains(pa) && !b.contains(pa));
+    assert!(b.contains(pb) && !a.contains(pb));
+
+    assert!(matches!(
+        EarlyAllocator::<PAGE_SIZE>::shard::<0>(BASE, 16 * PAGE_SIZE),
+        Err(AllocError::InvalidParam)
+    ));
+    assert!(matches!(
+        EarlyAllocator::<PAGE_SIZE>::shard::<8>(BASE, 4 * PAGE_SIZE),
+        Err(AllocError::InvalidParam)
+    ));
+}
+
+#[test]
+fn test_seal_window() {
+    let mut arena = Arena::new(8);
+    let start = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(start, 8 * PAGE_SIZE);
+    let a = early.alloc(Layout::new::<u64>()).unwrap();
+    let b = early.alloc(Layout::new::<u64>()).unwrap();
+    let page = early.alloc_pages(2, 12).unwrap();
+    let window = early.free_window();
+    assert_eq!(window, (start + 16, page - start - 16));
+    assert_eq!(early.free_range(), window.0..window.0 + window.1);
+
+    assert_eq!(early.seal_window(), Some(window));
+    assert_eq!(early.sealed_window(), Some(window));
+    assert_eq!(early.free_window(), (page, 0));
+    assert!(matches!(
+        early.alloc(Layout::new::<u8>()),
+        Err(AllocError::NoMemory)
+    ));
+    assert!(matches!(
+        early.alloc_pages(1, 12),
+        Err(AllocError::NoMemory)
+    ));
+    assert_eq!(early.seal_window(), None);
+
+    // 释放其他分配不会交还封存的窗口
+    early.dealloc(b, Layout::new::<u64>());
+    early.dealloc(a, Layout::new::<u64>());
+    assert_eq!(early.byte_alloc_count(), 1);
+    early.dealloc_pages(page, 2);
+    assert_eq!(early.free_window(), (page, 2 * PAGE_SIZE));
+    let pos = early.alloc_pages(2, 12).unwrap();
+    assert!(pos >= window.0 + window.1);
+    early.verify_invariants();
+
+    early.reset();
+    assert_eq!(early.sealed_window(), None);
+    assert_eq!(early.free_window(), (start, 8 * PAGE_SIZE));
+}
+
+#[test]
+fn test_merge_adjacent() {
+    type Early = EarlyAllocator<PAGE_SIZE>;
+    let [a, b, c, d] = Early::shard::<4>(BASE + 0x100, 16 * PAGE_SIZE).unwrap();
+    let ab = Early::merge_adjacent(a, b).unwrap();
+    assert_eq!(ab.start_addr(), BASE + 0x100);
+    assert_eq!(ab.end_addr(), c.start_addr());
+    assert_eq!(ab.available_pages(), 7);
+    // 顺序无关
+    let mut cd = Early::merge_adjacent(d, c).unwrap();
+    assert_eq!(cd.start_addr(), BASE + 8 * PAGE_SIZE);
+    assert_eq!(cd.end_addr(), BASE + 16 * PAGE_SIZE);
+    assert_eq!(cd.total_pages(), 8);
+    let pos = cd.alloc_pages(8, 12).unwrap();
+    assert_eq!(pos, BASE + 8 * PAGE_SIZE);
+    cd.reset();
+
+    let whole = Early::merge_adjacent(cd, ab).unwrap();
+    assert_eq!(whole.start_addr(), BASE + 0x100);
+    assert_eq!(whole.available_pages(), 15);
+    whole.verify_invariants();
+}
+
+#[test]
+fn test_merge_adjacent_rejected() {
+    type Early = EarlyAllocator<PAGE_SIZE>;
+    let [a, _, c, _] = Early::shard::<4>(BASE, 16 * PAGE_SIZE).unwrap();
+    assert!(matches!(
+        Early::merge_adjacent(a, c),
+        Err(AllocError::InvalidParam)
+    ));
+
+    let [mut a, b, ..] = Early::shard::<4>(BASE, 16 * PAGE_SIZE).unwrap();
+    a.alloc(Layout::new::<u64>()).unwrap();
+    assert!(matches!(
+        Early::merge_adjacent(a, b),
+        Err(AllocError::InvalidParam)
+    ));
+    let [a, mut b, ..] = Early::shard::<4>(BASE, 16 * PAGE_SIZE).unwrap();
+    b.alloc_pages(1, 12).unwrap();
+    assert!(matches!(
+        Early::merge_adjacent(a, b),
+        Err(AllocError::InvalidParam)
+    ));
+    let [a, ..] = Early::shard::<4>(BASE, 16 * PAGE_SIZE).unwrap();
+    assert!(matches!(
+        Early::merge_adjacent(a, Early::new()),
+        Err(AllocError::InvalidParam)
+    ));
+}
+
+#[test]
+fn test_verify_invariants() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 8 * PAGE_SIZE);
+    early.alloc_pages(1, 12).unwrap();
+    early.verify_invariants();
+    early.reset_pages();
+    early.verify_invariants();
+}
+
+#[test]
+#[cfg_attr(debug_assertions, should_panic(expected = "cursors out of order"))]
+fn test_verify_invariants_crossed() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 8 * PAGE_SIZE);
+    // 字节游标越过页游标
+    early.set_cursors(BASE + 5 * PAGE_SIZE, BASE + 4 * PAGE_SIZE);
+    early.verify_invariants();
+}
+
+#[test]
+#[cfg_attr(debug_assertions, should_panic(expected = "bytes count mismatches"))]
+fn test_verify_invariants_count() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 8 * PAGE_SIZE);
+    // 没有分配但字节区非空
+    early.set_cursors(BASE + 0x100, BASE + 8 * PAGE_SIZE);
+    early.verify_invariants();
+}
+
+#[test]
+fn test_shrink() {
+    let mut arena = Arena::new(2);
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(arena.start(), 2 * PAGE_SIZE);
+    let old = Layout::from_size_align(0x100, 8).unwrap();
+    let new = Layout::from_size_align(0x40, 8).unwrap();
+    let first = early.alloc(old).unwrap();
+    let second = early.alloc(old).unwrap();
+
+    // 栈顶块的尾部归还给字节区
+    assert_eq!(early.shrink(second, old, new), second);
+    assert_eq!(early.used_bytes(), 0x140);
+    let third = early.alloc(old).unwrap();
+    assert_eq!(third.as_ptr() as usize, second.as_ptr() as usize + 0x40);
+
+    // 中间的块无法回收
+    assert_eq!(early.shrink(first, old, new), first);
+    assert_eq!(early.used_bytes(), 0x240);
+    assert_eq!(early.shrink(third, old, Layout::new::<()>()), third);
+    assert_eq!(early.used_bytes(), 0x240);
+}
+
+#[test]
+fn test_alloc_with_size() {
+    let mut arena = Arena::new(4);
+    let base = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(base, 4 * PAGE_SIZE);
+    let cases = [(1, 1), (0x40, 0x40), (0x80, 8), (0x100, 0x100)];
+    for (size, align) in cases {
+        let b_pos = early.byte_cursor();
+        let layout = Layout::from_size_align(size, align).unwrap();
+        let (ptr, consumed) = early.alloc_with_size(layout).unwrap();
+        assert_eq!(consumed, early.byte_cursor() - b_pos);
+        // 对齐空隙在指针之前，尾部不向上取整
+        assert_eq!(ptr.as_ptr() as usize - b_pos, consumed - size);
+        assert_eq!(ptr.as_ptr() as usize + size, early.byte_cursor());
+    }
+
+    // 落在之前的对齐空隙里时游标不动
+    let b_pos = early.byte_cursor();
+    let small = Layout::from_size_align(4, 4).unwrap();
+    let (ptr, consumed) = early.alloc_with_size(small).unwrap();
+    assert!((ptr.as_ptr() as usize) < b_pos);
+    assert_eq!((consumed, early.byte_cursor()), (0, b_pos));
+    let zero = Layout::from_size_align(0, 8).unwrap();
+    assert_eq!(early.alloc_with_size(zero).unwrap().1, 0);
+}
+
+#[test]
+fn test_padding_reuse() {
+    let mut arena = Arena::new(4);
+    let base = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(base, 4 * PAGE_SIZE);
+    early.alloc(Layout::from_size_align(1, 1).unwrap()).unwrap();
+    let big = early
+        .alloc(Layout::from_size_align(64, 64).unwrap())
+        .unwrap();
+    assert_eq!(early.wasted_bytes(), 63);
+
+    // 小分配落在对齐留下的空隙里
+    let small = Layout::from_size_align(4, 4).unwrap();
+    let ptr = early.alloc(small).unwrap();
+    assert_eq!(ptr.as_ptr() as usize, base + 4);
+    assert_eq!(early.byte_cursor(), big.as_ptr() as usize + 64);
+    assert_eq!(early.wasted_bytes(), 59);
+    let ptr = early.alloc(small).unwrap();
+    assert_eq!(ptr.as_ptr() as usize, base + 8);
+    // 空隙放不下时照常推进游标
+    let ptr = early
+        .alloc(Layout::from_size_align(64, 8).unwrap())
+        .unwrap();
+    assert_eq!(ptr.as_ptr() as usize, base + 0x80);
+
+    // 释放对齐块后空隙仍然有效
+    early.dealloc(ptr, Layout::from_size_align(64, 8).unwrap());
+    early.dealloc(big, Layout::from_size_align(64, 64).unwrap());
+    assert_eq!(early.byte_cursor(), base + 0x40);
+    let last = early
+        .alloc(Layout::from_size_align(0x34, 4).unwrap())
+        .unwrap();
+    assert_eq!(last.as_ptr() as usize, base + 12);
+    // 空隙用尽后照常从游标分配
+    assert_eq!(early.alloc(small).unwrap().as_ptr() as usize, base + 0x40);
+}
+
+#[test]
+fn test_alloc_pages_below() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 8 * PAGE_SIZE);
+    let end = BASE + 8 * PAGE_SIZE;
+    // 上限低于区域顶端时无法分配，且不改变状态
+    assert!(matches!(
+        early.alloc_pages_below(1, 12, end - 1),
+        Err(AllocError::NoMemory)
+    ));
+    assert_eq!(early.used_pages(), 0);
+    let pos = early.alloc_pages_below(2, 12, end).unwrap();
+    assert_eq!(pos, end - 2 * PAGE_SIZE);
+
+    // 对齐后的块整体低于上限即可
+    let pos = early.alloc_pages_below(1, 14, end - 2 * PAGE_SIZE).unwrap();
+    assert_eq!(pos, BASE + 4 * PAGE_SIZE);
+    assert!(matches!(
+        early.alloc_pages_below(1, 12, BASE + 3 * PAGE_SIZE),
+        Err(AllocError::NoMemory)
+    ));
+    let pos = early.alloc_pages_below(1, 12, usize::MAX).unwrap();
+    assert_eq!(pos, BASE + 3 * PAGE_SIZE);
+    assert!(matches!(
+        early.alloc_pages_below(0, 12, usize::MAX),
+        Err(AllocError::InvalidParam)
+    ));
+}
+
+#[test]
+fn test_write_metrics() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 8 * PAGE_SIZE);
+    let layout = Layout::from_size_align(0x100, 8).unwrap();
+    let ptr = early.alloc(layout).unwrap();
+    early.alloc(layout).unwrap();
+    early.dealloc(ptr, layout);
+    early.alloc_pages(2, 12).unwrap();
+
+    let mut out = String::new();
+    early.write_metrics(&mut out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "early_used_bytes 512",
+            "early_available_bytes 24064",
+            "early_used_pages 2",
+            "early_available_pages 5",
+            "early_byte_allocs 1",
+            "early_page_allocs 2",
+            "early_peak_used_bytes 512",
+        ]
+    );
+}
+
+#[test]
+fn test_dump_bar() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    let mut out = String::new();
+    early.dump_bar(&mut out, 4).unwrap();
+    assert_eq!(out, "[....]");
+
+    early.init(BASE, 8 * PAGE_SIZE);
+    // 字节区占 1/4，页区占 1/2
+    early
+        .alloc(Layout::from_size_align(2 * PAGE_SIZE, 8).unwrap())
+        .unwrap();
+    early.alloc_pages(4, 12).unwrap();
+    let mut out = String::new();
+    early.dump_bar(&mut out, 16).unwrap();
+    assert_eq!(out, "[####....********]");
+    let count = |c| out.chars().filter(|&x| x == c).count();
+    assert_eq!((count('#'), count('.'), count('*')), (4, 4, 8));
+
+    // 不足一列的部分向下取整
+    early
+        .alloc(Layout::from_size_align(0x10, 8).unwrap())
+        .unwrap();
+    let mut out = String::new();
+    early.dump_bar(&mut out, 8).unwrap();
+    assert_eq!(out, "[##..****]");
+}
+
+#[test]
+fn test_fully_exhausted() {
+    fn never() -> bool {
+        unreachable!("retried a drained arena")
+    }
+
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    assert!(!early.fully_exhausted());
+    early.init(BASE, 4 * PAGE_SIZE);
+    early.set_retry_on_oom(Some(never));
+    early.alloc_pages(2, 12).unwrap();
+    let layout = Layout::from_size_align(PAGE_SIZE, 8).unwrap();
+    early.alloc(layout).unwrap();
+    assert!(!early.fully_exhausted());
+
+    // 从两端填满
+    early.alloc_pages(1, 12).unwrap();
+    assert!(early.fully_exhausted());
+    assert!(matches!(
+        early.alloc(Layout::new::<u8>()),
+        Err(AllocError::NoMemory)
+    ));
+    assert!(matches!(
+        early.alloc_pages(1, 12),
+        Err(AllocError::NoMemory)
+    ));
+
+    early.reset_pages();
+    assert!(!early.fully_exhausted());
+}
+
+#[test]
+fn test_init_aligned() {
+    const HUGE: usize = 0x20_0000;
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    // 区域顶端不在 2M 边界上
+    let size = 3 * HUGE + 5 * PAGE_SIZE;
+    early.init_aligned(BASE + PAGE_SIZE, size, HUGE).unwrap();
+    assert_eq!(early.end_addr(), BASE + 3 * HUGE);
+    assert_eq!(early.byte_cursor(), BASE + PAGE_SIZE);
+
+    // 第一个 2M 分配天然对齐，没有空隙
+    let (pos, slack) = early.alloc_aligned_pages(HUGE / PAGE_SIZE, 21).unwrap();
+    assert_eq!(pos, BASE + 2 * HUGE);
+    assert_eq!(slack, 0);
+    assert_eq!(early.alloc_pages(1, 12).unwrap() % HUGE, HUGE - PAGE_SIZE);
+
+    for align in [0, 3 * PAGE_SIZE, PAGE_SIZE / 2] {
+        assert!(matches!(
+            early.init_aligned(BASE, size, align),
+            Err(AllocError::InvalidParam)
+        ));
+    }
+    // 对齐后放不下一页
+    assert!(matches!(
+        early.init_aligned(BASE + PAGE_SIZE, HUGE - 2 * PAGE_SIZE, HUGE),
+        Err(AllocError::InvalidParam)
+    ));
+    assert_eq!(early.end_addr(), BASE + 3 * HUGE);
+}
+
+#[test]
+fn test_freeze() {
+    let mut arena = Arena::new(8);
+    let base = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(base, 8 * PAGE_SIZE);
+    let layout = Layout::from_size_align(0x100, 8).unwrap();
+    let ptr = early.alloc(layout).unwrap();
+    let page = early.alloc_pages(1, 12).unwrap();
+
+    early.freeze();
+    assert!(early.is_frozen());
+    // 释放被忽略，游标不变
+    early.dealloc(ptr, layout);
+    early.dealloc_pages(page, 1);
+    assert_eq!(early.byte_cursor(), base + 0x100);
+    assert_eq!(early.page_cursor(), page);
+    assert_eq!(early.shrink(ptr, layout, Layout::new::<u64>()), ptr);
+    assert_eq!(early.used_bytes(), 0x100);
+    // 分配全部失败
+    assert!(matches!(early.alloc(layout), Err(AllocError::NoMemory)));
+    assert!(matches!(
+        early.alloc_pages(1, 12),
+        Err(AllocError::NoMemory)
+    ));
+    assert!(matches!(early.reserve_front(8), Err(AllocError::NoMemory)));
+    assert!(matches!(
+        early.alloc_page_fragment(64, 8),
+        Err(AllocError::NoMemory)
+    ));
+    assert!(matches!(
+        early.realloc(ptr, layout, Layout::from_size_align(0x200, 8).unwrap()),
+        Err(AllocError::NoMemory)
+    ));
+    assert_eq!(early.used_bytes(), 0x100);
+    assert_eq!(early.used_pages(), 1);
+
+    early.unfreeze();
+    early.dealloc(ptr, layout);
+    early.dealloc_pages(page, 1);
+    assert_eq!(early.used_bytes(), 0);
+    assert_eq!(early.used_pages(), 0);
+}
+
+#[test]
+fn test_alloc_counts() {
+    let mut arena = Arena::new(8);
+    let base = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(base, 8 * PAGE_SIZE);
+    let layout = Layout::from_size_align(0x40, 8).unwrap();
+    let a = early.alloc(layout).unwrap();
+    let b = early.alloc(layout).unwrap();
+    early.alloc(Layout::from_size_align(0, 8).unwrap()).unwrap();
+    let pages = early.alloc_pages(2, 12).unwrap();
+    let page = early.alloc_pages(1, 12).unwrap();
+    assert_eq!(early.byte_alloc_count(), 2);
+    assert_eq!(early.page_alloc_count(), 3);
+
+    // 未释放到零之前游标不回收，计数仍反映存活分配
+    early.dealloc(a, layout);
+    early.dealloc_pages(pages, 2);
+    assert_eq!(early.byte_alloc_count(), 1);
+    assert_eq!(early.page_alloc_count(), 1);
+    assert_eq!(early.byte_cursor(), base + 0x80);
+
+    early.dealloc(b, layout);
+    early.dealloc_pages(page, 1);
+    assert_eq!(early.byte_alloc_count(), 0);
+    assert_eq!(early.page_alloc_count(), 0);
+    assert!(early.is_empty());
+}
+
+#[test]
+fn test_alloc_pages_within_start() {
+    // 起始地址只按页对齐，粗粒度对齐可能越过起点
+    let start = BASE + PAGE_SIZE;
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(start, 16 * PAGE_SIZE);
+    for align_pow2 in 0..=16 {
+        for num_pages in 1..=5 {
+            let mut allocated = 0;
+            while let Ok(pos) = early.alloc_pages(num_pages, align_pow2) {
+                assert!(pos >= start);
+                assert_eq!(pos % PAGE_SIZE, 0);
+                assert_eq!(pos % (1 << align_pow2), 0);
+                allocated += num_pages;
+            }
+            assert!(allocated <= 16);
+            assert!(early.page_cursor() >= start);
+            early.reset_pages();
+        }
+    }
+}
+
+#[test]
+fn test_reinit() {
+    let mut first = Arena::new(4);
+    let mut second = Arena::new(8);
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(first.start(), 4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(0x100, 8).unwrap();
+    let ptr = early.alloc(layout).unwrap();
+    let page = early.alloc_pages(1, 12).unwrap();
+    early.dealloc(ptr, layout);
+    early.dealloc_pages(page, 1);
+
+    // 换到第二块区域，旧区域的状态全部清除
+    let base = second.start();
+    early.reinit(base, 8 * PAGE_SIZE);
+    assert_eq!(early.byte_cursor(), base);
+    assert_eq!(early.end_addr(), base + 8 * PAGE_SIZE);
+    assert_eq!(early.total_bytes(), 8 * PAGE_SIZE);
+    let ptr = early.alloc(layout).unwrap();
+    assert_eq!(ptr.as_ptr() as usize, base);
+    assert_eq!(early.alloc_pages(2, 12).unwrap(), base + 6 * PAGE_SIZE);
+    assert!(!early.contains(first.start()));
+}
+
+#[test]
+#[cfg_attr(
+    debug_assertions,
+    should_panic(expected = "reinit over live allocations")
+)]
+fn test_reinit_live() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 4 * PAGE_SIZE);
+    early.alloc_pages(1, 12).unwrap();
+    early.reinit(BASE + 0x10_0000, 4 * PAGE_SIZE);
+}
+
+#[test]
+fn test_total_pages_constant() {
+    let mut arena = Arena::new(8);
+    let base = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    assert_eq!(early.currently_allocatable_pages(), 0);
+    early.init(base, 8 * PAGE_SIZE);
+    assert_eq!(early.total_pages(), 8);
+    assert_eq!(early.currently_allocatable_pages(), 8);
+
+    let layout = Layout::from_size_align(PAGE_SIZE + 0x10, 8).unwrap();
+    let ptr = early.alloc(layout).unwrap();
+    early.alloc_pages(2, 12).unwrap();
+    // 字节区增长只影响当前可分配的页数
+    assert_eq!(early.total_pages(), 8);
+    assert_eq!(early.currently_allocatable_pages(), 6);
+    assert_eq!(early.available_pages(), 4);
+    assert_eq!(early.page_used_permille(), 333);
+
+    early.dealloc(ptr, layout);
+    assert_eq!(early.total_pages(), 8);
+    assert_eq!(early.currently_allocatable_pages(), 8);
+}
+
+#[cfg(feature = "self-test")]
+#[test]
+fn test_self_test() {
+    fn oom(_: Layout) {
+        panic!("OOM hook called by the self test");
+    }
+
+    let mut arena = Arena::new(16);
+    // 起始地址不按页对齐，字节区和页区的边界不整齐
+    let start = arena.start() + 0x18;
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    assert!(early.self_test().is_err());
+    early.init(start, 16 * PAGE_SIZE - 0x18);
+    early.set_oom_hook(Some(oom));
+    early.self_test().unwrap();
+    assert!(early.is_empty());
+    assert_eq!(early.byte_cursor(), start);
+    assert_eq!(early.peak_used_bytes(), 0);
+
+    // 限制单次分配大小时按多块填满
+    early.set_max_alloc(3 * PAGE_SIZE);
+    early.self_test().unwrap();
+
+    let page = early.alloc_pages(1, 12).unwrap();
+    assert_eq!(early.self_test(), Err("live allocations before the test"));
+    early.dealloc_pages(page, 1);
+    early.freeze();
+    assert_eq!(early.self_test(), Err("allocator frozen"));
+
+    // 释放不回收字节区时也能通过
+    let mut early = EarlyAllocator::<PAGE_SIZE, 16, 1, false>::new();
+    early.init(start, 16 * PAGE_SIZE - 0x18);
+    early.self_test().unwrap();
+    assert_eq!(early.byte_cursor(), start);
+}
+
+#[test]
+fn test_byte_reserve() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 8 * PAGE_SIZE);
+    let layout = Layout::from_size_align(0x800, 8).unwrap();
+    early.alloc(layout).unwrap();
+    early.set_byte_reserve(2 * PAGE_SIZE);
+    // 页区最多到 b_pos + 保留空间向上取整的页边界
+    assert_eq!(early.available_pages(), 5);
+    assert!(matches!(
+        early.alloc_pages(6, 12),
+        Err(AllocError::NoMemory)
+    ));
+    early.alloc_pages(5, 12).unwrap();
+    assert!(matches!(
+        early.alloc_pages(1, 12),
+        Err(AllocError::NoMemory)
+    ));
+    assert!(matches!(early.reserve_back(1), Err(AllocError::NoMemory)));
+    assert_eq!(early.available_pages(), 0);
+
+    // 保留的空间仍可用于字节分配
+    let big = Layout::from_size_align(2 * PAGE_SIZE + 0x800, 8).unwrap();
+    early.alloc(big).unwrap();
+    assert_eq!(early.available_bytes(), 0);
+
+    early.reset();
+    early.set_byte_reserve(0);
+    assert_eq!(early.available_pages(), 8);
+    early.alloc_pages(8, 12).unwrap();
+}
+
+#[cfg(feature = "alloc-stats")]
+#[test]
+fn test_alloc_align_histogram() {
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(BASE, 8 * PAGE_SIZE);
+    for align in [1, 8, 8, 16, 64, 128, 4096] {
+        early
+            .alloc(Layout::from_size_align(24, align).unwrap())
+            .unwrap();
+    }
+    early.alloc(Layout::new::<u32>()).unwrap();
+    // 失败的分配不计数
+    assert!(early
+        .alloc(Layout::from_size_align(16 * PAGE_SIZE, 2).unwrap())
+        .is_err());
+    assert_eq!(early.alloc_align_histogram(), [1, 0, 1, 2, 1, 0, 1, 2]);
+
+    early.reset();
+    early.alloc(Layout::new::<u16>()).unwrap();
+    assert_eq!(early.alloc_align_histogram(), [1, 1, 1, 2, 1, 0, 1, 2]);
+}
+
+#[test]
+fn test_single_ended() {
+    let mut arena = Arena::new(8);
+    let base = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(base, 8 * PAGE_SIZE);
+    early.set_single_ended(true).unwrap();
+    assert!(early.is_single_ended());
+
+    // 字节和页推进同一个游标，页区游标不动
+    let layout = Layout::from_size_align(0x100, 8).unwrap();
+    let ptr = early.alloc(layout).unwrap();
+    assert_eq!(ptr.as_ptr() as usize, base);
+    let page = early.alloc_pages(2, 12).unwrap();
+    assert_eq!(page, base + PAGE_SIZE);
+    assert_eq!(early.byte_cursor(), base + 3 * PAGE_SIZE);
+    // 页对齐留下的空隙照常复用
+    let ptr2 = early.alloc(layout).unwrap();
+    assert_eq!(ptr2.as_ptr() as usize, base + 0x100);
+    let (page2, slack) = early.alloc_aligned_pages(1, 12).unwrap();
+    assert_eq!((page2, slack), (base + 3 * PAGE_SIZE, 0));
+    assert_eq!(early.page_cursor(), early.end_addr());
+    assert_eq!(early.used_pages(), 0);
+    assert_eq!(early.byte_alloc_count(), 4);
+    assert!(early
+        .alloc_pages_below(1, 12, base + 4 * PAGE_SIZE)
+        .is_err());
+
+    // 栈顶的页按字节回收
+    early.dealloc_pages(page2, 1);
+    assert_eq!(early.byte_cursor(), base + 3 * PAGE_SIZE);
+    assert!(matches!(
+        early.try_dealloc_pages(base + 6 * PAGE_SIZE, 1),
+        Err(AllocError::InvalidParam)
+    ));
+    assert!(matches!(
+        early.set_single_ended(false),
+        Err(AllocError::InvalidParam)
+    ));
+    early.dealloc(ptr2, layout);
+    early.try_dealloc_pages(page, 2).unwrap();
+    early.dealloc(ptr, layout);
+    assert!(early.is_empty());
+    assert_eq!(early.byte_cursor(), base);
+
+    early.set_single_ended(false).unwrap();
+    assert_eq!(early.alloc_pages(1, 12).unwrap(), base + 7 * PAGE_SIZE);
+}
+
+#[cfg(feature = "debug-warn")]
+#[test]
+fn test_warn_non_lifo() {
+    use core::cell::RefCell;
+    use std::string::{String, ToString};
+    use std::vec::Vec;
+
+    std::thread_local! {
+        static WARNINGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+    fn warn(args: core::fmt::Arguments) {
+        WARNINGS.with(|w| w.borrow_mut().push(args.to_string()));
+    }
+
+    let mut arena = Arena::new(4);
+    let base = arena.start();
+    let mut early = EarlyAllocator::<PAGE_SIZE>::new();
+    early.init(base, 4 * PAGE_SIZE);
+    early.set_warn_hook(Some(warn));
+    let layout = Layout::from_size_align(0x40, 8).unwrap();
+    let a = early.alloc(layout).unwrap();
+    let b = early.alloc(layout).unwrap();
+    let c = early.alloc(layout).unwrap();
+    // 栈顶的释放不提醒
+    early.dealloc(c, layout);
+    assert!(WARNINGS.with(|w| w.borrow().is_empty()));
+
+    // 中间的释放只提醒一次
+    let b2 = early.alloc(layout).unwrap();
+    early.dealloc(b, layout);
+    early.dealloc(a, layout);
+    let warnings = WARNINGS.with(|w| w.borrow().clone());
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("non-LIFO free"));
+    assert!(warnings[0].contains(&std::format!("{:#x}", b.as_ptr() as usize)));
+
+    // 最后一个存活分配的释放回收整个字节区，不提醒
+    early.dealloc(b2, layout);
+    assert_eq!(WARNINGS.with(|w| w.borrow().len()), 1);
+    assert_eq!(early.byte_cursor(), base);
+}
+
+#[test]
+fn test_dyn_facade() {
+    use crate::object::EarlyAllocatorDyn;
+
+    const BIG_PAGE: usize = 4 * PAGE_SIZE;
+    let mut arena = Arena::new(20);
+    let base = arena.start();
+    let mut small = EarlyAllocator::<PAGE_SIZE>::new();
+    small.init(base, 8 * PAGE_SIZE);
+    let mut big = EarlyAllocator::<BIG_PAGE>::new();
+    big.init(
+        (base + 8 * PAGE_SIZE).next_multiple_of(BIG_PAGE),
+        2 * BIG_PAGE,
+    );
+
+    let allocators: [&mut dyn EarlyAllocatorDyn; 2] = [&mut small, &mut big];
+    let layout = Layout::from_size_align(0x40, 8).unwrap();
+    for early in allocators {
+        let page_size = early.page_size();
+        let start = early.stats().total_bytes;
+        let ptr = early.alloc(layout).unwrap();
+        let pos = early
+            .alloc_pages(1, page_size.trailing_zeros() as usize)
+            .unwrap();
+        assert_eq!(pos % page_size, 0);
+        let stats = early.stats();
+        assert_eq!((stats.byte_allocs, stats.page_allocs), (1, 1));
+        assert_eq!(stats.total_bytes, start);
+        assert_eq!(stats.used_pages, 1);
+
+        early.dealloc_pages(pos, 1);
+        early.dealloc(ptr, layout);
+        let stats = early.stats();
+        assert_eq!((stats.byte_allocs, stats.page_allocs), (0, 0));
+        assert_eq!(stats.available_bytes, stats.total_bytes);
+    }
+    assert_eq!(small.page_size(), PAGE_SIZE);
+    assert_eq!(EarlyAllocatorDyn::page_size(&big), BIG_PAGE);
+    assert_eq!(big.total_pages(), 2);
+}
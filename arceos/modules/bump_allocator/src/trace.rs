@@ -0,0 +1,79 @@
+//! Tracking of recent allocations, enabled by the `alloc-trace` feature.
+
+use core::fmt;
+
+/// A traced bytes allocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    /// Requested size.
+    pub size: usize,
+    /// Requested alignment.
+    pub align: usize,
+    /// Site id passed to [`alloc_tagged`](crate::EarlyAllocator::alloc_tagged),
+    /// or 0 for plain allocations.
+    pub site: u16,
+}
+
+/// A fixed-size ring of the last `N` allocations.
+///
+/// The entries are stored inline, so no allocation is needed to record them.
+#[derive(Clone)]
+pub struct AllocTrace<const N: usize> {
+    ring: [TraceEntry; N],
+    next: usize,
+    len: usize,
+}
+
+impl<const N: usize> AllocTrace<N> {
+    pub(crate) const fn new() -> Self {
+        const EMPTY: TraceEntry = TraceEntry {
+            size: 0,
+            align: 0,
+            site: 0,
+        };
+        Self {
+            ring: [EMPTY; N],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    pub(crate) fn record(&mut self, entry: TraceEntry) {
+        if N == 0 {
+            return;
+        }
+        self.ring[self.next] = entry;
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+    }
+
+    /// Returns the number of recorded entries, at most `N`.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether nothing has been recorded yet.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterates over the recorded entries, from the oldest to the newest.
+    pub fn iter(&self) -> impl Iterator<Item = &TraceEntry> {
+        // 环未满时最旧的记录在下标 0
+        let first = if self.len < N { 0 } else { self.next };
+        (0..self.len).map(move |i| &self.ring[(first + i) % N])
+    }
+}
+
+impl<const N: usize> fmt::Display for AllocTrace<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, e) in self.iter().enumerate() {
+            writeln!(
+                f,
+                "[{}] site {}: size {:#x}, align {:#x}",
+                i, e.site, e.size, e.align
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+//! Per-task override of the address space that page faults are resolved in.
+//!
+//! A task operating on a borrowed address space, e.g. a kernel thread
+//! servicing another process, installs it with [`set_active_aspace`], so that
+//! its faults are resolved there instead of in its own address space. Only
+//! the self-tests install overrides yet.
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+
+use axmm::AddrSpace;
+use axsync::Mutex;
+use kspin::SpinNoIrq;
+
+static OVERRIDES: SpinNoIrq<AspaceOverrides<Arc<Mutex<AddrSpace>>>> =
+    SpinNoIrq::new(AspaceOverrides::new());
+
+/// The overrides of all tasks, keyed by task ID.
+pub struct AspaceOverrides<T>(BTreeMap<u64, T>);
+
+impl<T: Clone> AspaceOverrides<T> {
+    pub const fn new() -> Self {
+        Self(BTreeMap::new())
+    }
+
+    /// Installs the override of a task, returning the previous one.
+    #[cfg(feature = "selftest")]
+    pub fn set(&mut self, task_id: u64, aspace: T) -> Option<T> {
+        self.0.insert(task_id, aspace)
+    }
+
+    /// Removes the override of a task, returning it.
+    #[cfg(feature = "selftest")]
+    pub fn clear(&mut self, task_id: u64) -> Option<T> {
+        self.0.remove(&task_id)
+    }
+
+    /// Returns the override of a task.
+    pub fn get(&self, task_id: u64) -> Option<T> {
+        self.0.get(&task_id).cloned()
+    }
+}
+
+fn current_id() -> u64 {
+    axtask::current().id().as_u64()
+}
+
+/// Resolves the page faults of the current task in `aspace` until
+/// [`clear_active_aspace`] is called. Returns the previous override.
+#[cfg(feature = "selftest")]
+pub fn set_active_aspace(aspace: Arc<Mutex<AddrSpace>>) -> Option<Arc<Mutex<AddrSpace>>> {
+    OVERRIDES.lock().set(current_id(), aspace)
+}
+
+/// Removes the override of the current task, returning it. Its page faults
+/// are resolved in its own address space again.
+#[cfg(feature = "selftest")]
+pub fn clear_active_aspace() -> Option<Arc<Mutex<AddrSpace>>> {
+    OVERRIDES.lock().clear(current_id())
+}
+
+/// Returns the override of the current task, if any.
+pub fn active_aspace() -> Option<Arc<Mutex<AddrSpace>>> {
+    OVERRIDES.lock().get(current_id())
+}
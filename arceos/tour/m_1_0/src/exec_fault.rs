@@ -0,0 +1,76 @@
+//! Diagnostics of instruction fetch faults.
+
+use core::fmt;
+
+use axhal::paging::MappingFlags;
+use axmm::PageFaultError;
+
+use crate::page_fault::FaultPc;
+
+/// A page fault caused by fetching an instruction.
+///
+/// Such faults usually mean a corrupted return address or a jump into data,
+/// so they are reported apart from the data read and write faults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecFault {
+    /// The faulting address.
+    pub vaddr: usize,
+    /// The instruction pointer of the trap, if the architecture records it.
+    pub pc: Option<usize>,
+    /// The permissions of the page, if it is mapped but not executable.
+    pub present: Option<MappingFlags>,
+}
+
+impl ExecFault {
+    /// Decodes an instruction fetch fault from the access flags of a page
+    /// fault, or returns `None` for a data access.
+    pub fn decode(
+        vaddr: usize,
+        flags: MappingFlags,
+        pc: Option<usize>,
+        err: PageFaultError,
+    ) -> Option<Self> {
+        if !flags.contains(MappingFlags::EXECUTE) {
+            return None;
+        }
+        let present = match err {
+            PageFaultError::Protection { present, .. } => Some(present),
+            _ => None,
+        };
+        Some(Self { vaddr, pc, present })
+    }
+
+    /// Whether the fault is on the instruction pointer itself, i.e., the
+    /// control flow jumped to the faulting address, rather than an
+    /// instruction straddling the end of an executable page. It is `false`
+    /// if the instruction pointer is unknown.
+    pub fn is_jump(&self) -> bool {
+        self.pc == Some(self.vaddr)
+    }
+
+    /// Whether the page is writable but not executable, i.e., an attempt to
+    /// execute data.
+    pub fn is_wx_violation(&self) -> bool {
+        self.present
+            .is_some_and(|present| present.contains(MappingFlags::WRITE))
+    }
+}
+
+impl fmt::Display for ExecFault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "instruction fetch fault at {:#x}, pc={}",
+            self.vaddr,
+            FaultPc(self.pc)
+        )?;
+        if self.is_jump() {
+            write!(f, " (jumped to the fault address)")?;
+        }
+        match self.present {
+            Some(present) if self.is_wx_violation() => write!(f, ", W^X violation: {:?}", present),
+            Some(present) => write!(f, ", not executable: {:?}", present),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Rate limiting of page-fault log lines.
+
+use core::fmt;
+
+use kspin::SpinNoIrq;
+
+/// Number of recent fault sites remembered.
+pub const RING_SIZE: usize = 8;
+
+/// A repeated fault site is logged once per this many occurrences.
+const LOG_EVERY: usize = 1000;
+
+static FAULT_LOG: SpinNoIrq<FaultLogLimiter> = SpinNoIrq::new(FaultLogLimiter::new(LOG_EVERY));
+
+#[derive(Clone, Copy)]
+struct Site {
+    page: usize,
+    is_user: bool,
+    suppressed: usize,
+}
+
+/// Remembers the recent fault sites in a fixed-size ring, so that no
+/// allocation is needed in the trap handler.
+pub struct FaultLogLimiter {
+    ring: [Option<Site>; RING_SIZE],
+    next: usize,
+    every: usize,
+}
+
+impl FaultLogLimiter {
+    pub const fn new(every: usize) -> Self {
+        Self {
+            ring: [None; RING_SIZE],
+            next: 0,
+            every,
+        }
+    }
+
+    /// Records a fault on `page` and decides whether to log it.
+    ///
+    /// Returns `Some(n)` if the fault should be logged, where `n` is the
+    /// number of identical faults suppressed since the last log line, or
+    /// `None` if it should be suppressed.
+    pub fn record(&mut self, page: usize, is_user: bool) -> Option<usize> {
+        let every = self.every;
+        let found = self
+            .ring
+            .iter_mut()
+            .flatten()
+            .find(|s| s.page == page && s.is_user == is_user);
+        if let Some(site) = found {
+            if site.suppressed + 1 >= every {
+                let n = site.suppressed;
+                site.suppressed = 0;
+                Some(n)
+            } else {
+                site.suppressed += 1;
+                None
+            }
+        } else {
+            // 新的出错位置，替换最旧的记录
+            self.ring[self.next] = Some(Site {
+                page,
+                is_user,
+                suppressed: 0,
+            });
+            self.next = (self.next + 1) % RING_SIZE;
+            Some(0)
+        }
+    }
+}
+
+/// Records a fault with the global limiter, see [`FaultLogLimiter::record`].
+pub fn record_fault(page: usize, is_user: bool) -> Option<usize> {
+    FAULT_LOG.lock().record(page, is_user)
+}
+
+/// Formats the `(xN suppressed)` suffix of a log line, or nothing if no
+/// fault has been suppressed.
+pub struct Suppressed(pub usize);
+
+impl fmt::Display for Suppressed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0 > 0 {
+            write!(f, " (x{} suppressed)", self.0)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Formats a fault address with the name of the region containing it, e.g.
+/// `0x1000 in user-heap`.
+pub struct FaultSite {
+    pub vaddr: usize,
+    pub region: &'static str,
+}
+
+impl fmt::Display for FaultSite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#x} in {}", self.vaddr, self.region)
+    }
+}
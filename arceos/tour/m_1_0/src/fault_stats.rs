@@ -0,0 +1,117 @@
+//! Cumulative page-fault statistics.
+
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+static STATS: PageFaultStats = PageFaultStats::new();
+
+/// Page-fault counters, updated from the trap handler.
+///
+/// Only relaxed atomics are used, so it is safe to update them in trap
+/// context without taking any lock.
+pub struct PageFaultStats {
+    total: AtomicUsize,
+    user: AtomicUsize,
+    kernel: AtomicUsize,
+    handled: AtomicUsize,
+    failed: AtomicUsize,
+    populated: AtomicUsize,
+    populate_ticks: AtomicU64,
+}
+
+/// A snapshot of [`PageFaultStats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PageFaultSnapshot {
+    /// Total number of page faults.
+    pub total: usize,
+    /// Number of faults from user mode.
+    pub user: usize,
+    /// Number of faults from kernel mode.
+    pub kernel: usize,
+    /// Number of faults handled successfully.
+    pub handled: usize,
+    /// Number of faults that could not be handled.
+    pub failed: usize,
+    /// Number of faults that populated a page, see
+    /// [`PageFaultStats::record_populate`].
+    pub populated: usize,
+    /// Total ticks spent populating the pages.
+    pub populate_ticks: u64,
+}
+
+impl PageFaultSnapshot {
+    /// Returns the average ticks to populate a page, i.e., to allocate, zero
+    /// and map it, or `None` if no page has been populated.
+    pub fn avg_populate_ticks(&self) -> Option<u64> {
+        self.populate_ticks.checked_div(self.populated as u64)
+    }
+}
+
+impl PageFaultStats {
+    pub const fn new() -> Self {
+        Self {
+            total: AtomicUsize::new(0),
+            user: AtomicUsize::new(0),
+            kernel: AtomicUsize::new(0),
+            handled: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
+            populated: AtomicUsize::new(0),
+            populate_ticks: AtomicU64::new(0),
+        }
+    }
+
+    /// Records a fault and whether it has been handled.
+    pub fn record(&self, is_user: bool, handled: bool) {
+        self.total.fetch_add(1, Ordering::Relaxed);
+        if is_user {
+            self.user.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.kernel.fetch_add(1, Ordering::Relaxed);
+        }
+        if handled {
+            self.handled.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.failed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Records a fault that populated a page, from the ticks sampled before
+    /// and after the population.
+    pub fn record_populate(&self, start: u64, end: u64) {
+        self.populated.fetch_add(1, Ordering::Relaxed);
+        // 时钟不应倒退，防止溢出
+        self.populate_ticks
+            .fetch_add(end.saturating_sub(start), Ordering::Relaxed);
+    }
+
+    /// Returns the current values of the counters.
+    ///
+    /// The counters are read one by one, so they may be slightly out of
+    /// sync with each other if faults happen meanwhile.
+    pub fn snapshot(&self) -> PageFaultSnapshot {
+        PageFaultSnapshot {
+            total: self.total.load(Ordering::Relaxed),
+            user: self.user.load(Ordering::Relaxed),
+            kernel: self.kernel.load(Ordering::Relaxed),
+            handled: self.handled.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            populated: self.populated.load(Ordering::Relaxed),
+            populate_ticks: self.populate_ticks.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Records a fault in the global statistics.
+pub fn record_fault_stats(is_user: bool, handled: bool) {
+    STATS.record(is_user, handled);
+}
+
+/// Records the latency of a fault populating a page in the global
+/// statistics.
+pub fn record_populate_latency(start: u64, end: u64) {
+    STATS.record_populate(start, end);
+}
+
+/// Returns a snapshot of the global page-fault statistics.
+pub fn page_fault_stats() -> PageFaultSnapshot {
+    STATS.snapshot()
+}
@@ -0,0 +1,73 @@
+//! Detection of tasks stuck re-taking the same page fault.
+
+use axhal::paging::MappingFlags;
+
+/// Number of identical consecutive faults after which a task is wedged.
+pub const FAULT_STORM_THRESHOLD: usize = 64;
+
+/// Number of identical consecutive faults failing with a transient error,
+/// after which the fault is not retried anymore.
+pub const FAULT_RETRY_LIMIT: usize = 256;
+
+/// Tracks the last page fault of a task.
+///
+/// A fault that is resolved but taken again right away at the same address
+/// with the same flags makes no progress. After `threshold` such faults in a
+/// row, the task is declared wedged.
+///
+/// A fault that fails and is retried at the same address with the same
+/// flags makes no progress either. After [`FAULT_RETRY_LIMIT`] such faults
+/// in a row, the retries are exhausted.
+pub struct FaultStorm {
+    last: Option<(usize, MappingFlags)>,
+    repeats: usize,
+    retries: usize,
+    threshold: usize,
+}
+
+impl FaultStorm {
+    /// Creates a tracker that trips after `threshold` identical faults, or
+    /// never if it is 0.
+    pub const fn new(threshold: usize) -> Self {
+        Self {
+            last: None,
+            repeats: 0,
+            retries: 0,
+            threshold,
+        }
+    }
+
+    /// Changes the threshold, see [`new`](Self::new).
+    #[cfg(feature = "selftest")]
+    pub fn set_threshold(&mut self, threshold: usize) {
+        self.threshold = threshold;
+    }
+
+    /// Records a fault at `vaddr` and whether it has been handled.
+    ///
+    /// Returns `true` if the task is wedged, i.e., this is the `threshold`-th
+    /// handled fault in a row at the same address with the same flags.
+    pub fn record(&mut self, vaddr: usize, flags: MappingFlags, handled: bool) -> bool {
+        if self.last != Some((vaddr, flags)) {
+            self.last = Some((vaddr, flags));
+            self.repeats = 0;
+            self.retries = 0;
+        }
+        if !handled {
+            // 失败的缺页由调用方另行处理，这里只计数重试
+            self.repeats = 0;
+            self.retries += 1;
+            return false;
+        }
+        self.retries = 0;
+        self.repeats += 1;
+        self.threshold != 0 && self.repeats >= self.threshold
+    }
+
+    /// Whether the last fault recorded is the [`FAULT_RETRY_LIMIT`]-th one in
+    /// a row that failed at the same address with the same flags, so that
+    /// retrying it again would likely never succeed.
+    pub const fn retries_exhausted(&self) -> bool {
+        self.retries >= FAULT_RETRY_LIMIT
+    }
+}
@@ -0,0 +1,162 @@
+//! A trace of the last page faults, dumped before an unhandled fault panics.
+
+use core::fmt;
+use core::sync::atomic::{fence, AtomicUsize, Ordering};
+
+use axhal::paging::MappingFlags;
+
+/// Number of faults kept in the trace.
+const TRACE_LEN: usize = 16;
+
+static TRACE: FaultTrace<TRACE_LEN> = FaultTrace::new();
+
+/// How the handler finished with a traced fault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceOutcome {
+    /// The fault has been resolved.
+    Handled,
+    /// The faulting task retries the access later.
+    Retry,
+    /// The faulting task is killed, or diverted to its fault handler.
+    Kill,
+    /// The fault is reported as unhandled, which panics the kernel.
+    Panic,
+}
+
+impl TraceOutcome {
+    /// All the outcomes, in the order of their encoding.
+    pub const ALL: [Self; 4] = [Self::Handled, Self::Retry, Self::Kill, Self::Panic];
+}
+
+/// A fault recorded in a [`FaultTrace`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FaultEvent {
+    /// The faulting address.
+    pub vaddr: usize,
+    /// The access flags of the fault.
+    pub flags: MappingFlags,
+    /// Whether the fault is from user mode.
+    pub is_user: bool,
+    /// How the fault was handled.
+    pub outcome: TraceOutcome,
+}
+
+impl FaultEvent {
+    fn pack(&self) -> usize {
+        self.flags.bits() | (self.is_user as usize) << 16 | (self.outcome as usize) << 17
+    }
+
+    fn unpack(vaddr: usize, info: usize) -> Self {
+        Self {
+            vaddr,
+            flags: MappingFlags::from_bits_truncate(info & 0xffff),
+            is_user: info & 1 << 16 != 0,
+            outcome: TraceOutcome::ALL[(info >> 17) & 0b11],
+        }
+    }
+}
+
+impl fmt::Display for FaultEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "vaddr={:#x}, flags={:?}, is_user={}, {:?}",
+            self.vaddr, self.flags, self.is_user, self.outcome
+        )
+    }
+}
+
+struct Slot {
+    /// Sequence number of the event plus one, or 0 while it is written.
+    seq: AtomicUsize,
+    vaddr: AtomicUsize,
+    info: AtomicUsize,
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            seq: AtomicUsize::new(0),
+            vaddr: AtomicUsize::new(0),
+            info: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// The last `N` faults, in a fixed-size ring of atomics.
+///
+/// Recording takes no lock, so that it is safe in the trap handler, and
+/// concurrent faults on other harts write other slots. A slot overwritten
+/// while it is read is skipped by the reader.
+pub struct FaultTrace<const N: usize> {
+    slots: [Slot; N],
+    next: AtomicUsize,
+}
+
+impl<const N: usize> FaultTrace<N> {
+    pub const fn new() -> Self {
+        Self {
+            slots: [const { Slot::new() }; N],
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Records a fault, overwriting the oldest one if the ring is full.
+    pub fn record(&self, event: FaultEvent) {
+        let seq = self.next.fetch_add(1, Ordering::Relaxed);
+        let slot = &self.slots[seq % N];
+        slot.seq.store(0, Ordering::Relaxed);
+        fence(Ordering::Release);
+        slot.vaddr.store(event.vaddr, Ordering::Relaxed);
+        slot.info.store(event.pack(), Ordering::Relaxed);
+        slot.seq.store(seq + 1, Ordering::Release);
+    }
+
+    /// Calls `f` with the sequence number and the event of each fault kept,
+    /// from the oldest to the newest.
+    pub fn for_each(&self, mut f: impl FnMut(usize, FaultEvent)) {
+        let next = self.next.load(Ordering::Relaxed);
+        for seq in next.saturating_sub(N)..next {
+            let slot = &self.slots[seq % N];
+            if slot.seq.load(Ordering::Acquire) != seq + 1 {
+                continue; // 正在写入，或已被更新的事件覆盖
+            }
+            let (vaddr, info) = (
+                slot.vaddr.load(Ordering::Relaxed),
+                slot.info.load(Ordering::Relaxed),
+            );
+            fence(Ordering::Acquire);
+            if slot.seq.load(Ordering::Relaxed) == seq + 1 {
+                f(seq, FaultEvent::unpack(vaddr, info));
+            }
+        }
+    }
+}
+
+impl<const N: usize> fmt::Display for FaultTrace<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut res = Ok(());
+        self.for_each(|seq, event| {
+            if res.is_ok() {
+                res = writeln!(f, "#{}: {}", seq, event);
+            }
+        });
+        res
+    }
+}
+
+/// Records a fault in the global trace, see [`FaultTrace::record`].
+pub fn record_fault_trace(vaddr: usize, flags: MappingFlags, is_user: bool, outcome: TraceOutcome) {
+    TRACE.record(FaultEvent {
+        vaddr,
+        flags,
+        is_user,
+        outcome,
+    });
+}
+
+/// Prints the last faults of the global trace, e.g. before an unhandled
+/// fault panics the kernel.
+pub fn dump_fault_trace() {
+    ax_println!("last {} page faults:\n{}", TRACE_LEN, TRACE);
+}
@@ -10,6 +10,7 @@ pub fn load_user_app(fname: &str, uspace: &mut AddrSpace) -> io::Result<()> {
     load_file(fname, &mut buf)?;
 
     uspace.map_alloc(APP_ENTRY.into(), PAGE_SIZE_4K, MappingFlags::READ|MappingFlags::WRITE|MappingFlags::EXECUTE|MappingFlags::USER, true).unwrap();
+    uspace.name_region(APP_ENTRY.into(), PAGE_SIZE_4K, "user-text").unwrap();
 
     let (paddr, _, _) = uspace
         .page_table()
@@ -9,9 +9,18 @@ extern crate alloc;
 extern crate axlog;
 
 mod task;
+mod active_aspace;
 mod syscall;
 mod page_fault;
+mod fault_log;
+mod fault_stats;
+mod fault_trace;
+mod fault_storm;
+mod exec_fault;
+mod reg_dump;
 mod loader;
+#[cfg(feature = "selftest")]
+mod selftest;
 
 use axstd::io;
 use axhal::paging::MappingFlags;
@@ -23,6 +32,8 @@ use axmm::AddrSpace;
 use loader::load_user_app;
 
 const USER_STACK_SIZE: usize = 0x10000;
+const USER_STACK_MAX_SIZE: usize = 0x80000; // 512 KiB
+const USER_STACK_GUARD: usize = 0x1000;
 const KERNEL_STACK_SIZE: usize = 0x40000; // 256 KiB
 const APP_ENTRY: usize = 0x1000;
 
@@ -38,6 +49,8 @@ fn main() {
 
     // Init user stack.
     let ustack_top = init_user_stack(&mut uspace, false).unwrap();
+    #[cfg(feature = "selftest")]
+    selftest::run();
     ax_println!("New user address space: {:#x?}", uspace);
 
     // Let's kick off the user process.
@@ -48,6 +61,12 @@ fn main() {
 
     // Wait for user process to exit ...
     let exit_code = user_task.join();
+    let stats = fault_stats::page_fault_stats();
+    ax_println!(
+        "page faults: {:?}, avg populate ticks: {:?}",
+        stats,
+        stats.avg_populate_ticks()
+    );
     ax_println!("monolithic kernel exit [{:?}] normally!", exit_code);
 }
 
@@ -64,5 +83,12 @@ fn init_user_stack(uspace: &mut AddrSpace, populating: bool) -> io::Result<VirtA
         MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER,
         populating,
     ).unwrap();
+    uspace.set_stack_growth(ustack_vaddr, ustack_top, USER_STACK_MAX_SIZE, USER_STACK_GUARD);
+    // 栈最大范围之下的一页作为保护页
+    let guard = ustack_top - USER_STACK_MAX_SIZE - 0x1000;
+    uspace.add_guard_range(guard, 0x1000).unwrap();
+    uspace
+        .name_region(ustack_top - USER_STACK_MAX_SIZE, USER_STACK_MAX_SIZE, "user-stack")
+        .unwrap();
     Ok(ustack_top)
 }
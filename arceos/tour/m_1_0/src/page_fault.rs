@@ -1,20 +1,700 @@
-use axhal::trap::{register_trap_handler, PAGE_FAULT};
+use alloc::sync::Arc;
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axhal::trap::{register_trap_handler, FaultContext, PAGE_FAULT};
 use axhal::paging::MappingFlags;
-use axhal::mem::VirtAddr;
-use axtask::TaskExtRef;
+use axhal::mem::{MemoryAddr, VirtAddr, PAGE_SIZE_4K};
+use axmm::{AddrSpace, PageFaultError, PageFaultResult};
+use axsync::{Mutex, MutexGuard};
+
+use crate::active_aspace::active_aspace;
+use crate::exec_fault::ExecFault;
+use crate::fault_log::{record_fault, FaultSite, Suppressed};
+use crate::fault_storm::FAULT_RETRY_LIMIT;
+use crate::fault_stats::{record_fault_stats, record_populate_latency};
+use crate::fault_trace::{dump_fault_trace, record_fault_trace, TraceOutcome};
+use crate::reg_dump::RegDump;
+use crate::task::try_task_ext;
+
+/// Exit code of a task killed by an unrecoverable page fault.
+pub const PAGE_FAULT_EXIT_CODE: i32 = -11; // 同 SIGSEGV
+
+/// Number of attempts to lock the address space for a user fault, before
+/// the lock is considered stuck.
+pub const ASPACE_LOCK_RETRIES: usize = 1000;
+
+/// Size of the range at address 0 where a user fault is a null pointer
+/// dereference, see [`set_null_guard_size`].
+static NULL_GUARD_SIZE: AtomicUsize = AtomicUsize::new(PAGE_SIZE_4K);
+
+/// Sets the size of the range `[0, size)` where a user fault is reported as
+/// a null pointer dereference and terminates the task, without trying to
+/// resolve it, even if the range is mapped. It is one 4K page by default,
+/// and 0 disables the check.
+#[cfg(feature = "selftest")]
+pub fn set_null_guard_size(size: usize) {
+    NULL_GUARD_SIZE.store(size, Ordering::Relaxed);
+}
+
+/// Whether a user fault at `vaddr` is a null pointer dereference, see
+/// [`set_null_guard_size`].
+pub fn is_null_deref(vaddr: VirtAddr) -> bool {
+    vaddr.as_usize() < NULL_GUARD_SIZE.load(Ordering::Relaxed)
+}
+
+/// Formats an instruction pointer from [`FaultContext::pc`] in hex, or as
+/// `unavailable` for a fault that does not come from a trap.
+pub struct FaultPc(pub Option<usize>);
+
+impl fmt::Display for FaultPc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(pc) => write!(f, "{:#x}", pc),
+            None => write!(f, "unavailable"),
+        }
+    }
+}
+
+#[cfg(feature = "selftest")]
+static KERNEL_FAULT_POLICY: kspin::SpinNoIrq<KernelFaultPolicy> =
+    kspin::SpinNoIrq::new(KernelFaultPolicy::Panic);
+
+/// What to do with an unhandled kernel fault, after dumping it.
+///
+/// Nothing but the self-tests sets a policy yet, so it is only built with
+/// them, and the unhandled kernel faults always panic otherwise.
+#[cfg(feature = "selftest")]
+#[derive(Debug, Clone, Copy)]
+pub enum KernelFaultPolicy {
+    /// Report the fault as unhandled, which panics the kernel, e.g., to halt
+    /// a development board with the full diagnostics.
+    Panic,
+    /// Call the function to reset the offending subsystem, then terminate
+    /// the faulting task with [`PAGE_FAULT_EXIT_CODE`], so that the rest of
+    /// the kernel keeps running.
+    Reset(fn()),
+}
+
+/// Sets the policy for the unhandled kernel faults, [`KernelFaultPolicy::Panic`]
+/// by default.
+#[cfg(feature = "selftest")]
+pub fn set_kernel_fault_policy(policy: KernelFaultPolicy) {
+    *KERNEL_FAULT_POLICY.lock() = policy;
+}
+
+/// Returns the policy set by [`set_kernel_fault_policy`].
+#[cfg(feature = "selftest")]
+pub fn kernel_fault_policy() -> KernelFaultPolicy {
+    *KERNEL_FAULT_POLICY.lock()
+}
+
+/// What to do with a page fault that the address space failed to resolve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultAction {
+    /// Yield and return to the faulting instruction to try again.
+    Retry,
+    /// Terminate the faulting task.
+    Kill,
+    /// Report the fault as unhandled, which panics the kernel.
+    Panic,
+}
+
+pub fn fault_action(err: PageFaultError, is_user: bool) -> FaultAction {
+    match (err, is_user) {
+        (_, false) => FaultAction::Panic,
+        // 内存可能被其他任务释放，或地址空间被其他任务锁住，稍后重试
+        (PageFaultError::NoMemory | PageFaultError::PageTableOom | PageFaultError::Busy, true) => {
+            FaultAction::Retry
+        }
+        (_, true) => FaultAction::Kill,
+    }
+}
+
+/// The address space operations needed to resolve a page fault.
+///
+/// It is implemented by [`AddrSpace`], and by mock address spaces in tests.
+pub trait FaultAspace {
+    /// Whether `vaddr` belongs to the address space.
+    fn contains(&self, vaddr: VirtAddr) -> bool;
+    /// Whether `vaddr` is a user address, see [`AddrSpace::is_user_addr`].
+    fn is_user_addr(&self, vaddr: VirtAddr) -> bool;
+    /// Resolves a page fault at `vaddr`, see [`AddrSpace::try_handle_page_fault`].
+    fn handle_fault(&mut self, vaddr: VirtAddr, flags: MappingFlags) -> PageFaultResult;
+}
+
+impl FaultAspace for AddrSpace {
+    fn contains(&self, vaddr: VirtAddr) -> bool {
+        self.contains_range(vaddr, 1)
+    }
+
+    fn is_user_addr(&self, vaddr: VirtAddr) -> bool {
+        AddrSpace::is_user_addr(self, vaddr)
+    }
+
+    fn handle_fault(&mut self, vaddr: VirtAddr, flags: MappingFlags) -> PageFaultResult {
+        self.try_handle_page_fault(vaddr, flags)
+    }
+}
+
+/// The outcome of a page fault, decided by [`resolve_fault`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultOutcome {
+    /// The fault has been resolved.
+    Handled,
+    /// The fault could not be resolved, and the faulting task should act as
+    /// [`fault_action`] decides.
+    Failed(PageFaultError, FaultAction),
+}
+
+impl FaultOutcome {
+    /// Decides the outcome of a fault from the resolving result.
+    pub fn from_result(res: PageFaultResult, is_user: bool) -> Self {
+        match res {
+            Ok(()) => Self::Handled,
+            Err(err) => Self::Failed(err, fault_action(err, is_user)),
+        }
+    }
+
+    /// Decides how the trap handler returns from the fault, or `None` if the
+    /// faulting task is killed and never returns.
+    pub const fn trap_return(self) -> Option<FaultReturn> {
+        match self {
+            Self::Handled | Self::Failed(_, FaultAction::Retry) => Some(FaultReturn::Retry),
+            Self::Failed(_, FaultAction::Kill) => None,
+            Self::Failed(_, FaultAction::Panic) => Some(FaultReturn::Fatal),
+        }
+    }
+}
+
+impl From<FaultOutcome> for TraceOutcome {
+    fn from(outcome: FaultOutcome) -> Self {
+        match outcome {
+            FaultOutcome::Handled => Self::Handled,
+            FaultOutcome::Failed(_, FaultAction::Retry) => Self::Retry,
+            FaultOutcome::Failed(_, FaultAction::Kill) => Self::Kill,
+            FaultOutcome::Failed(_, FaultAction::Panic) => Self::Panic,
+        }
+    }
+}
+
+/// How the page fault handler returns to the trap layer.
+///
+/// The `axhal` trap layer returns to the faulting instruction if a
+/// [`PAGE_FAULT`] handler returns `true`, and panics otherwise. It never
+/// skips the faulting instruction, so an access must not be emulated by the
+/// handler and then reported as handled, or it would be executed twice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultReturn {
+    /// Re-execute the faulting instruction.
+    Retry,
+    /// Report the fault as unhandled, which panics the kernel.
+    Fatal,
+}
+
+impl FaultReturn {
+    /// Converts to the value returned by the [`PAGE_FAULT`] handlers.
+    pub const fn is_handled(self) -> bool {
+        matches!(self, Self::Retry)
+    }
+}
 
 #[register_trap_handler(PAGE_FAULT)]
-fn handle_page_fault(vaddr: VirtAddr, flags: MappingFlags, is_user: bool) -> bool {
-    ax_println!("handle_page_fault...");
-    if is_user {
-        if axtask::current().task_ext().aspace.lock().handle_page_fault(vaddr, flags) {
-            ax_println!("handle_page_fault: OK");
-            true
-        } else {
-            ax_println!("handle_page_fault: FAIL");
-            false
-        }
-    } else {
-        false
-    }
-}
\ No newline at end of file
+pub fn handle_page_fault(
+    vaddr: VirtAddr,
+    flags: MappingFlags,
+    is_user: bool,
+    ctx: &mut FaultContext,
+) -> bool {
+    // 没有任务扩展数据时不是用户任务，按内核缺页处理
+    let has_ext = try_task_ext(&axtask::current()).is_some();
+    let is_user = is_user && has_ext;
+    let aspace = current_aspace();
+    // 同一位置反复出错时限制日志输出
+    let logged = record_fault(vaddr.align_down_4k().as_usize(), is_user);
+    // 处理之前查询所在区域，映射可能随后改变
+    let site = FaultSite {
+        vaddr: vaddr.as_usize(),
+        region: fault_region(aspace.as_deref(), vaddr),
+    };
+    if let Some(suppressed) = logged {
+        // 处理之前先记录能否恢复
+        ax_println!(
+            "handle_page_fault: pc={}, vaddr={}, flags={:?}, is_user={}, {}{}",
+            FaultPc(ctx.pc()),
+            site,
+            flags,
+            is_user,
+            fault_class(aspace.as_deref(), vaddr, flags, is_user),
+            Suppressed(suppressed)
+        );
+    }
+    if is_user && is_null_deref(vaddr) {
+        // 不尝试映射零页，即使那里有合法的映射
+        record_fault_stats(is_user, false);
+        record_fault_trace(vaddr.as_usize(), flags, is_user, TraceOutcome::Kill);
+        ax_println!(
+            "{}: null pointer dereference at {}, flags: {:?}, exit!",
+            axtask::current().id_name(),
+            site,
+            flags
+        );
+        axtask::exit(PAGE_FAULT_EXIT_CODE);
+    }
+    let mut outcome = match aspace.as_deref() {
+        None => FaultOutcome::from_result(Err(PageFaultError::NoRegion), is_user),
+        Some(lock) => match with_fault_aspace(lock, is_user, axtask::yield_now, |aspace| {
+            // 只统计填充页面的缺页，不含虚假缺页
+            let populates = !aspace.is_populated(vaddr);
+            let start = axhal::time::current_ticks();
+            let outcome = resolve_fault(aspace, vaddr, flags, is_user);
+            if populates && outcome == FaultOutcome::Handled {
+                record_populate_latency(start, axhal::time::current_ticks());
+            }
+            outcome
+        }) {
+            Ok(outcome) => outcome,
+            Err(failure) => {
+                if failure == LockFailure::Stuck {
+                    ax_println!(
+                        "handle_page_fault: aspace lock stuck, likely prior fault panic, \
+                         {} attempts",
+                        ASPACE_LOCK_RETRIES
+                    );
+                }
+                failure.outcome(is_user)
+            }
+        },
+    };
+    let wedged = is_user && is_wedged(vaddr, flags, outcome == FaultOutcome::Handled);
+    if let FaultOutcome::Failed(err, FaultAction::Retry) = outcome {
+        if is_user && retries_exhausted() {
+            // 一直失败的缺页重试也不会成功，按不可恢复处理
+            ax_println!(
+                "{}: {:?} at {} after {} retries, give up",
+                axtask::current().id_name(),
+                err,
+                site,
+                FAULT_RETRY_LIMIT
+            );
+            outcome = FaultOutcome::Failed(err, FaultAction::Kill);
+        }
+    }
+    record_fault_stats(is_user, outcome == FaultOutcome::Handled);
+    record_fault_trace(vaddr.as_usize(), flags, is_user, outcome.into());
+    if wedged {
+        ax_println!(
+            "{}: stuck faulting at {:#x}, flags: {:?}, exit!",
+            axtask::current().id_name(),
+            vaddr,
+            flags
+        );
+        axtask::exit(PAGE_FAULT_EXIT_CODE);
+    }
+    match outcome {
+        FaultOutcome::Handled => {
+            if logged.is_some() {
+                ax_println!("handle_page_fault: OK at {}", site);
+            }
+        }
+        FaultOutcome::Failed(err, FaultAction::Retry) => {
+            if logged.is_some() {
+                ax_println!("handle_page_fault: {:?} at {}, retry", err, site);
+            }
+            axtask::yield_now();
+        }
+        FaultOutcome::Failed(err, FaultAction::Kill) => {
+            if divert_to_handler(vaddr, err, &site, ctx) {
+                return true;
+            }
+            // 用户态非法访问，只结束当前任务，不让内核 panic
+            let kind = aspace
+                .as_deref()
+                .and_then(|aspace| Some(aspace.try_lock()?.fault_kind(vaddr, flags)));
+            if err == PageFaultError::GuardPage {
+                ax_println!(
+                    "{}: stack overflow / guard page hit at {}, exit!",
+                    axtask::current().id_name(),
+                    site
+                );
+                axtask::exit(PAGE_FAULT_EXIT_CODE);
+            }
+            if err == PageFaultError::BudgetExceeded {
+                ax_println!(
+                    "{}: exceeded paging budget at {}, exit!",
+                    axtask::current().id_name(),
+                    site
+                );
+                axtask::exit(PAGE_FAULT_EXIT_CODE);
+            }
+            if err == PageFaultError::Repopulated {
+                ax_println!(
+                    "{}: fault again in a populate-once region at {}, exit!",
+                    axtask::current().id_name(),
+                    site
+                );
+                axtask::exit(PAGE_FAULT_EXIT_CODE);
+            }
+            // 取指错误单独报告，包括 W^X 违规
+            if let Some(fetch) = ExecFault::decode(vaddr.as_usize(), flags, ctx.pc(), err) {
+                ax_println!("{}: {}, exit!", axtask::current().id_name(), fetch);
+                axtask::exit(PAGE_FAULT_EXIT_CODE);
+            }
+            if let PageFaultError::Protection { required, present } = err {
+                ax_println!(
+                    "{}: protection fault at {}, requires {:?} but has {:?}, exit!",
+                    axtask::current().id_name(),
+                    site,
+                    required,
+                    present
+                );
+                axtask::exit(PAGE_FAULT_EXIT_CODE);
+            }
+            if err == PageFaultError::NoRegion {
+                // 列出所有区域，看出访问偏离了多远
+                dump_regions(aspace.as_deref());
+            }
+            ax_println!(
+                "{}: segmentation fault at {}, flags: {:?} ({:?}, {:?}), exit!",
+                axtask::current().id_name(),
+                site,
+                flags,
+                kind,
+                err
+            );
+            axtask::exit(PAGE_FAULT_EXIT_CODE);
+        }
+        FaultOutcome::Failed(err, FaultAction::Panic) => {
+            dump_fatal_fault(vaddr, flags, err, ctx);
+            #[cfg(feature = "selftest")]
+            if let (false, KernelFaultPolicy::Reset(reset)) = (is_user, kernel_fault_policy()) {
+                ax_println!(
+                    "{}: unhandled kernel fault at {}, reset and exit!",
+                    axtask::current().id_name(),
+                    site
+                );
+                reset();
+                axtask::exit(PAGE_FAULT_EXIT_CODE);
+            }
+        }
+    }
+    // 被结束的任务不会执行到这里
+    outcome.trap_return().is_some_and(FaultReturn::is_handled)
+}
+
+/// Diverts an unrecoverable user fault to the fault handler registered by
+/// the current task, see [`TaskExt::set_fault_handler`], and returns whether
+/// there is one.
+///
+/// The handler is entered with the fault address as the first argument when
+/// the trap returns.
+///
+/// [`TaskExt::set_fault_handler`]: crate::task::TaskExt::set_fault_handler
+fn divert_to_handler(
+    vaddr: VirtAddr,
+    err: PageFaultError,
+    site: &FaultSite,
+    ctx: &mut FaultContext,
+) -> bool {
+    let curr = axtask::current();
+    let Some(entry) = try_task_ext(&curr).and_then(|ext| ext.fault_handler()) else {
+        return false;
+    };
+    ax_println!(
+        "{}: {:?} at {}, divert to the fault handler at {:#x}",
+        curr.id_name(),
+        err,
+        site,
+        entry
+    );
+    ctx.divert(entry, vaddr.as_usize());
+    true
+}
+
+/// Dumps a fault that is about to be reported as unhandled, with the
+/// registers of the trap and the last faults, since the kernel panic that
+/// follows tells little.
+fn dump_fatal_fault(
+    vaddr: VirtAddr,
+    flags: MappingFlags,
+    err: PageFaultError,
+    ctx: &FaultContext,
+) {
+    ax_println!(
+        "handle_page_fault: fatal {:?} at {:#x}, flags: {:?}",
+        err,
+        vaddr,
+        flags
+    );
+    if let Some(fetch) = ExecFault::decode(vaddr.as_usize(), flags, ctx.pc(), err) {
+        ax_println!("handle_page_fault: {}", fetch);
+    }
+    let dump = RegDump {
+        cause: "page_fault",
+        pc: ctx.pc(),
+        addr: Some(vaddr.as_usize()),
+        regs: ctx.regs(),
+    };
+    ax_println!("{}", dump);
+    dump_fault_trace();
+}
+
+/// Displays the region table of an address space, see
+/// [`AddrSpace::dump_regions`].
+pub struct RegionMap<'a>(pub &'a AddrSpace);
+
+impl fmt::Display for RegionMap<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.dump_regions(f)
+    }
+}
+
+/// Prints the region table of the faulting address space, unless it is
+/// locked.
+fn dump_regions(aspace: Option<&Mutex<AddrSpace>>) {
+    match aspace.map(|aspace| aspace.try_lock()) {
+        Some(Some(aspace)) => ax_println!(
+            "{}: address space regions:\n{}",
+            axtask::current().id_name(),
+            RegionMap(&aspace)
+        ),
+        Some(None) => ax_println!("handle_page_fault: address space locked, no region dump"),
+        None => ax_println!("handle_page_fault: no address space"),
+    }
+}
+
+/// Records a user fault of the current task, and checks whether the task
+/// keeps re-taking the same fault without making progress.
+fn is_wedged(vaddr: VirtAddr, flags: MappingFlags, handled: bool) -> bool {
+    let curr = axtask::current();
+    let Some(ext) = try_task_ext(&curr) else {
+        return false;
+    };
+    ext.fault_storm
+        .lock()
+        .record(vaddr.as_usize(), flags, handled)
+}
+
+/// Whether the current task has retried the same failing fault too many
+/// times, see [`FaultStorm::retries_exhausted`].
+///
+/// [`FaultStorm::retries_exhausted`]: crate::fault_storm::FaultStorm::retries_exhausted
+fn retries_exhausted() -> bool {
+    let curr = axtask::current();
+    try_task_ext(&curr).is_some_and(|ext| ext.fault_storm.lock().retries_exhausted())
+}
+
+/// Returns the name of the region containing `vaddr` for the log, see
+/// [`AddrSpace::region_name`], or `unknown` if the address space is locked.
+fn fault_region(aspace: Option<&Mutex<AddrSpace>>, vaddr: VirtAddr) -> &'static str {
+    let Some(aspace) = aspace else {
+        return "unmapped";
+    };
+    match aspace.try_lock() {
+        Some(aspace) => aspace.region_name(vaddr),
+        None => "unknown",
+    }
+}
+
+/// Classifies a fault as `recoverable` or `fatal` for the log, or `unknown`
+/// if the address space is locked.
+fn fault_class(
+    aspace: Option<&Mutex<AddrSpace>>,
+    vaddr: VirtAddr,
+    flags: MappingFlags,
+    is_user: bool,
+) -> &'static str {
+    let Some(aspace) = aspace else {
+        return "fatal";
+    };
+    match aspace.try_lock() {
+        Some(aspace) if aspace.fault_is_recoverable(vaddr, flags, is_user) => "recoverable",
+        Some(_) => "fatal",
+        None => "unknown",
+    }
+}
+
+/// Returns the address space that the faults of the current task are
+/// resolved in.
+///
+/// It is the override installed by
+/// [`set_active_aspace`](crate::active_aspace::set_active_aspace) if any,
+/// then the address space of the task, or `None` if it is a kernel task
+/// without one.
+pub fn current_aspace() -> Option<Arc<Mutex<AddrSpace>>> {
+    if let Some(aspace) = active_aspace() {
+        return Some(aspace);
+    }
+    Some(try_task_ext(&axtask::current())?.aspace.clone())
+}
+
+/// Resolves a page fault in the given address space.
+///
+/// A kernel-mode fault is handled like a user one if the address belongs to
+/// the user address space, e.g., when the kernel accesses a lazily mapped
+/// user buffer. Faults on kernel addresses are never resolved, and a
+/// user-mode fault on one terminates the task.
+///
+/// # Locking
+///
+/// The address space lock is only acquired with `try_lock`, never waited
+/// for in the trap handler. If it is already held, [`PageFaultError::Busy`]
+/// is returned:
+///
+/// - For a user fault, the lock can only be held by another task, so the
+///   fault is retried after yielding. The page fault handler gives up after
+///   a bounded number of attempts, see [`lock_for_fault`].
+/// - For a kernel fault, the lock may be held by the faulting code itself
+///   (a nested fault), which would never be released, so the fault is
+///   reported as unhandled.
+///
+/// Code holding the address space lock must not access lazily mapped user
+/// memory.
+#[cfg(feature = "selftest")]
+pub fn resolve_page_fault(
+    aspace: Option<&Mutex<AddrSpace>>,
+    vaddr: VirtAddr,
+    flags: MappingFlags,
+    is_user: bool,
+) -> PageFaultResult {
+    handle_fault_in(&mut *lock_aspace(aspace)?, vaddr, flags, is_user)
+}
+
+/// Resolves a page fault in a locked address space and decides what to do
+/// if it fails.
+///
+/// This is the decision logic of the page fault handler, which only adds
+/// the locking described in [`resolve_page_fault`].
+pub fn resolve_fault(
+    aspace: &mut dyn FaultAspace,
+    vaddr: VirtAddr,
+    flags: MappingFlags,
+    is_user: bool,
+) -> FaultOutcome {
+    FaultOutcome::from_result(handle_fault_in(aspace, vaddr, flags, is_user), is_user)
+}
+
+/// A lock that can be tried without blocking.
+///
+/// It is implemented by [`Mutex`], and by mock locks in tests.
+pub trait TryLock {
+    /// The guard releasing the lock when dropped.
+    type Guard<'a>
+    where
+        Self: 'a;
+    /// Acquires the lock if it is free.
+    fn try_lock(&self) -> Option<Self::Guard<'_>>;
+}
+
+impl<T> TryLock for Mutex<T> {
+    type Guard<'a> = MutexGuard<'a, T> where Self: 'a;
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        Mutex::try_lock(self)
+    }
+}
+
+/// A lock whose data the page fault handler accesses, see
+/// [`with_fault_aspace`].
+pub trait FaultLock: TryLock {
+    /// The data protected by the lock.
+    type Target: ?Sized;
+    /// Calls `f` with the data behind `guard`.
+    fn with_guard<R>(guard: Self::Guard<'_>, f: impl FnOnce(&mut Self::Target) -> R) -> R;
+}
+
+impl<T> FaultLock for Mutex<T> {
+    type Target = T;
+
+    fn with_guard<R>(mut guard: MutexGuard<'_, T>, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut *guard)
+    }
+}
+
+/// Why the address space could not be locked for a fault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockFailure {
+    /// The lock is held, see [`resolve_page_fault`].
+    Busy,
+    /// The lock stayed held for [`ASPACE_LOCK_RETRIES`] attempts, which
+    /// likely means that a prior fault panicked while holding it.
+    Stuck,
+}
+
+impl LockFailure {
+    /// Decides the outcome of a fault that failed to lock the address space.
+    ///
+    /// A stuck lock is fatal even for a user fault, otherwise the task would
+    /// retry the fault forever.
+    pub fn outcome(self, is_user: bool) -> FaultOutcome {
+        match self {
+            Self::Busy => FaultOutcome::from_result(Err(PageFaultError::Busy), is_user),
+            Self::Stuck => FaultOutcome::Failed(PageFaultError::Busy, FaultAction::Panic),
+        }
+    }
+}
+
+/// Locks the address space for a fault.
+///
+/// A kernel fault only tries once, as the lock may be held by the faulting
+/// code itself. A user fault tries up to [`ASPACE_LOCK_RETRIES`] times,
+/// calling `relax` between the attempts, and then gives up with
+/// [`LockFailure::Stuck`] instead of spinning forever.
+pub fn lock_for_fault<L: TryLock + ?Sized>(
+    lock: &L,
+    is_user: bool,
+    mut relax: impl FnMut(),
+) -> Result<L::Guard<'_>, LockFailure> {
+    if !is_user {
+        return lock.try_lock().ok_or(LockFailure::Busy);
+    }
+    for i in 0..ASPACE_LOCK_RETRIES {
+        if i != 0 {
+            relax();
+        }
+        if let Some(guard) = lock.try_lock() {
+            return Ok(guard);
+        }
+    }
+    Err(LockFailure::Stuck)
+}
+
+/// Calls `f` with the address space of a fault, locked by
+/// [`lock_for_fault`].
+///
+/// The lock is held until `f` returns, even on a single hart, as the fault
+/// handling may yield, e.g. while populating many pages, and let another
+/// task access the address space.
+pub fn with_fault_aspace<L: FaultLock + ?Sized, R>(
+    lock: &L,
+    is_user: bool,
+    relax: impl FnMut(),
+    f: impl FnOnce(&mut L::Target) -> R,
+) -> Result<R, LockFailure> {
+    let guard = lock_for_fault(lock, is_user, relax)?;
+    Ok(L::with_guard(guard, f))
+}
+
+#[cfg(feature = "selftest")]
+fn lock_aspace(
+    aspace: Option<&Mutex<AddrSpace>>,
+) -> Result<MutexGuard<'_, AddrSpace>, PageFaultError> {
+    let aspace = aspace.ok_or(PageFaultError::NoRegion)?;
+    aspace.try_lock().ok_or(PageFaultError::Busy)
+}
+
+fn handle_fault_in(
+    aspace: &mut dyn FaultAspace,
+    vaddr: VirtAddr,
+    flags: MappingFlags,
+    is_user: bool,
+) -> PageFaultResult {
+    if !is_user && !aspace.contains(vaddr) {
+        return Err(PageFaultError::NoRegion); // 内核地址
+    }
+    if is_user && !aspace.is_user_addr(vaddr) {
+        // 不信任硬件给出的特权级，用户态不能访问内核地址
+        return Err(PageFaultError::NoRegion);
+    }
+    aspace.handle_fault(vaddr, flags)
+}
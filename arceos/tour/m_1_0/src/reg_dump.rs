@@ -0,0 +1,65 @@
+//! Register dumps of fatal traps, for post-mortem debugging.
+
+use core::fmt;
+
+/// The prefix of every line of a [`RegDump`], to grep it out of the log.
+pub const REG_DUMP_PREFIX: &str = "REGDUMP";
+
+/// Number of registers printed per line.
+const REGS_PER_LINE: usize = 4;
+
+/// A dump of a fatal trap, formatted as `key=value` lines that all start
+/// with [`REG_DUMP_PREFIX`].
+///
+/// The first line describes the trap, then the registers follow, in the
+/// order of the trap frame of the architecture, e.g.:
+///
+/// ```text
+/// REGDUMP cause=page_fault pc=0xffffffc080200000 addr=0x0000000000004000
+/// REGDUMP r00=0x0000000000000000 r01=0x0000000000000001 ...
+/// REGDUMP end
+/// ```
+pub struct RegDump<'a> {
+    /// What trapped, without spaces.
+    pub cause: &'a str,
+    /// The instruction pointer of the trap, if the architecture records it.
+    pub pc: Option<usize>,
+    /// The faulting address, if any.
+    pub addr: Option<usize>,
+    /// The raw words of the trap frame.
+    pub regs: &'a [usize],
+}
+
+/// Formats a word in hex, zero-padded to the width of a machine word.
+struct Word(usize);
+
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:#0width$x}",
+            self.0,
+            width = 2 + 2 * core::mem::size_of::<usize>()
+        )
+    }
+}
+
+impl fmt::Display for RegDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} cause={} pc=", REG_DUMP_PREFIX, self.cause)?;
+        match self.pc {
+            Some(pc) => write!(f, "{}", Word(pc))?,
+            None => write!(f, "unavailable")?,
+        }
+        if let Some(addr) = self.addr {
+            write!(f, " addr={}", Word(addr))?;
+        }
+        for (i, line) in self.regs.chunks(REGS_PER_LINE).enumerate() {
+            write!(f, "\n{}", REG_DUMP_PREFIX)?;
+            for (j, reg) in line.iter().enumerate() {
+                write!(f, " r{:02}={}", i * REGS_PER_LINE + j, Word(*reg))?;
+            }
+        }
+        write!(f, "\n{} end", REG_DUMP_PREFIX)
+    }
+}
@@ -0,0 +1,1227 @@
+//! Self-tests of the page fault handling, run at boot before the user app
+//! with the `selftest` feature.
+
+use alloc::format;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use axhal::mem::VirtAddr;
+use axhal::paging::{MappingFlags, PageSize};
+use axhal::trap::FaultContext;
+use axmm::AddrSpace;
+use axsync::Mutex;
+
+use crate::{active_aspace, exec_fault, fault_log, fault_stats, fault_storm, fault_trace};
+use crate::{page_fault, task};
+use crate::{APP_ENTRY, USER_STACK_GUARD, USER_STACK_SIZE};
+
+/// Runs all the self-tests, each on its own address space.
+pub fn run() {
+    check_demand_zero();
+    check_cow();
+    check_cow_interleaved_fork();
+    check_cow_before_touch();
+    check_stack_growth();
+    check_kernel_fault();
+    check_guard_page();
+    check_active_aspace();
+    check_populate();
+    check_populate_yield();
+    check_resident_pages();
+    check_resident_frames();
+    check_spurious_fault();
+    check_protection_fault();
+    check_permission_upgrade();
+    check_fault_recoverable();
+    check_fault_without_task_ext();
+    check_kernel_fault_policy();
+    check_fault_handler();
+    check_null_deref();
+    check_huge_fault();
+    check_split_huge_page();
+    check_map_at_fault();
+    check_lazy_region();
+    check_fault_budget();
+    check_region_name();
+    check_populate_once();
+    #[cfg(feature = "fault-inject")]
+    check_fault_inject();
+    check_fault_action();
+    check_resolve_fault();
+    check_lock_for_fault();
+    check_fault_log();
+    check_fault_stats();
+    check_fault_trace();
+    check_fault_storm();
+    check_exec_fault();
+    check_reg_dump();
+    check_aspace_overrides();
+}
+
+/// Checks that the first touch of a demand-zero page populates it with a
+/// zeroed frame.
+fn check_demand_zero() {
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    let vaddr = uspace.end() - USER_STACK_SIZE;
+    let flags = MappingFlags::WRITE | MappingFlags::USER;
+    uspace.map_alloc(vaddr, 0x1000, flags | MappingFlags::READ, false).unwrap();
+    assert!(uspace.handle_page_fault(vaddr, flags), "demand-zero fault not handled");
+    let mut buf = [0xffu8; 8];
+    uspace.read(vaddr, &mut buf).unwrap();
+    assert_eq!(buf, [0; 8]);
+    ax_println!("Demand-zero page at {:#x?}: OK", vaddr);
+}
+
+/// Checks that a write to a copy-on-write page gives the writer a private
+/// copy, and the other address space still sees the original data.
+fn check_cow() {
+    let vaddr = VirtAddr::from(APP_ENTRY);
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let mut parent = axmm::new_user_aspace().unwrap();
+    parent.map_alloc(vaddr, 0x1000, flags, true).unwrap();
+    parent.write(vaddr, b"parent").unwrap();
+
+    let mut child = axmm::new_user_aspace().unwrap();
+    parent.share_cow(&mut child).unwrap();
+    let write = MappingFlags::WRITE | MappingFlags::USER;
+    assert!(child.handle_page_fault(vaddr, write), "cow fault not handled");
+    child.write(vaddr, b"child!").unwrap();
+
+    let mut buf = [0u8; 6];
+    parent.read(vaddr, &mut buf).unwrap();
+    assert_eq!(&buf, b"parent");
+    child.read(vaddr, &mut buf).unwrap();
+    assert_eq!(&buf, b"child!");
+    // 父地址空间是最后的持有者，直接恢复可写
+    assert!(parent.handle_page_fault(vaddr, write), "cow fault not handled");
+    ax_println!("Copy-on-write page at {:#x?}: OK", vaddr);
+}
+
+/// Checks that a fork while a copy-on-write page is still shared copies it
+/// once per writer, and leaves the frame to its last owner.
+fn check_cow_interleaved_fork() {
+    let vaddr = VirtAddr::from(APP_ENTRY);
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let write = MappingFlags::WRITE | MappingFlags::USER;
+    let frame_of = |aspace: &AddrSpace| aspace.page_table().query(vaddr).unwrap().0;
+    let mut parent = axmm::new_user_aspace().unwrap();
+    parent.map_alloc(vaddr, 0x1000, flags, true).unwrap();
+    parent.write(vaddr, b"parent").unwrap();
+    let frame = frame_of(&parent);
+
+    // 第一个子进程写入之前，父进程再次 fork
+    let mut first = axmm::new_user_aspace().unwrap();
+    parent.share_cow(&mut first).unwrap();
+    let mut second = axmm::new_user_aspace().unwrap();
+    parent.share_cow(&mut second).unwrap();
+    assert!(first.handle_page_fault(vaddr, write));
+    assert_ne!(frame_of(&first), frame);
+    assert!(parent.handle_page_fault(vaddr, write));
+    assert_ne!(frame_of(&parent), frame);
+    // 最后的持有者不再复制
+    assert!(second.handle_page_fault(vaddr, write));
+    assert_eq!(frame_of(&second), frame);
+    // 已是私有的页不会再被复制
+    assert!(parent.handle_page_fault(vaddr, write));
+
+    let mut buf = [0u8; 6];
+    for aspace in [&first, &parent, &second] {
+        aspace.read(vaddr, &mut buf).unwrap();
+        assert_eq!(&buf, b"parent");
+    }
+    second.write(vaddr, b"second").unwrap();
+    first.read(vaddr, &mut buf).unwrap();
+    assert_eq!(&buf, b"parent");
+    ax_println!("Copy-on-write page across forks at {:#x?}: OK", vaddr);
+}
+
+/// Checks that a fork before the first touch of a lazy page leaves it to
+/// be populated on each side, without sharing anything for it.
+fn check_cow_before_touch() {
+    let vaddr = VirtAddr::from(APP_ENTRY);
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let write = MappingFlags::WRITE | MappingFlags::USER;
+    let mut parent = axmm::new_user_aspace().unwrap();
+    parent.map_demand_zero(vaddr, 0x2000, flags).unwrap();
+    assert!(parent.handle_page_fault(vaddr, write));
+    parent.write(vaddr, b"parent").unwrap();
+
+    let mut child = axmm::new_user_aspace().unwrap();
+    parent.share_cow(&mut child).unwrap();
+    // 只共享已填充的页
+    assert_eq!(child.resident_pages(), 1);
+    let untouched = vaddr + 0x1000;
+    assert!(!parent.is_populated(untouched));
+    assert!(!child.is_populated(untouched));
+    assert!(child.handle_page_fault(untouched, write));
+    assert!(parent.handle_page_fault(untouched, write));
+    let frame_of = |aspace: &AddrSpace| aspace.page_table().query(untouched).unwrap().0;
+    assert_ne!(frame_of(&parent), frame_of(&child));
+    let mut buf = [0xffu8; 6];
+    child.read(untouched, &mut buf).unwrap();
+    assert_eq!(buf, [0; 6]);
+    child.read(vaddr, &mut buf).unwrap();
+    assert_eq!(&buf, b"parent");
+    ax_println!("Copy-on-write fork before first touch at {:#x?}: OK", vaddr);
+}
+
+/// Checks that a fault just below a growable stack extends the stack.
+fn check_stack_growth() {
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    let top = uspace.end();
+    let bottom = top - 0x1000;
+    uspace.map_alloc(bottom, 0x1000, flags, true).unwrap();
+    uspace.set_stack_growth(bottom, top, 0x2000, USER_STACK_GUARD);
+
+    let vaddr = bottom - 8;
+    let write = MappingFlags::WRITE | MappingFlags::USER;
+    assert!(uspace.handle_page_fault(vaddr, write), "stack not grown");
+    uspace.write(vaddr, &[1; 8]).unwrap();
+    // 已达到最大栈大小
+    assert!(!uspace.handle_page_fault(vaddr - 0x1000, write));
+    ax_println!("Stack growth to {:#x?}: OK", vaddr);
+}
+
+/// Checks that a kernel access to a lazily mapped user page is resolved, and
+/// a fault on a kernel address is not.
+fn check_kernel_fault() {
+    let vaddr = VirtAddr::from(APP_ENTRY);
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    uspace.map_demand_zero(vaddr, 0x1000, flags).unwrap();
+    let uspace = Mutex::new(uspace);
+
+    let res = page_fault::resolve_page_fault(Some(&uspace), vaddr, MappingFlags::READ, false);
+    assert_eq!(res, Ok(()));
+    let mut buf = [0xffu8; 8];
+    uspace.lock().read(vaddr, &mut buf).unwrap();
+    assert_eq!(buf, [0; 8]);
+
+    let kaddr = uspace.lock().end(); // 用户地址空间之外
+    let res = page_fault::resolve_page_fault(Some(&uspace), kaddr, MappingFlags::READ, false);
+    assert_eq!(res, Err(axmm::PageFaultError::NoRegion));
+    // 用户态访问内核地址同样被拒绝
+    assert!(uspace.lock().is_user_addr(vaddr));
+    assert!(!uspace.lock().is_user_addr(kaddr));
+    let res = page_fault::resolve_page_fault(Some(&uspace), kaddr, MappingFlags::READ, true);
+    assert_eq!(res, Err(axmm::PageFaultError::NoRegion));
+
+    // 持有锁时再次缺页，不会死锁
+    let guard = uspace.lock();
+    let res = page_fault::resolve_page_fault(Some(&uspace), vaddr, MappingFlags::READ, false);
+    assert_eq!(res, Err(axmm::PageFaultError::Busy));
+    drop(guard);
+    ax_println!("Kernel fault on user page at {:#x?}: OK", vaddr);
+}
+
+/// Checks that a fault on a guard page is reported as such.
+fn check_guard_page() {
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    let guard = uspace.end() - 0x1000;
+    uspace.add_guard_range(guard, 0x1000).unwrap();
+    let flags = MappingFlags::WRITE | MappingFlags::USER;
+    assert!(uspace.is_guard_page(guard + 8));
+    assert_eq!(
+        uspace.try_handle_page_fault(guard + 8, flags),
+        Err(axmm::PageFaultError::GuardPage)
+    );
+    ax_println!("Guard page at {:#x?}: OK", guard);
+}
+
+/// Checks that the faults of a task are resolved in its active address space
+/// override while it is installed.
+fn check_active_aspace() {
+    use active_aspace::{clear_active_aspace, set_active_aspace};
+    use page_fault::{current_aspace, resolve_page_fault};
+
+    let vaddr = VirtAddr::from(APP_ENTRY);
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    uspace.map_demand_zero(vaddr, 0x1000, flags).unwrap();
+    let borrowed = Arc::new(Mutex::new(uspace));
+
+    // 当前为内核任务，没有自己的地址空间
+    assert!(current_aspace().is_none());
+    set_active_aspace(borrowed.clone());
+    let aspace = current_aspace().unwrap();
+    assert!(Arc::ptr_eq(&aspace, &borrowed));
+    assert_eq!(resolve_page_fault(Some(&aspace), vaddr, flags, true), Ok(()));
+    let mut buf = [0xffu8; 8];
+    borrowed.lock().read(vaddr, &mut buf).unwrap();
+    assert_eq!(buf, [0; 8]);
+
+    assert!(clear_active_aspace().is_some());
+    assert!(current_aspace().is_none());
+    ax_println!("Active address space override at {:#x?}: OK", vaddr);
+}
+
+/// Checks that populating a lazy region in advance maps all of its pages,
+/// skipping the ones already populated.
+fn check_populate() {
+    let vaddr = VirtAddr::from(APP_ENTRY);
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    uspace.map_demand_zero(vaddr, 0x4000, flags).unwrap();
+    assert!(uspace.handle_page_fault(vaddr + 0x1000, flags));
+
+    // 预先映射不是缺页，不消耗预算
+    uspace.set_fault_budget(Some(1));
+    assert_eq!(uspace.populate_range(vaddr, 0x4000, flags), Ok(3));
+    assert_eq!(uspace.fault_budget(), Some(1));
+    assert_eq!(uspace.spurious_faults(), 0);
+    // 之后的访问不再缺页
+    for offset in (0..0x4000).step_by(0x1000) {
+        let (_, pte_flags, _) = uspace.page_table().query(vaddr + offset).unwrap();
+        assert!(pte_flags.contains(flags));
+    }
+    assert_eq!(uspace.populate_range(vaddr, 0x4000, flags), Ok(0));
+    ax_println!("Populate range at {:#x?}: OK", vaddr);
+}
+
+/// Checks that a populated mapping of many pages yields the CPU between the
+/// pages, outside of the traps.
+fn check_populate_yield() {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static YIELDS: AtomicUsize = AtomicUsize::new(0);
+    fn yield_now() {
+        YIELDS.fetch_add(1, Ordering::Relaxed);
+        axtask::yield_now();
+    }
+
+    let vaddr = VirtAddr::from(APP_ENTRY);
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    axmm::set_populate_yield(Some(axmm::PopulateYield { every: 4, yield_now }));
+    uspace.map_alloc(vaddr, 0xa000, flags, true).unwrap();
+    axmm::set_populate_yield(None);
+    assert_eq!(YIELDS.load(Ordering::Relaxed), 2);
+    ax_println!("Populate yield at {:#x?}: OK", vaddr);
+}
+
+/// Checks that the resident pages follow the pages populated by the faults,
+/// and drop on unmapping and teardown.
+fn check_resident_pages() {
+    let vaddr = VirtAddr::from(APP_ENTRY);
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    uspace.map_demand_zero(vaddr, 0x8000, flags).unwrap();
+    assert_eq!(uspace.resident_pages(), 0);
+    for offset in [0, 0x2000, 0x3000, 0x7000] {
+        assert!(uspace.handle_page_fault(vaddr + offset, flags));
+    }
+    // 虚假的缺页不重复计数
+    assert!(uspace.handle_page_fault(vaddr, flags));
+    assert_eq!(uspace.resident_pages(), 4);
+
+    uspace.map_alloc(vaddr + 0x8000, 0x2000, flags, true).unwrap();
+    assert_eq!(uspace.resident_pages(), 6);
+    uspace.unmap(vaddr + 0x2000, 0x2000).unwrap();
+    assert_eq!(uspace.resident_pages(), 4);
+    uspace.clear();
+    assert_eq!(uspace.resident_pages(), 0);
+    ax_println!("Resident pages at {:#x?}: OK", vaddr);
+}
+
+/// Checks that the resident frames are the ones populated by the faults.
+fn check_resident_frames() {
+    let vaddr = VirtAddr::from(APP_ENTRY);
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    uspace.map_demand_zero(vaddr, 0x8000, flags).unwrap();
+    let mut populated = Vec::new();
+    for offset in [0x1000, 0x4000, 0x6000] {
+        assert!(uspace.handle_page_fault(vaddr + offset, flags));
+        populated.push(uspace.page_table().query(vaddr + offset).unwrap().0);
+    }
+
+    let mut frames = Vec::new();
+    uspace.for_each_resident_frame(|paddr| frames.push(paddr));
+    assert_eq!(frames, populated);
+    assert_eq!(frames.len(), uspace.resident_pages());
+    uspace.clear();
+    uspace.for_each_resident_frame(|paddr| panic!("frame {:#x?} left", paddr));
+    ax_println!("Resident frames at {:#x?}: OK", vaddr);
+}
+
+/// Checks that a second fault on a page already populated, as when two CPUs
+/// fault on it at the same time, keeps the first frame.
+fn check_spurious_fault() {
+    let vaddr = VirtAddr::from(APP_ENTRY);
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    uspace.map_demand_zero(vaddr, 0x1000, flags).unwrap();
+
+    assert!(uspace.handle_page_fault(vaddr, flags));
+    let (frame, _, _) = uspace.page_table().query(vaddr).unwrap();
+    uspace.write(vaddr, b"first").unwrap();
+    assert_eq!(uspace.spurious_faults(), 0);
+    assert!(uspace.handle_page_fault(vaddr + 8, flags));
+    assert_eq!(uspace.spurious_faults(), 1);
+    // 没有重新分配页帧，数据保持不变
+    assert_eq!(uspace.page_table().query(vaddr).unwrap().0, frame);
+    let mut buf = [0u8; 5];
+    uspace.read(vaddr, &mut buf).unwrap();
+    assert_eq!(&buf, b"first");
+    ax_println!("Spurious fault at {:#x?}: OK", vaddr);
+}
+
+/// Checks that a write to a read-only mapping reports the missing
+/// permission.
+fn check_protection_fault() {
+    let vaddr = VirtAddr::from(APP_ENTRY);
+    let flags = MappingFlags::READ | MappingFlags::USER;
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    uspace.map_alloc(vaddr, 0x1000, flags, true).unwrap();
+
+    let write = MappingFlags::WRITE | MappingFlags::USER;
+    assert_eq!(
+        uspace.try_handle_page_fault(vaddr, write),
+        Err(axmm::PageFaultError::Protection {
+            required: write,
+            present: flags,
+        })
+    );
+    ax_println!("Protection fault at {:#x?}: OK", vaddr);
+}
+
+/// Checks that a write to a page left read-only in a writable area makes the
+/// page writable in place, keeping its frame and data.
+fn check_permission_upgrade() {
+    let vaddr = VirtAddr::from(APP_ENTRY);
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    uspace.map_alloc(vaddr, 0x1000, flags, true).unwrap();
+    uspace.write(vaddr, b"kept").unwrap();
+    let (frame, _, _) = uspace.page_table().query(vaddr).unwrap();
+    // 只更新页表项，区域仍可写
+    uspace
+        .protect(vaddr, 0x1000, MappingFlags::READ | MappingFlags::USER)
+        .unwrap();
+
+    let write = MappingFlags::WRITE | MappingFlags::USER;
+    assert!(uspace.fault_is_recoverable(vaddr, MappingFlags::WRITE, true));
+    assert_eq!(uspace.try_handle_page_fault(vaddr, write), Ok(()));
+    let (new_frame, pte_flags, _) = uspace.page_table().query(vaddr).unwrap();
+    assert_eq!(new_frame, frame);
+    assert!(pte_flags.contains(flags));
+    let mut buf = [0u8; 4];
+    uspace.read(vaddr, &mut buf).unwrap();
+    assert_eq!(&buf, b"kept");
+    ax_println!("Permission upgrade at {:#x?}: OK", vaddr);
+}
+
+/// Checks that faults are classified as recoverable or fatal before being
+/// handled, without changing the address space.
+fn check_fault_recoverable() {
+    let vaddr = VirtAddr::from(APP_ENTRY);
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    uspace.map_demand_zero(vaddr, 0x1000, flags).unwrap();
+
+    assert!(uspace.fault_is_recoverable(vaddr, MappingFlags::WRITE, true));
+    // 只做判断，不会分配页帧
+    assert!(!uspace.is_populated(vaddr));
+    assert!(uspace.handle_page_fault(vaddr, MappingFlags::WRITE | MappingFlags::USER));
+
+    let unmapped = vaddr + 0x1000;
+    assert!(!uspace.fault_is_recoverable(unmapped, MappingFlags::READ, true));
+    assert!(!uspace.fault_is_recoverable(uspace.end(), MappingFlags::READ, false));
+    ax_println!("Fault classification at {:#x?}: OK", vaddr);
+}
+
+/// Checks that a fault in a task without extended data is reported as an
+/// unhandled kernel fault, instead of panicking in the handler.
+fn check_fault_without_task_ext() {
+    let task = axtask::spawn(|| {
+        let mut ctx = FaultContext::default();
+        let kaddr = VirtAddr::from(0xdead_0000);
+        assert!(!page_fault::handle_page_fault(kaddr, MappingFlags::READ, false, &mut ctx));
+        // 即使标记为用户态缺页，也不会结束任务
+        assert!(!page_fault::handle_page_fault(kaddr, MappingFlags::READ, true, &mut ctx));
+    });
+    assert_eq!(task.join(), Some(0));
+    ax_println!("Fault without task extension: OK");
+}
+
+/// Checks that an unhandled kernel fault is reported as unhandled by default,
+/// and resets the subsystem and terminates the faulting task under the reset
+/// policy.
+fn check_kernel_fault_policy() {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use page_fault::{kernel_fault_policy, set_kernel_fault_policy, KernelFaultPolicy};
+
+    static RESETS: AtomicUsize = AtomicUsize::new(0);
+    fn reset() {
+        RESETS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let kaddr = VirtAddr::from(0xdead_0000);
+    assert!(matches!(kernel_fault_policy(), KernelFaultPolicy::Panic));
+    let task = axtask::spawn(move || {
+        let mut ctx = FaultContext::default();
+        assert!(!page_fault::handle_page_fault(kaddr, MappingFlags::READ, false, &mut ctx));
+    });
+    assert_eq!(task.join(), Some(0));
+    assert_eq!(RESETS.load(Ordering::Relaxed), 0);
+
+    set_kernel_fault_policy(KernelFaultPolicy::Reset(reset));
+    let task = axtask::spawn(move || {
+        let mut ctx = FaultContext::default();
+        page_fault::handle_page_fault(kaddr, MappingFlags::READ, false, &mut ctx);
+        unreachable!("task not terminated under the reset policy");
+    });
+    assert_eq!(task.join(), Some(page_fault::PAGE_FAULT_EXIT_CODE));
+    assert_eq!(RESETS.load(Ordering::Relaxed), 1);
+    set_kernel_fault_policy(KernelFaultPolicy::Panic);
+    ax_println!("Kernel fault policy: OK");
+}
+
+/// Checks that an unrecoverable user fault returns to the fault handler
+/// registered by the task instead of killing it.
+fn check_fault_handler() {
+    const HANDLER: usize = APP_ENTRY + 0x800;
+
+    let vaddr = VirtAddr::from(APP_ENTRY + 0x10_0000);
+    let flags = MappingFlags::READ | MappingFlags::USER;
+    let uspace = Arc::new(Mutex::new(axmm::new_user_aspace().unwrap()));
+    let task = task::spawn_as_user(uspace, move || {
+        let mut ctx = FaultContext::default();
+        let curr = axtask::current();
+        let ext = task::try_task_ext(&curr).unwrap();
+        ext.set_fault_handler(Some(HANDLER));
+        assert!(page_fault::handle_page_fault(vaddr, flags, true, &mut ctx));
+        assert_eq!(ctx.diversion(), Some((HANDLER, vaddr.as_usize())));
+
+        // 没有处理函数时结束任务
+        ext.set_fault_handler(None);
+        page_fault::handle_page_fault(vaddr, flags, true, &mut ctx);
+        unreachable!("task not killed without a fault handler");
+    });
+    assert_eq!(task.join(), Some(page_fault::PAGE_FAULT_EXIT_CODE));
+    ax_println!("Fault handler at {:#x}: OK", HANDLER);
+}
+
+/// Checks that a user fault in the first page is a null pointer dereference
+/// killing the task, even if the page is mapped.
+fn check_null_deref() {
+    use axhal::mem::PAGE_SIZE_4K;
+    use page_fault::{is_null_deref, set_null_guard_size};
+
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    uspace.map_demand_zero(VirtAddr::from(0), 2 * PAGE_SIZE_4K, flags).unwrap();
+    let uspace = Arc::new(Mutex::new(uspace));
+    for vaddr in [0, PAGE_SIZE_4K - 1] {
+        let vaddr = VirtAddr::from(vaddr);
+        assert!(is_null_deref(vaddr));
+        let task = task::spawn_as_user(uspace.clone(), move || {
+            let mut ctx = FaultContext::default();
+            page_fault::handle_page_fault(vaddr, flags, true, &mut ctx);
+            unreachable!("task not killed on a null pointer dereference");
+        });
+        assert_eq!(task.join(), Some(page_fault::PAGE_FAULT_EXIT_CODE));
+    }
+    // 零页没有被映射
+    assert!(!uspace.lock().is_populated(VirtAddr::from(0)));
+    assert!(!is_null_deref(VirtAddr::from(PAGE_SIZE_4K)));
+
+    // 扩大检查范围
+    set_null_guard_size(2 * PAGE_SIZE_4K);
+    assert!(is_null_deref(VirtAddr::from(PAGE_SIZE_4K)));
+    set_null_guard_size(PAGE_SIZE_4K);
+    ax_println!("Null pointer dereference: OK");
+}
+
+/// Checks that a fault in a region with 2M fault granularity maps the whole
+/// huge page, which covers the following accesses to other 4K pages in it.
+fn check_huge_fault() {
+    let vaddr = VirtAddr::from(0x20_0000);
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    uspace
+        .map_demand_zero_with(vaddr, 0x40_0000, flags, PageSize::Size2M)
+        .unwrap();
+
+    assert!(uspace.handle_page_fault(vaddr + 0x3000, flags));
+    let (_, _, page_size) = uspace.page_table().query(vaddr).unwrap();
+    assert_eq!(page_size, PageSize::Size2M);
+    // 同一大页内的其他 4K 页不再分配页帧
+    for offset in [0, 0x1000, 0x1f_f000] {
+        assert!(uspace.handle_page_fault(vaddr + offset, flags));
+    }
+    assert_eq!(uspace.spurious_faults(), 3);
+    uspace.write(vaddr + 0x1f_f000, b"huge").unwrap();
+    let mut buf = [0u8; 4];
+    uspace.read(vaddr + 0x1f_f000, &mut buf).unwrap();
+    assert_eq!(&buf, b"huge");
+    // 第二个大页尚未映射
+    assert!(!uspace.is_populated(vaddr + 0x20_0000));
+    ax_println!("Huge page fault at {:#x?}: OK", vaddr);
+}
+
+/// Checks that a protection fault on a huge page splits it, so that only
+/// the faulting 4K page gets the permissions of the area.
+fn check_split_huge_page() {
+    let vaddr = VirtAddr::from(0x20_0000);
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let read_only = MappingFlags::READ | MappingFlags::USER;
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    uspace
+        .map_demand_zero_with(vaddr, 0x20_0000, flags, PageSize::Size2M)
+        .unwrap();
+    assert!(uspace.handle_page_fault(vaddr, flags));
+    uspace.write(vaddr + 0x4000, b"huge").unwrap();
+    uspace.protect(vaddr, 0x20_0000, read_only).unwrap();
+    let (frame, _, page_size) = uspace.page_table().query(vaddr).unwrap();
+    assert_eq!(page_size, PageSize::Size2M);
+
+    let fault = vaddr + 0x3000;
+    assert!(uspace.fault_is_recoverable(fault, MappingFlags::WRITE, true));
+    assert!(uspace.handle_page_fault(fault, MappingFlags::WRITE | MappingFlags::USER));
+    let (paddr, pte_flags, page_size) = uspace.page_table().query(fault).unwrap();
+    assert_eq!((paddr, pte_flags, page_size), (frame + 0x3000, flags, PageSize::Size4K));
+    // 相邻的 4K 页仍映射到原来的页帧，权限不变
+    for offset in [0, 0x2000, 0x4000, 0x1f_f000] {
+        let (paddr, pte_flags, page_size) = uspace.page_table().query(vaddr + offset).unwrap();
+        assert_eq!(
+            (paddr, pte_flags, page_size),
+            (frame + offset, read_only, PageSize::Size4K)
+        );
+    }
+    let mut buf = [0u8; 4];
+    uspace.read(vaddr + 0x4000, &mut buf).unwrap();
+    assert_eq!(&buf, b"huge");
+    ax_println!("Split huge page at {:#x?}: OK", fault);
+}
+
+/// Checks that a fault in a region with a backing source maps the frame
+/// given by the source, with its flags, instead of allocating memory.
+fn check_map_at_fault() {
+    const MMIO_BASE: usize = 0xfe00_0000;
+    // 只读的设备寄存器
+    const DEVICE_FLAGS: MappingFlags = MappingFlags::READ
+        .union(MappingFlags::USER)
+        .union(MappingFlags::DEVICE);
+    fn mmio(offset: usize) -> Option<(axhal::mem::PhysAddr, MappingFlags)> {
+        // 只有前两页有寄存器
+        (offset < 0x2000).then(|| ((MMIO_BASE + offset).into(), DEVICE_FLAGS))
+    }
+
+    let vaddr = VirtAddr::from(APP_ENTRY);
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    uspace.map_at_fault(vaddr, 0x4000, flags, mmio).unwrap();
+    assert!(!uspace.is_populated(vaddr));
+
+    let read = MappingFlags::READ | MappingFlags::USER;
+    assert!(uspace.handle_page_fault(vaddr + 0x1008, read));
+    let (paddr, pte_flags, _) = uspace.page_table().query(vaddr + 0x1000).unwrap();
+    assert_eq!(paddr.as_usize(), MMIO_BASE + 0x1000);
+    // 使用来源给出的权限，而不是区域的权限
+    assert!(pte_flags.contains(read) && !pte_flags.contains(MappingFlags::WRITE));
+    assert_eq!(
+        uspace.try_handle_page_fault(vaddr + 0x2000, read),
+        Err(axmm::PageFaultError::NoRegion)
+    );
+    uspace.unmap(vaddr, 0x4000).unwrap();
+    assert!(!uspace.is_populated(vaddr + 0x1000));
+    ax_println!("Map at fault at {:#x?}: OK", vaddr);
+}
+
+/// Checks that faults in a registered lazy region are only serviced the way
+/// the region says, and with the accesses it allows.
+fn check_lazy_region() {
+    use axmm::{LazyKind, PageFaultError};
+
+    let vaddr = VirtAddr::from(APP_ENTRY);
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let read = MappingFlags::READ | MappingFlags::USER;
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    uspace.map_demand_zero(vaddr, 0x3000, flags).unwrap();
+    uspace
+        .add_lazy_region(vaddr, 0x1000, read, LazyKind::DemandZero)
+        .unwrap();
+    // 相邻区域的服务方式与映射不符
+    uspace
+        .add_lazy_region(vaddr + 0x1000, 0x1000, flags, LazyKind::Device)
+        .unwrap();
+    assert!(uspace
+        .add_lazy_region(vaddr + 0x800, 0x1000, flags, LazyKind::Cow)
+        .is_err());
+    let region = uspace.lazy_region(vaddr + 0x1000).unwrap();
+    assert_eq!(region.kind, LazyKind::Device);
+
+    // 区域只允许读
+    assert!(matches!(
+        uspace.try_handle_page_fault(vaddr, flags),
+        Err(PageFaultError::Protection { .. })
+    ));
+    assert!(uspace.handle_page_fault(vaddr, read));
+    assert_eq!(
+        uspace.try_handle_page_fault(vaddr + 0x1000, read),
+        Err(PageFaultError::Unhandled)
+    );
+    // 未登记的地址只由区域处理
+    assert!(uspace.handle_page_fault(vaddr + 0x2000, flags));
+    uspace.remove_lazy_region(vaddr + 0x1000, 0x1000).unwrap();
+    assert!(uspace.handle_page_fault(vaddr + 0x1000, read));
+    ax_println!("Lazy region at {:#x?}: OK", vaddr);
+}
+
+/// Checks that a task demand paging beyond the budget of its address space is
+/// killed, while the faults not mapping new pages are not limited.
+fn check_fault_budget() {
+    use axmm::PageFaultError;
+    use page_fault::{fault_action, resolve_page_fault, FaultAction};
+
+    let vaddr = VirtAddr::from(APP_ENTRY);
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    uspace.map_demand_zero(vaddr, 0x4000, flags).unwrap();
+    uspace.set_fault_budget(Some(2));
+    let uspace = Arc::new(Mutex::new(uspace));
+
+    assert_eq!(resolve_page_fault(Some(&uspace), vaddr, flags, true), Ok(()));
+    // 虚假的缺页不消耗预算
+    assert_eq!(resolve_page_fault(Some(&uspace), vaddr + 8, flags, true), Ok(()));
+    assert_eq!(uspace.lock().fault_budget(), Some(1));
+    let next = vaddr + 0x1000;
+    assert_eq!(resolve_page_fault(Some(&uspace), next, flags, true), Ok(()));
+    assert_eq!(uspace.lock().fault_budget(), Some(0));
+    let over = vaddr + 0x2000;
+    assert!(!uspace.lock().fault_is_recoverable(over, flags, true));
+    let res = resolve_page_fault(Some(&uspace), over, flags, true);
+    assert_eq!(res, Err(PageFaultError::BudgetExceeded));
+    assert_eq!(fault_action(PageFaultError::BudgetExceeded, true), FaultAction::Kill);
+    assert!(!uspace.lock().is_populated(over));
+
+    // 用户任务超出预算时被结束，不影响内核
+    let task = task::spawn_as_user(uspace.clone(), move || {
+        let mut ctx = FaultContext::default();
+        page_fault::handle_page_fault(over, flags, true, &mut ctx);
+        unreachable!("task not killed on exceeding its paging budget");
+    });
+    assert_eq!(task.join(), Some(page_fault::PAGE_FAULT_EXIT_CODE));
+    uspace.lock().set_fault_budget(None);
+    assert_eq!(resolve_page_fault(Some(&uspace), over, flags, true), Ok(()));
+    ax_println!("Fault budget at {:#x?}: OK", vaddr);
+}
+
+/// Checks that the fault log names the region containing the fault address.
+fn check_region_name() {
+    use fault_log::FaultSite;
+
+    let vaddr = VirtAddr::from(APP_ENTRY);
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    uspace.map_demand_zero(vaddr, 0x4000, flags).unwrap();
+    uspace.name_region(vaddr, 0x2000, "user-heap").unwrap();
+    uspace.name_region(vaddr + 0x10_0000, 0x1000, "mmio:uart").unwrap();
+    assert_eq!(uspace.region_name(vaddr + 0x1fff), "user-heap");
+    assert_eq!(uspace.region_name(vaddr + 0x2000), "anon");
+    assert_eq!(uspace.region_name(vaddr + 0x4000), "unmapped");
+    // 未映射的区域同样可以命名
+    assert_eq!(uspace.region_name(vaddr + 0x10_0000), "mmio:uart");
+    assert!(uspace.name_region(uspace.end(), 0x1000, "bad").is_err());
+    let region = uspace.region_of(vaddr + 0x1000).unwrap();
+    assert_eq!(region.range.start, vaddr);
+    assert_eq!(region.range.size(), 0x2000);
+    assert!(uspace.region_of(vaddr + 0x2000).is_none());
+
+    let site = FaultSite {
+        vaddr: vaddr.as_usize() + 8,
+        region: uspace.region_name(vaddr + 8),
+    };
+    assert_eq!(alloc::format!("{}", site), "0x1008 in user-heap");
+    assert!(uspace.handle_page_fault(vaddr + 8, flags));
+
+    // 区域表每个映射区域一行，未映射的命名区域不列出
+    assert_eq!(
+        alloc::format!("{}", page_fault::RegionMap(&uspace)),
+        "0x0000000000001000-0x0000000000005000 rw-u user-heap\n"
+    );
+    ax_println!("Region name at {:#x?}: OK", vaddr);
+}
+
+/// Checks that the pages of a populate-once region are populated by their
+/// first faults, and that a task faulting on them again after the region is
+/// torn down is killed.
+fn check_populate_once() {
+    use axmm::PageFaultError;
+    use page_fault::resolve_page_fault;
+
+    let vaddr = VirtAddr::from(APP_ENTRY);
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    uspace.map_demand_zero(vaddr, 0x2000, flags).unwrap();
+    uspace.set_populate_once(vaddr, 0x2000).unwrap();
+    assert!(uspace.set_populate_once(vaddr + 0x800, 0x1000).is_err());
+    let uspace = Arc::new(Mutex::new(uspace));
+
+    // 第一次填充正常处理，虚假的缺页不受影响
+    assert_eq!(resolve_page_fault(Some(&uspace), vaddr, flags, true), Ok(()));
+    assert_eq!(resolve_page_fault(Some(&uspace), vaddr + 8, flags, true), Ok(()));
+    let next = vaddr + 0x1000;
+    assert_eq!(resolve_page_fault(Some(&uspace), next, flags, true), Ok(()));
+
+    // 拆除后重建区域，再次缺页说明逻辑错误
+    {
+        let mut uspace = uspace.lock();
+        uspace.unmap(vaddr, 0x2000).unwrap();
+        uspace.map_demand_zero(vaddr, 0x2000, flags).unwrap();
+        assert!(!uspace.fault_is_recoverable(next, flags, true));
+    }
+    let res = resolve_page_fault(Some(&uspace), next, flags, true);
+    assert_eq!(res, Err(PageFaultError::Repopulated));
+    assert!(!uspace.lock().is_populated(next));
+    let task = task::spawn_as_user(uspace, move || {
+        let mut ctx = FaultContext::default();
+        page_fault::handle_page_fault(vaddr, flags, true, &mut ctx);
+        unreachable!("task not killed on populating a page again");
+    });
+    assert_eq!(task.join(), Some(page_fault::PAGE_FAULT_EXIT_CODE));
+    ax_println!("Populate-once region at {:#x?}: OK", vaddr);
+}
+
+/// Checks the recovery branches of the page fault handler with injected
+/// faults.
+#[cfg(feature = "fault-inject")]
+fn check_fault_inject() {
+    use axmm::inject::{inject_oom, inject_oom_after, inject_page_fault, inject_table_oom_after};
+    use axmm::PageFaultError;
+    use page_fault::{fault_action, resolve_page_fault, FaultAction};
+
+    let vaddr = VirtAddr::from(APP_ENTRY);
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    let mut uspace = axmm::new_user_aspace().unwrap();
+    uspace.map_demand_zero(vaddr, 0x1000, flags).unwrap();
+    let uspace = Mutex::new(uspace);
+
+    // 按需分配时内存不足，重试后成功
+    inject_oom();
+    let res = resolve_page_fault(Some(&uspace), vaddr, flags, true);
+    assert_eq!(res, Err(PageFaultError::NoMemory));
+    assert_eq!(fault_action(PageFaultError::NoMemory, true), FaultAction::Retry);
+    assert_eq!(resolve_page_fault(Some(&uspace), vaddr, flags, true), Ok(()));
+
+    // 强制失败，结束任务
+    inject_page_fault(PageFaultError::PermissionDenied);
+    let err = resolve_page_fault(Some(&uspace), vaddr, flags, true).unwrap_err();
+    assert_eq!(fault_action(err, true), FaultAction::Kill);
+
+    // 预先填充时内存不足，立即停止
+    let mut uspace = uspace.into_inner();
+    uspace.map_demand_zero(vaddr + 0x1000, 0x2000, flags).unwrap();
+    inject_oom();
+    let res = uspace.populate_range(vaddr + 0x1000, 0x2000, flags);
+    assert_eq!(res, Err(PageFaultError::NoMemory));
+
+    // 大页分配失败时退回到 4K 页
+    let huge = VirtAddr::from(0x20_0000);
+    uspace
+        .map_demand_zero_with(huge, 0x20_0000, flags, PageSize::Size2M)
+        .unwrap();
+    inject_oom();
+    assert!(uspace.handle_page_fault(huge, flags));
+    let (_, _, page_size) = uspace.page_table().query(huge).unwrap();
+    assert_eq!(page_size, PageSize::Size4K);
+    assert!(!uspace.is_populated(huge + 0x1000));
+
+    // 填充映射全部成功，然后在中途内存不足时回滚，不泄漏物理页
+    let populated = VirtAddr::from(0x40_0000);
+    uspace.map_alloc(populated, 0x4000, flags, true).unwrap();
+    for i in 0..4 {
+        assert!(uspace.is_populated(populated + i * 0x1000));
+    }
+    uspace.unmap(populated, 0x4000).unwrap();
+    let used_pages = axalloc::global_allocator().used_pages();
+    inject_oom_after(2);
+    assert!(uspace.map_alloc(populated, 0x4000, flags, true).is_err());
+    assert_eq!(axalloc::global_allocator().used_pages(), used_pages);
+    for i in 0..4 {
+        assert!(!uspace.is_populated(populated + i * 0x1000));
+    }
+
+    // 建立中间页表时内存不足，不留下建了一半的页表
+    let far = VirtAddr::from(0x4000_0000);
+    uspace
+        .map_demand_zero_with(far, 0x20_0000, flags, PageSize::Size2M)
+        .unwrap();
+    let used_pages = axalloc::global_allocator().used_pages();
+    inject_oom(); // 退回到 4K 页
+    inject_table_oom_after(1);
+    let res = uspace.try_handle_page_fault(far, flags);
+    assert_eq!(res, Err(PageFaultError::PageTableOom));
+    assert_eq!(fault_action(PageFaultError::PageTableOom, true), FaultAction::Retry);
+    assert_eq!(axalloc::global_allocator().used_pages(), used_pages);
+    assert!(!uspace.is_populated(far));
+    assert_eq!(uspace.try_handle_page_fault(far, flags), Ok(()));
+    ax_println!("Fault injection: OK");
+}
+
+/// Checks what is done with each error of an unresolved fault, and how the
+/// trap returns from it.
+fn check_fault_action() {
+    use axmm::PageFaultError::*;
+    use page_fault::{fault_action, FaultAction, FaultOutcome, FaultPc, FaultReturn};
+
+    for err in [NoMemory, PageTableOom, Busy] {
+        assert_eq!(fault_action(err, true), FaultAction::Retry);
+    }
+    let protection = Protection {
+        required: MappingFlags::WRITE,
+        present: MappingFlags::READ,
+    };
+    for err in [NoRegion, GuardPage, PermissionDenied, Unhandled, BudgetExceeded, Repopulated] {
+        assert_eq!(fault_action(err, true), FaultAction::Kill);
+    }
+    assert_eq!(fault_action(protection, true), FaultAction::Kill);
+    for err in [NoRegion, GuardPage, PermissionDenied, NoMemory, Busy, Unhandled, protection] {
+        assert_eq!(fault_action(err, false), FaultAction::Panic);
+    }
+
+    let retry = Some(FaultReturn::Retry);
+    assert_eq!(FaultOutcome::Handled.trap_return(), retry);
+    assert_eq!(FaultOutcome::Failed(Busy, FaultAction::Retry).trap_return(), retry);
+    // 任务被结束，不返回到陷入处
+    assert_eq!(FaultOutcome::Failed(NoRegion, FaultAction::Kill).trap_return(), None);
+    let outcome = FaultOutcome::Failed(Unhandled, FaultAction::Panic);
+    assert_eq!(outcome.trap_return(), Some(FaultReturn::Fatal));
+    assert!(FaultReturn::Retry.is_handled());
+    assert!(!FaultReturn::Fatal.is_handled());
+
+    assert_eq!(FaultPc(Some(0x8020_0000)).to_string(), "0x80200000");
+    assert_eq!(FaultPc(None).to_string(), "unavailable");
+    ax_println!("Fault actions: OK");
+}
+
+/// Checks the outcome of faults resolved by an address space, or rejected
+/// before reaching it.
+fn check_resolve_fault() {
+    use axmm::{PageFaultError::*, PageFaultResult};
+    use page_fault::{resolve_fault, FaultAction, FaultAspace, FaultOutcome};
+
+    /// The end of the user addresses in [`MockAspace`].
+    const MOCK_USER_END: usize = 0x8000;
+
+    /// An address space covering `[0x1000, 0x4000)` that resolves every
+    /// fault with `result`.
+    struct MockAspace {
+        result: PageFaultResult,
+        faults: usize,
+    }
+
+    impl FaultAspace for MockAspace {
+        fn contains(&self, vaddr: VirtAddr) -> bool {
+            (0x1000..0x4000).contains(&vaddr.as_usize())
+        }
+
+        fn is_user_addr(&self, vaddr: VirtAddr) -> bool {
+            vaddr.as_usize() < MOCK_USER_END
+        }
+
+        fn handle_fault(&mut self, _vaddr: VirtAddr, _flags: MappingFlags) -> PageFaultResult {
+            self.faults += 1;
+            self.result
+        }
+    }
+
+    let vaddr = VirtAddr::from(0x2000);
+    let flags = MappingFlags::WRITE | MappingFlags::USER;
+    let mut aspace = MockAspace { result: Ok(()), faults: 0 };
+    assert_eq!(resolve_fault(&mut aspace, vaddr, flags, true), FaultOutcome::Handled);
+    // 内核访问用户地址同样处理
+    let outcome = resolve_fault(&mut aspace, vaddr, MappingFlags::READ, false);
+    assert_eq!(outcome, FaultOutcome::Handled);
+    // 内核地址不交给地址空间处理
+    let kaddr = VirtAddr::from(MOCK_USER_END);
+    let outcome = resolve_fault(&mut aspace, kaddr, flags, true);
+    assert_eq!(outcome, FaultOutcome::Failed(NoRegion, FaultAction::Kill));
+    let outcome = resolve_fault(&mut aspace, kaddr, MappingFlags::READ, false);
+    assert_eq!(outcome, FaultOutcome::Failed(NoRegion, FaultAction::Panic));
+    assert_eq!(aspace.faults, 2);
+
+    let mut aspace = MockAspace { result: Err(NoMemory), faults: 0 };
+    let outcome = resolve_fault(&mut aspace, vaddr, flags, true);
+    assert_eq!(outcome, FaultOutcome::Failed(NoMemory, FaultAction::Retry));
+    let mut aspace = MockAspace { result: Err(Unhandled), faults: 0 };
+    let outcome = resolve_fault(&mut aspace, vaddr, MappingFlags::READ, false);
+    assert_eq!(outcome, FaultOutcome::Failed(Unhandled, FaultAction::Panic));
+    ax_println!("Fault resolution: OK");
+}
+
+/// Checks that the address space of a fault is locked with bounded retries.
+fn check_lock_for_fault() {
+    use axmm::PageFaultError;
+    use core::cell::Cell;
+    use page_fault::{lock_for_fault, with_fault_aspace, FaultLock, LockFailure, TryLock};
+    use page_fault::{FaultAction, FaultOutcome, FaultReturn, ASPACE_LOCK_RETRIES};
+
+    /// A lock that is held for the first `held` attempts, or forever.
+    struct MockLock {
+        held: Option<usize>,
+        attempts: Cell<usize>,
+    }
+
+    impl MockLock {
+        fn new(held: Option<usize>) -> Self {
+            Self {
+                held,
+                attempts: Cell::new(0),
+            }
+        }
+    }
+
+    impl TryLock for MockLock {
+        type Guard<'a> = ();
+
+        fn try_lock(&self) -> Option<()> {
+            let attempt = self.attempts.get();
+            self.attempts.set(attempt + 1);
+            self.held.is_some_and(|held| attempt >= held).then_some(())
+        }
+    }
+
+    impl FaultLock for MockLock {
+        type Target = ();
+
+        fn with_guard<R>(_guard: (), f: impl FnOnce(&mut ()) -> R) -> R {
+            f(&mut ())
+        }
+    }
+
+    // 被其他任务短暂持有，让出后可以获得
+    let lock = MockLock::new(Some(3));
+    let mut relaxed = 0;
+    assert_eq!(lock_for_fault(&lock, true, || relaxed += 1), Ok(()));
+    assert_eq!((lock.attempts.get(), relaxed), (4, 3));
+    // 内核缺页只尝试一次
+    let lock = MockLock::new(Some(1));
+    let res = with_fault_aspace(&lock, false, || unreachable!(), |_| 1);
+    assert_eq!(res, Err(LockFailure::Busy));
+    assert_eq!(lock.attempts.get(), 1);
+    let lock = MockLock::new(Some(0));
+    assert_eq!(with_fault_aspace(&lock, true, || unreachable!(), |_| 2), Ok(2));
+
+    // 锁一直被持有，有限次尝试后升级为致命错误
+    let lock = MockLock::new(None);
+    let mut relaxed = 0;
+    let res = lock_for_fault(&lock, true, || relaxed += 1);
+    assert_eq!(res, Err(LockFailure::Stuck));
+    assert_eq!((lock.attempts.get(), relaxed), (ASPACE_LOCK_RETRIES, ASPACE_LOCK_RETRIES - 1));
+    let outcome = LockFailure::Stuck.outcome(true);
+    assert_eq!(outcome, FaultOutcome::Failed(PageFaultError::Busy, FaultAction::Panic));
+    assert_eq!(outcome.trap_return(), Some(FaultReturn::Fatal));
+    assert_eq!(
+        LockFailure::Busy.outcome(true),
+        FaultOutcome::Failed(PageFaultError::Busy, FaultAction::Retry)
+    );
+    ax_println!("Fault aspace lock: OK");
+}
+
+/// Checks that the log of repeated faults at the same page is rate-limited.
+fn check_fault_log() {
+    use fault_log::{FaultLogLimiter, FaultSite, Suppressed, RING_SIZE};
+
+    let mut log = FaultLogLimiter::new(4);
+    assert_eq!(log.record(0x1000, true), Some(0));
+    for _ in 0..3 {
+        assert_eq!(log.record(0x1000, true), None);
+    }
+    assert_eq!(log.record(0x1000, true), Some(3));
+    // 不同的页或特权级分别计数
+    assert_eq!(log.record(0x2000, true), Some(0));
+    assert_eq!(log.record(0x1000, false), Some(0));
+
+    let mut log = FaultLogLimiter::new(4);
+    for i in 0..=RING_SIZE {
+        assert_eq!(log.record(i * 0x1000, true), Some(0));
+    }
+    // 最早的记录已被替换
+    assert_eq!(log.record(0, true), Some(0));
+    assert_eq!(log.record(RING_SIZE * 0x1000, true), None);
+
+    assert_eq!(Suppressed(0).to_string(), "");
+    assert_eq!(Suppressed(1234).to_string(), " (x1234 suppressed)");
+    let site = FaultSite {
+        vaddr: 0xdead_0000,
+        region: "unmapped",
+    };
+    assert_eq!(site.to_string(), "0xdead0000 in unmapped");
+    ax_println!("Fault log: OK");
+}
+
+/// Checks the counters of the page fault statistics.
+fn check_fault_stats() {
+    use fault_stats::{PageFaultSnapshot, PageFaultStats};
+
+    let stats = PageFaultStats::new();
+    assert_eq!(stats.snapshot(), PageFaultSnapshot::default());
+    stats.record(true, true);
+    stats.record(true, false);
+    stats.record(false, false);
+    let expected = PageFaultSnapshot {
+        total: 3,
+        user: 2,
+        kernel: 1,
+        handled: 1,
+        failed: 2,
+        ..Default::default()
+    };
+    assert_eq!(stats.snapshot(), expected);
+
+    let stats = PageFaultStats::new();
+    assert_eq!(stats.snapshot().avg_populate_ticks(), None);
+    stats.record_populate(100, 130);
+    stats.record_populate(200, 290);
+    // 倒退的时钟按 0 计
+    stats.record_populate(300, 299);
+    let snapshot = stats.snapshot();
+    assert_eq!((snapshot.populated, snapshot.populate_ticks), (3, 120));
+    assert_eq!(snapshot.avg_populate_ticks(), Some(40));
+    assert_eq!(snapshot.total, 0);
+    ax_println!("Fault statistics: OK");
+}
+
+/// Checks that the fault trace keeps the last faults, from the oldest.
+fn check_fault_trace() {
+    use fault_trace::{FaultEvent, FaultTrace, TraceOutcome};
+
+    fn event(vaddr: usize, outcome: TraceOutcome) -> FaultEvent {
+        FaultEvent {
+            vaddr,
+            flags: MappingFlags::WRITE | MappingFlags::USER,
+            is_user: vaddr % 0x2000 == 0,
+            outcome,
+        }
+    }
+
+    let trace = FaultTrace::<4>::new();
+    assert_eq!(trace.to_string(), "");
+    for i in 0..6 {
+        trace.record(event(i * 0x1000, TraceOutcome::ALL[i % 4]));
+    }
+    let mut events = Vec::new();
+    trace.for_each(|seq, event| events.push((seq, event)));
+    let expected: Vec<_> = (2..6)
+        .map(|i| (i, event(i * 0x1000, TraceOutcome::ALL[i % 4])))
+        .collect();
+    assert_eq!(events, expected);
+
+    let dump = trace.to_string();
+    let lines: Vec<_> = dump.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with("#2: vaddr=0x2000, "));
+    assert!(lines[0].ends_with(", is_user=true, Kill"));
+    assert!(lines[3].ends_with(", is_user=false, Retry"));
+    ax_println!("Fault trace: OK");
+}
+
+/// Checks that a task re-taking the same fault is detected, whether the
+/// fault is resolved or keeps failing.
+fn check_fault_storm() {
+    use fault_storm::{FaultStorm, FAULT_RETRY_LIMIT};
+
+    const W: MappingFlags = MappingFlags::WRITE;
+    let mut storm = FaultStorm::new(3);
+    assert!(!storm.record(0x1000, W, true));
+    assert!(!storm.record(0x1000, W, true));
+    assert!(storm.record(0x1000, W, true));
+    // 地址、访问类型变化或处理失败说明有进展
+    assert!(!storm.record(0x1008, W, true));
+    assert!(!storm.record(0x1008, MappingFlags::READ, true));
+    assert!(!storm.record(0x1008, MappingFlags::READ, false));
+    assert!(!storm.record(0x1008, MappingFlags::READ, true));
+    storm.set_threshold(0);
+    for _ in 0..100 {
+        assert!(!storm.record(0x1000, W, true));
+    }
+
+    for _ in 1..FAULT_RETRY_LIMIT {
+        assert!(!storm.record(0x2000, W, false));
+        assert!(!storm.retries_exhausted());
+    }
+    assert!(!storm.record(0x2000, W, false));
+    assert!(storm.retries_exhausted());
+    // 换了位置重新计数
+    storm.record(0x3000, W, false);
+    assert!(!storm.retries_exhausted());
+    ax_println!("Fault storm: OK");
+}
+
+/// Checks the reports of instruction fetch faults.
+fn check_exec_fault() {
+    use axmm::PageFaultError;
+    use exec_fault::ExecFault;
+
+    const X: MappingFlags = MappingFlags::EXECUTE;
+    let err = PageFaultError::NoRegion;
+    assert_eq!(ExecFault::decode(0x1000, MappingFlags::READ, Some(0x2000), err), None);
+    let fault = ExecFault::decode(0x1000, X | MappingFlags::USER, Some(0x1000), err).unwrap();
+    assert!(fault.is_jump() && !fault.is_wx_violation());
+    assert_eq!(
+        fault.to_string(),
+        "instruction fetch fault at 0x1000, pc=0x1000 (jumped to the fault address)"
+    );
+    // 没有 pc 时无法判断是否跳转
+    let fault = ExecFault::decode(0x1000, X, None, err).unwrap();
+    assert!(!fault.is_jump());
+    assert_eq!(fault.to_string(), "instruction fetch fault at 0x1000, pc=unavailable");
+
+    let present = MappingFlags::READ | MappingFlags::WRITE;
+    let err = PageFaultError::Protection { required: X, present };
+    // 指令跨越页边界，pc 不等于出错地址
+    let fault = ExecFault::decode(0x4000, X, Some(0x3ffe), err).unwrap();
+    assert!(!fault.is_jump() && fault.is_wx_violation());
+    let suffix = format!(", W^X violation: {:?}", present);
+    assert!(fault.to_string().ends_with(&suffix));
+    ax_println!("Exec fault report: OK");
+}
+
+/// Checks the format of the register dumps.
+fn check_reg_dump() {
+    use crate::reg_dump::RegDump;
+
+    let regs: Vec<usize> = (0..6).collect();
+    let dump = RegDump {
+        cause: "page_fault",
+        pc: Some(0x8020_0000),
+        addr: Some(0x4000),
+        regs: &regs,
+    };
+    let text = dump.to_string();
+    let lines: Vec<&str> = text.lines().collect();
+    // 每行都能按前缀检索
+    assert!(lines.iter().all(|line| line.starts_with("REGDUMP ")));
+    assert_eq!(
+        lines[0],
+        "REGDUMP cause=page_fault pc=0x0000000080200000 addr=0x0000000000004000"
+    );
+    assert_eq!(lines[2], "REGDUMP r04=0x0000000000000004 r05=0x0000000000000005");
+    assert_eq!(lines[3], "REGDUMP end");
+
+    let dump = RegDump {
+        cause: "page_fault",
+        pc: None,
+        addr: None,
+        regs: &[],
+    };
+    assert_eq!(dump.to_string(), "REGDUMP cause=page_fault pc=unavailable\nREGDUMP end");
+    ax_println!("Register dump: OK");
+}
+
+/// Checks that the address space overrides are kept per task.
+fn check_aspace_overrides() {
+    use active_aspace::AspaceOverrides;
+
+    let mut overrides = AspaceOverrides::new();
+    assert_eq!(overrides.set(1, "borrowed"), None);
+    assert_eq!(overrides.get(1), Some("borrowed"));
+    assert_eq!(overrides.get(2), None);
+    assert_eq!(overrides.set(1, "other"), Some("borrowed"));
+    assert_eq!(overrides.clear(1), Some("other"));
+    assert_eq!(overrides.clear(1), None);
+    ax_println!("Aspace overrides: OK");
+}
@@ -4,6 +4,9 @@ use axhal::arch::UspaceContext;
 use axmm::AddrSpace;
 use axsync::Mutex;
 use axtask::{AxTaskRef, TaskExtRef, TaskInner};
+use kspin::SpinNoIrq;
+
+use crate::fault_storm::{FaultStorm, FAULT_STORM_THRESHOLD};
 
 /// Task extended data for the monolithic kernel.
 pub struct TaskExt {
@@ -13,6 +16,11 @@ pub struct TaskExt {
     pub uctx: UspaceContext,
     /// The virtual memory address space.
     pub aspace: Arc<Mutex<AddrSpace>>,
+    /// The last page faults, to detect the task getting stuck on one.
+    pub fault_storm: SpinNoIrq<FaultStorm>,
+    /// The user entry of the handler of the unrecoverable page faults, see
+    /// [`set_fault_handler`](Self::set_fault_handler).
+    fault_handler: SpinNoIrq<Option<usize>>,
 }
 
 impl TaskExt {
@@ -21,12 +29,35 @@ impl TaskExt {
             proc_id: 1,
             uctx,
             aspace,
+            fault_storm: SpinNoIrq::new(FaultStorm::new(FAULT_STORM_THRESHOLD)),
+            fault_handler: SpinNoIrq::new(None),
         }
     }
+
+    /// Registers the user function that an unrecoverable page fault of the
+    /// task returns to, with the fault address as the argument, instead of
+    /// killing the task. `None` removes it.
+    pub fn set_fault_handler(&self, entry: Option<usize>) {
+        *self.fault_handler.lock() = entry;
+    }
+
+    /// Returns the handler registered by [`set_fault_handler`](Self::set_fault_handler).
+    pub fn fault_handler(&self) -> Option<usize> {
+        *self.fault_handler.lock()
+    }
 }
 
 axtask::def_task_ext!(TaskExt);
 
+/// Returns the extended data of `task`, or `None` if it has not been
+/// initialized, e.g., for kernel tasks.
+pub fn try_task_ext(task: &TaskInner) -> Option<&TaskExt> {
+    if unsafe { task.task_ext_ptr() }.is_null() {
+        return None;
+    }
+    Some(task.task_ext())
+}
+
 pub fn spawn_user_task(aspace: Arc<Mutex<AddrSpace>>, uctx: UspaceContext) -> AxTaskRef {
     let mut task = TaskInner::new(
         || {
@@ -48,3 +79,17 @@ pub fn spawn_user_task(aspace: Arc<Mutex<AddrSpace>>, uctx: UspaceContext) -> Ax
     task.init_task_ext(TaskExt::new(uctx, aspace));
     axtask::spawn_task(task)
 }
+
+/// Spawns a kernel task running `f` with the extended data of a user task on
+/// `aspace`, so that its page faults are handled as the ones of a user task,
+/// e.g. to check that a faulting task is killed.
+#[cfg(feature = "selftest")]
+pub fn spawn_as_user<F>(aspace: Arc<Mutex<AddrSpace>>, f: F) -> AxTaskRef
+where
+    F: FnOnce() + Send + 'static,
+{
+    let mut task = TaskInner::new(f, "as_user".into(), crate::KERNEL_STACK_SIZE);
+    let uctx = UspaceContext::new(0, 0.into());
+    task.init_task_ext(TaskExt::new(uctx, aspace));
+    axtask::spawn_task(task)
+}
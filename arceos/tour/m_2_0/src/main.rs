@@ -21,7 +21,7 @@ use alloc::sync::Arc;
 use axmm::AddrSpace;
 use loader::load_user_app;
 use axtask::TaskExtRef;
-use axhal::trap::{register_trap_handler, PAGE_FAULT};
+use axhal::trap::{register_trap_handler, FaultContext, PAGE_FAULT};
 
 const USER_STACK_SIZE: usize = 0x10000;
 const KERNEL_STACK_SIZE: usize = 0x40000; // 256 KiB
@@ -69,7 +69,12 @@ fn init_user_stack(uspace: &mut AddrSpace, populating: bool) -> io::Result<VirtA
 }
 
 #[register_trap_handler(PAGE_FAULT)]
-fn handle_page_fault(vaddr: VirtAddr, access_flags: MappingFlags, is_user: bool) -> bool {
+fn handle_page_fault(
+    vaddr: VirtAddr,
+    access_flags: MappingFlags,
+    is_user: bool,
+    _ctx: &mut FaultContext,
+) -> bool {
     if is_user {
         if !axtask::current()
             .task_ext()